@@ -0,0 +1,165 @@
+//! Records the compiler and codegen settings so reports can say what built
+//! the binary. See `src/build_info.rs`.
+
+use std::env;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let verbose = Command::new(&rustc)
+        .arg("-vV")
+        .output()
+        .ok()
+        .filter(|out| out.status.success())
+        .map(|out| String::from_utf8_lossy(&out.stdout).trim().to_owned())
+        .unwrap_or_default();
+    let field = |name: &str| {
+        verbose
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(|value| value.trim().to_owned())
+            .unwrap_or_else(|| "unknown".into())
+    };
+    let version = verbose.lines().next().unwrap_or("unknown").to_owned();
+
+    let var = |name: &str| env::var(name).unwrap_or_default();
+    let profile = var("PROFILE");
+    // `PROFILE` is only `debug` or `release`; the profile's own name is the
+    // directory cargo builds it in, `<target-dir>/[<triple>/]<name>/build/
+    // <package>/out`, with `dev` built in `debug`.
+    let out_dir = PathBuf::from(var("OUT_DIR"));
+    let profile_name = out_dir
+        .ancestors()
+        .nth(3)
+        .and_then(|dir| dir.file_name()?.to_str())
+        .map(|name| match name {
+            "debug" => "dev",
+            name => name,
+        });
+    // Cargo doesn't tell build scripts about `lto` or `codegen-units`, so
+    // record the rustflags and environment overrides, which is how the
+    // matrix runner sets them, and failing those the manifest's profile.
+    let profile_var = |setting: &str| {
+        let profile = profile_name?.to_uppercase().replace('-', "_");
+        let name = format!("CARGO_PROFILE_{profile}_{setting}");
+        println!("cargo::rerun-if-env-changed={name}");
+        env::var(name).ok()
+    };
+    // Read both up front: each must be watched even when rustflags win.
+    let profile_lto = profile_var("LTO");
+    let profile_codegen_units = profile_var("CODEGEN_UNITS");
+    let rustflags: Vec<String> = var("CARGO_ENCODED_RUSTFLAGS")
+        .split('\x1f')
+        .filter(|flag| !flag.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let mut codegen_opts = Vec::new();
+    let mut flags = rustflags.iter();
+    while let Some(flag) = flags.next() {
+        if flag == "-C" {
+            codegen_opts.extend(flags.next().map(String::as_str));
+        } else if let Some(opt) = flag.strip_prefix("-C") {
+            codegen_opts.push(opt);
+        }
+    }
+    let codegen_flag = |key: &str| {
+        codegen_opts
+            .iter()
+            .rev()
+            .find_map(|opt| opt.strip_prefix(key)?.strip_prefix('='))
+            .map(str::to_owned)
+    };
+    let manifest = PathBuf::from(var("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    println!("cargo::rerun-if-changed={}", manifest.display());
+    let manifest = std::fs::read_to_string(manifest).unwrap_or_default();
+    let manifest_setting = |key: &str| manifest_setting(&manifest, profile_name?, key);
+    let unknown = || "unknown (not set by rustflags, environment or Cargo.toml)".to_owned();
+    let lto = codegen_flag("lto")
+        .or(profile_lto)
+        .or_else(|| manifest_setting("lto"))
+        .unwrap_or_else(unknown);
+    let codegen_units = codegen_flag("codegen-units")
+        .or(profile_codegen_units)
+        .or_else(|| manifest_setting("codegen-units"))
+        .unwrap_or_else(unknown);
+    let target_features: Vec<String> = var("CARGO_CFG_TARGET_FEATURE")
+        .split(',')
+        .filter(|f| !f.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(k, _)| Some(k.strip_prefix("CARGO_FEATURE_")?.to_lowercase()))
+        .collect();
+    features.sort();
+
+    let mut out = String::from("BuildInfo {\n");
+    let mut str_field = |name: &str, value: &str| {
+        let _ = writeln!(out, "    {name}: {value:?},");
+    };
+    str_field("rustc_version", &version);
+    str_field("rustc_verbose", &verbose);
+    str_field("rustc_release", &field("release"));
+    str_field("rustc_commit", &field("commit-hash"));
+    str_field("llvm_version", &field("LLVM version"));
+    str_field("host", &field("host"));
+    str_field("target", &var("TARGET"));
+    str_field("profile", &profile);
+    str_field("opt_level", &var("OPT_LEVEL"));
+    str_field("lto", &lto);
+    str_field("codegen_units", &codegen_units);
+    let _ = writeln!(
+        out,
+        "    debug_assertions: {},",
+        env::var_os("CARGO_CFG_DEBUG_ASSERTIONS").is_some()
+    );
+    let _ = writeln!(out, "    target_features: &{target_features:?},");
+    let _ = writeln!(out, "    rustflags: &{rustflags:?},");
+    let _ = writeln!(out, "    features: &{features:?},");
+    out.push('}');
+
+    std::fs::write(out_dir.join("build_info.rs"), out).expect("write build_info.rs");
+    println!("cargo::rerun-if-changed=build.rs");
+    println!("cargo::rerun-if-env-changed=CARGO_ENCODED_RUSTFLAGS");
+}
+
+/// `key` in the manifest's `[profile.<profile>]` table, or in the profiles
+/// it inherits from. This scans lines rather than parsing TOML, so it only
+/// sees keys written one per line under the table's own header.
+fn manifest_setting(manifest: &str, profile: &str, key: &str) -> Option<String> {
+    let mut profile = profile.to_owned();
+    // Inheritance is at most a few profiles deep; the bound stops a cycle.
+    for _ in 0..8 {
+        let table = profile_table(manifest, &profile);
+        let get = |key: &str| table.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        if let Some(value) = get(key) {
+            return Some(value);
+        }
+        profile = match (get("inherits"), profile.as_str()) {
+            (Some(parent), _) => parent,
+            (None, "test") => "dev".to_owned(),
+            (None, "bench") => "release".to_owned(),
+            (None, _) => return None,
+        };
+    }
+    None
+}
+
+/// The `key = value` lines under `[profile.<profile>]`, with string values
+/// unquoted.
+fn profile_table(manifest: &str, profile: &str) -> Vec<(String, String)> {
+    let header = format!("[profile.{profile}]");
+    let mut table = Vec::new();
+    let mut inside = false;
+    for line in manifest.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.starts_with('[') {
+            inside = line == header;
+        } else if inside && let Some((key, value)) = line.split_once('=') {
+            let value = value.trim().trim_matches('"');
+            table.push((key.trim().to_owned(), value.to_owned()));
+        }
+    }
+    table
+}
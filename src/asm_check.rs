@@ -0,0 +1,1468 @@
+//! The `check-asm` command: a static check of the compiled sampling loop
+//! for signs that the `Choice` from `ct_lt` was constant-folded or turned
+//! into a branch.
+//!
+//! The check reads x86_64 (AT&T syntax) or aarch64 assembly, so it needs
+//! neither the target hardware nor an emulator. For every outermost loop in
+//! a matching function it asks:
+//!
+//! - does the loop exit at all?
+//! - does it still compute a borrow (`sub`/`sbb`, `subs`/`sbcs`, `cmp`)?
+//! - does some exit branch depend on that borrow, tracing registers, flags
+//!   and stack slots backwards through the loop? A call to `ct_lt` or
+//!   `sbb` counts as a borrow; an exit on the return value of any other
+//!   call is opaque, since the comparison may be in the callee, and makes
+//!   the loop inconclusive rather than a pass or a failure.
+//! - is the borrow turned into a mask (`sbb`, `setb`, `cmov`, `cset`,
+//!   `csel`, ...) or only ever branched on?
+//!
+//! It is a heuristic: a pass means the shape looks right, not that the
+//! code is constant-time.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::build_info::build_info;
+use crate::emit::{self, Function};
+use crate::json;
+use crate::matrix::{self, MatrixOptions};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+
+/// Process exit code used when nothing failed but some loop only exits on
+/// the result of a call the check cannot see into.
+pub const EXIT_INCONCLUSIVE: i32 = 9;
+
+/// Options for the `check-asm` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CheckAsmOptions {
+    /// Assembly files to check; when empty, every configuration of `build`
+    /// is compiled and checked instead.
+    pub asm: Vec<PathBuf>,
+    /// Toolchains and profiles to compile, as for `matrix`.
+    pub build: MatrixOptions,
+    /// Substrings of the symbols to check.
+    pub functions: Vec<String>,
+}
+
+impl Default for CheckAsmOptions {
+    fn default() -> Self {
+        Self {
+            asm: Vec::new(),
+            build: MatrixOptions {
+                opt_levels: vec!["3".into()],
+                ..MatrixOptions::default()
+            },
+            functions: vec!["random_mod".into()],
+        }
+    }
+}
+
+/// Instruction set of the assembly being checked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+}
+
+impl Arch {
+    /// AT&T syntax marks every register with `%`.
+    fn detect(asm: &str) -> Self {
+        if asm.contains('%') {
+            Self::X86_64
+        } else {
+            Self::Aarch64
+        }
+    }
+}
+
+/// One parsed instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Insn {
+    /// 1-based line within the function.
+    pub line: usize,
+    pub mnemonic: String,
+    pub operands: Vec<String>,
+}
+
+impl Insn {
+    fn text(&self) -> String {
+        if self.operands.is_empty() {
+            self.mnemonic.clone()
+        } else {
+            format!("{} {}", self.mnemonic, self.operands.join(", "))
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Item {
+    Label(String),
+    Insn(Insn),
+}
+
+/// Something an instruction reads or writes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum Loc {
+    Reg(String),
+    Flags,
+    /// A memory operand, keyed by its text.
+    Mem(String),
+}
+
+#[derive(Debug, Default)]
+struct Effects {
+    reads: Vec<Loc>,
+    writes: Vec<Loc>,
+}
+
+/// Splits operands at top-level commas.
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut depth = 0i32;
+    let mut current = String::new();
+    for c in text.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                operands.push(current.trim().to_owned());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        operands.push(current.trim().to_owned());
+    }
+    operands
+}
+
+fn parse(body: &str, arch: Arch) -> Vec<Item> {
+    let mut items = Vec::new();
+    for (i, raw) in body.lines().enumerate() {
+        let comment = match arch {
+            Arch::X86_64 => raw.find('#'),
+            Arch::Aarch64 => raw.find("//"),
+        };
+        let line = raw[..comment.unwrap_or(raw.len())].trim();
+        if line.is_empty() {
+            continue;
+        }
+        let label = line
+            .strip_suffix(':')
+            .filter(|l| !l.contains(char::is_whitespace));
+        if let Some(label) = label {
+            items.push(Item::Label(label.trim_matches('"').to_owned()));
+            continue;
+        }
+        if line.starts_with('.') {
+            continue;
+        }
+        let (mnemonic, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        items.push(Item::Insn(Insn {
+            line: i + 1,
+            mnemonic: mnemonic.to_ascii_lowercase(),
+            operands: split_operands(rest),
+        }));
+    }
+    items
+}
+
+const X86_CONDITIONS: [&str; 30] = [
+    "o", "no", "b", "c", "nae", "ae", "nb", "nc", "e", "z", "ne", "nz", "be", "na", "a", "nbe",
+    "s", "ns", "p", "pe", "np", "po", "l", "nge", "ge", "nl", "le", "ng", "g", "nle",
+];
+/// x86 conditions that read the carry flag, which holds the borrow.
+const X86_CARRY: [&str; 10] = ["b", "c", "nae", "ae", "nb", "nc", "be", "na", "a", "nbe"];
+/// aarch64 conditions that read the carry flag.
+const A64_CARRY: [&str; 6] = ["lo", "hs", "cc", "cs", "ls", "hi"];
+
+/// The condition of an x86 `j<cc>`, `set<cc>` or `cmov<cc>`, allowing for
+/// an AT&T size suffix.
+fn x86_condition<'a>(mnemonic: &'a str, prefix: &str) -> Option<&'a str> {
+    let cc = mnemonic.strip_prefix(prefix)?;
+    if X86_CONDITIONS.contains(&cc) {
+        return Some(cc);
+    }
+    let cc = &cc[..cc.len().checked_sub(1)?];
+    X86_CONDITIONS.contains(&cc).then_some(cc)
+}
+
+/// Integer instructions that write the flags; vector and move instructions
+/// leave them alone.
+const X86_FLAG_WRITERS: [&str; 30] = [
+    "add", "adc", "sub", "sbb", "and", "andn", "or", "xor", "neg", "inc", "dec", "shl", "shr",
+    "sar", "sal", "rol", "ror", "rcl", "rcr", "imul", "mul", "div", "idiv", "bsr", "bsf", "tzcnt",
+    "lzcnt", "popcnt", "shld", "shrd",
+];
+
+/// The base of an x86 flag-writing mnemonic, allowing for an AT&T size
+/// suffix.
+fn x86_alu(mnemonic: &str) -> Option<&str> {
+    if X86_FLAG_WRITERS.contains(&mnemonic) {
+        return Some(mnemonic);
+    }
+    let base = mnemonic.strip_suffix(['b', 'w', 'l', 'q'])?;
+    X86_FLAG_WRITERS.contains(&base).then_some(base)
+}
+
+fn x86_register(name: &str) -> String {
+    let name = name.trim_start_matches('%');
+    if let Some(rest) = name.strip_prefix('r') {
+        let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+        if !digits.is_empty() {
+            return format!("r{digits}");
+        }
+    }
+    let base = match name {
+        "rax" | "eax" | "ax" | "al" | "ah" => "a",
+        "rbx" | "ebx" | "bx" | "bl" | "bh" => "b",
+        "rcx" | "ecx" | "cx" | "cl" | "ch" => "c",
+        "rdx" | "edx" | "dx" | "dl" | "dh" => "d",
+        "rsi" | "esi" | "si" | "sil" => "si",
+        "rdi" | "edi" | "di" | "dil" => "di",
+        "rbp" | "ebp" | "bp" | "bpl" => "bp",
+        "rsp" | "esp" | "sp" | "spl" => "sp",
+        "rip" | "eip" => "ip",
+        other => other,
+    };
+    base.to_owned()
+}
+
+fn a64_register(token: &str) -> Option<String> {
+    let token = token.trim();
+    if token == "sp" || token == "wsp" {
+        return Some("sp".to_owned());
+    }
+    let rest = token.strip_prefix(['x', 'w'])?;
+    (!rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit())).then(|| format!("r{rest}"))
+}
+
+/// Registers named in an operand.
+fn registers(operand: &str, arch: Arch) -> Vec<Loc> {
+    match arch {
+        Arch::X86_64 => operand
+            .split(|c: char| !(c.is_ascii_alphanumeric() || c == '%'))
+            .filter(|t| t.starts_with('%'))
+            .map(|t| Loc::Reg(x86_register(t)))
+            .collect(),
+        Arch::Aarch64 => operand
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter_map(a64_register)
+            .map(Loc::Reg)
+            .collect(),
+    }
+}
+
+fn is_memory(operand: &str, arch: Arch) -> bool {
+    match arch {
+        Arch::X86_64 => operand.contains('('),
+        Arch::Aarch64 => operand.starts_with('['),
+    }
+}
+
+fn memory_key(operand: &str) -> Loc {
+    Loc::Mem(operand.replace([' ', '!'], ""))
+}
+
+/// Adds what reading `operand` reads.
+fn read_operand(operand: &str, arch: Arch, reads: &mut Vec<Loc>) {
+    if is_memory(operand, arch) {
+        reads.push(memory_key(operand));
+    }
+    reads.extend(registers(operand, arch));
+}
+
+/// Adds what writing `operand` writes, and the address registers it reads.
+fn write_operand(operand: &str, arch: Arch, effects: &mut Effects) {
+    if is_memory(operand, arch) {
+        effects.writes.push(memory_key(operand));
+        effects.reads.extend(registers(operand, arch));
+    } else {
+        effects.writes.extend(registers(operand, arch));
+    }
+}
+
+fn x86_effects(insn: &Insn) -> Effects {
+    let arch = Arch::X86_64;
+    let m = insn.mnemonic.as_str();
+    let ops = &insn.operands;
+    let mut e = Effects::default();
+    let Some((dst, srcs)) = ops.split_last() else {
+        if m.starts_with("call") {
+            e.writes = ["a", "d", "c", "si", "di", "r8", "r9", "r10", "r11"]
+                .map(|r| Loc::Reg(r.into()))
+                .into();
+            e.writes.push(Loc::Flags);
+        }
+        return e;
+    };
+    if m.starts_with("call") {
+        e.reads = ["di", "si", "d", "c", "r8", "r9"]
+            .map(|r| Loc::Reg(r.into()))
+            .into();
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+        e.writes = ["a", "d", "c", "si", "di", "r8", "r9", "r10", "r11"]
+            .map(|r| Loc::Reg(r.into()))
+            .into();
+        e.writes.push(Loc::Flags);
+        return e;
+    }
+    if m.starts_with("jmp") {
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+        return e;
+    }
+    if x86_condition(m, "j").is_some() {
+        e.reads.push(Loc::Flags);
+        return e;
+    }
+    if x86_condition(m, "set").is_some() {
+        e.reads.push(Loc::Flags);
+        write_operand(dst, arch, &mut e);
+        return e;
+    }
+    if x86_condition(m, "cmov").is_some() {
+        e.reads.push(Loc::Flags);
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+        write_operand(dst, arch, &mut e);
+        return e;
+    }
+    if ["cmp", "test", "bt"].iter().any(|p| m.starts_with(p)) {
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+        e.writes.push(Loc::Flags);
+        return e;
+    }
+    if m.starts_with("push") {
+        read_operand(dst, arch, &mut e.reads);
+        return e;
+    }
+    if m.starts_with("pop") {
+        write_operand(dst, arch, &mut e);
+        return e;
+    }
+    if m.starts_with("lea") {
+        // Computes the address without reading the memory.
+        srcs.iter()
+            .for_each(|op| e.reads.extend(registers(op, arch)));
+        write_operand(dst, arch, &mut e);
+        return e;
+    }
+    if m.starts_with("mov") {
+        srcs.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+        write_operand(dst, arch, &mut e);
+        return e;
+    }
+    let alu = x86_alu(m);
+    let same = srcs.len() == 1 && srcs[0] == *dst;
+    if same && matches!(alu, Some("xor" | "sub")) {
+        // Zeroing idiom.
+        write_operand(dst, arch, &mut e);
+        e.writes.push(Loc::Flags);
+        return e;
+    }
+    if matches!(alu, Some("sbb" | "adc" | "rcl" | "rcr")) {
+        e.reads.push(Loc::Flags);
+    }
+    if !same || alu != Some("sbb") {
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+    }
+    write_operand(dst, arch, &mut e);
+    if alu.is_some() {
+        e.writes.push(Loc::Flags);
+    }
+    e
+}
+
+fn a64_effects(insn: &Insn) -> Effects {
+    let arch = Arch::Aarch64;
+    let m = insn.mnemonic.as_str();
+    let ops = &insn.operands;
+    let mut e = Effects::default();
+    let read_all = |e: &mut Effects, ops: &[String]| {
+        ops.iter()
+            .for_each(|op| read_operand(op, arch, &mut e.reads));
+    };
+    match m {
+        "b" | "ret" => {}
+        "br" => read_all(&mut e, ops),
+        "bl" | "blr" => {
+            e.reads = (0..8).map(|r| Loc::Reg(format!("r{r}"))).collect();
+            read_all(&mut e, ops);
+            e.writes = (0..19).map(|r| Loc::Reg(format!("r{r}"))).collect();
+            e.writes.push(Loc::Flags);
+        }
+        "cbz" | "cbnz" | "tbz" | "tbnz" => read_all(&mut e, &ops[..ops.len().min(1)]),
+        _ if m.starts_with("b.") => e.reads.push(Loc::Flags),
+        "cmp" | "cmn" | "tst" => {
+            read_all(&mut e, ops);
+            e.writes.push(Loc::Flags);
+        }
+        "ccmp" | "ccmn" => {
+            read_all(&mut e, ops);
+            e.reads.push(Loc::Flags);
+            e.writes.push(Loc::Flags);
+        }
+        _ if m.starts_with("st") => {
+            for op in ops {
+                if is_memory(op, arch) {
+                    write_operand(op, arch, &mut e);
+                } else {
+                    read_operand(op, arch, &mut e.reads);
+                }
+            }
+        }
+        _ if m.starts_with("ld") => {
+            for op in ops {
+                if is_memory(op, arch) {
+                    read_operand(op, arch, &mut e.reads);
+                } else {
+                    write_operand(op, arch, &mut e);
+                }
+            }
+        }
+        _ => {
+            if let Some((dst, srcs)) = ops.split_first() {
+                write_operand(dst, arch, &mut e);
+                read_all(&mut e, srcs);
+                if m == "movk" {
+                    read_operand(dst, arch, &mut e.reads);
+                }
+            }
+            if a64_reads_flags(m) {
+                e.reads.push(Loc::Flags);
+            }
+            if [
+                "adds", "subs", "adcs", "sbcs", "ands", "bics", "negs", "ngcs",
+            ]
+            .contains(&m)
+            {
+                e.writes.push(Loc::Flags);
+            }
+        }
+    }
+    e
+}
+
+const A64_CONDITIONAL: [&str; 10] = [
+    "csel", "csinc", "csinv", "csneg", "cset", "csetm", "cinc", "cinv", "cneg", "fcsel",
+];
+
+fn a64_reads_flags(m: &str) -> bool {
+    A64_CONDITIONAL.contains(&m) || ["adc", "adcs", "sbc", "sbcs", "ngc", "ngcs"].contains(&m)
+}
+
+fn effects(insn: &Insn, arch: Arch) -> Effects {
+    match arch {
+        Arch::X86_64 => x86_effects(insn),
+        Arch::Aarch64 => a64_effects(insn),
+    }
+}
+
+/// The condition an aarch64 conditional-select reads is its last operand.
+fn a64_condition(insn: &Insn) -> Option<&str> {
+    if let Some(cc) = insn.mnemonic.strip_prefix("b.") {
+        return Some(cc);
+    }
+    A64_CONDITIONAL
+        .contains(&insn.mnemonic.as_str())
+        .then(|| insn.operands.last().map(String::as_str))
+        .flatten()
+}
+
+fn is_call(insn: &Insn, arch: Arch) -> bool {
+    match arch {
+        Arch::X86_64 => insn.mnemonic.starts_with("call"),
+        Arch::Aarch64 => insn.mnemonic == "bl" || insn.mnemonic == "blr",
+    }
+}
+
+/// Out-of-line comparisons: `ct_lt` itself, or the subtraction it is built
+/// on when that was not inlined.
+const COMPARISONS: [&str; 3] = ["ct_lt", "sbb", "borrowing_sub"];
+
+/// Whether `insn` calls an out-of-line `ct_lt`, which then stands for the
+/// whole comparison; check it separately with `--functions ct_lt`.
+fn calls_comparison(insn: &Insn, arch: Arch) -> bool {
+    is_call(insn, arch)
+        && insn
+            .operands
+            .first()
+            .is_some_and(|f| COMPARISONS.iter().any(|c| f.contains(c)))
+}
+
+/// Whether `insn` consumes the borrow: a subtract-with-borrow, anything
+/// conditional on the carry flag, or a call to `ct_lt`.
+fn consumes_borrow(insn: &Insn, arch: Arch) -> bool {
+    let m = insn.mnemonic.as_str();
+    if calls_comparison(insn, arch) {
+        return true;
+    }
+    match arch {
+        Arch::X86_64 => {
+            m.starts_with("sbb")
+                || m.starts_with("adc")
+                || ["j", "set", "cmov"]
+                    .iter()
+                    .filter_map(|p| x86_condition(m, p))
+                    .any(|cc| X86_CARRY.contains(&cc))
+        }
+        Arch::Aarch64 => {
+            ["adc", "adcs", "sbc", "sbcs", "ngc", "ngcs"].contains(&m)
+                || a64_condition(insn).is_some_and(|cc| A64_CARRY.contains(&cc))
+        }
+    }
+}
+
+/// Whether `insn` computes a borrow: a flag-setting subtraction, or a call
+/// to `ct_lt`.
+fn produces_borrow(insn: &Insn, arch: Arch) -> bool {
+    let m = insn.mnemonic.as_str();
+    if calls_comparison(insn, arch) {
+        return true;
+    }
+    match arch {
+        Arch::X86_64 => ["sub", "sbb", "cmp", "neg"]
+            .iter()
+            .any(|p| m.starts_with(p)),
+        Arch::Aarch64 => ["subs", "sbcs", "cmp", "negs", "ngcs"].contains(&m),
+    }
+}
+
+/// Whether `insn` computes a borrow from two values. A comparison against
+/// a constant is a loop counter or a bounds check, not `ct_lt`.
+fn compares(insn: &Insn, arch: Arch) -> bool {
+    produces_borrow(insn, arch)
+        && !insn
+            .operands
+            .iter()
+            .any(|o| o.starts_with('$') || o.starts_with('#'))
+}
+
+fn is_branch(insn: &Insn, arch: Arch) -> bool {
+    let m = insn.mnemonic.as_str();
+    match arch {
+        Arch::X86_64 => m.starts_with('j'),
+        Arch::Aarch64 => ["b", "cbz", "cbnz", "tbz", "tbnz"].contains(&m) || m.starts_with("b."),
+    }
+}
+
+fn is_unconditional(insn: &Insn, arch: Arch) -> bool {
+    match arch {
+        Arch::X86_64 => insn.mnemonic.starts_with("jmp"),
+        Arch::Aarch64 => insn.mnemonic == "b",
+    }
+}
+
+/// Functions that never return. Unoptimized code has no trap after a call to
+/// one, so without this a bounds-check panic falls through into the loop.
+const NORETURN: [&str; 3] = ["panic", "unwrap_failed", "expect_failed"];
+
+/// Returns, traps, calls to panics and other instructions control never
+/// passes.
+fn ends_flow(insn: &Insn, arch: Arch) -> bool {
+    let m = insn.mnemonic.as_str();
+    if is_call(insn, arch) {
+        return insn
+            .operands
+            .first()
+            .is_some_and(|f| NORETURN.iter().any(|n| f.contains(n)));
+    }
+    m.starts_with("ret") || ["ud2", "hlt", "brk", "udf"].contains(&m)
+}
+
+/// What a check found wrong with a loop.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// No branch leaves the loop: the sampler can never return.
+    NoExit,
+    /// Nothing subtracts, so the comparison was folded away.
+    NoComparison,
+    /// No exit depends on a borrow; these are the exits.
+    ExitIgnoresBorrow { exits: Vec<String> },
+    /// The borrow is only ever branched on, never made into a mask.
+    BranchOnBorrow { branch: String },
+    /// The exit depends on what a call returned, and nothing in the loop
+    /// itself; whether the callee compares is not checked.
+    OpaqueExit { exit: String, call: String },
+}
+
+impl Finding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NoExit => "no-exit",
+            Self::NoComparison => "no-comparison",
+            Self::ExitIgnoresBorrow { .. } => "exit-ignores-borrow",
+            Self::BranchOnBorrow { .. } => "branch-on-borrow",
+            Self::OpaqueExit { .. } => "opaque-exit",
+        }
+    }
+
+    /// Whether the finding only says the check could not tell.
+    pub fn is_inconclusive(&self) -> bool {
+        matches!(self, Self::OpaqueExit { .. })
+    }
+
+    /// A readable account of what is wrong and why it matters.
+    pub fn explain(&self) -> String {
+        match self {
+            Self::NoExit => "no branch leaves the loop, so it can never return: \
+                             the exit on `ct_lt` was optimized out"
+                .to_owned(),
+            Self::NoComparison => "the code computes no borrow (no sub/sbb, subs/sbcs or cmp), \
+                                   so `ct_lt` was constant-folded away"
+                .to_owned(),
+            Self::ExitIgnoresBorrow { exits } => format!(
+                "no exit branch depends on a borrow; the loop leaves only through {}, \
+                 so whether it returns no longer follows from `ct_lt`",
+                exits.join(", ")
+            ),
+            Self::BranchOnBorrow { branch } => format!(
+                "the borrow is never turned into a mask (no sbb/setb/cmov or \
+                 cset/csel/sbc on it); the loop branches on the flag directly with \
+                 `{branch}`, so the `Choice` became a branch"
+            ),
+            Self::OpaqueExit { exit, call } => format!(
+                "the exit {exit} depends only on the result of {call}; the comparison, \
+                 if any, is in the callee, so whether the `Choice` survived is not known"
+            ),
+        }
+    }
+}
+
+/// A checked loop, or the body of a function without one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionReport {
+    pub looped: bool,
+    /// First and last line of the region within the function.
+    pub lines: (usize, usize),
+    pub findings: Vec<Finding>,
+    /// For a passing loop, the exit and the borrow it depends on.
+    pub evidence: Option<(String, String)>,
+}
+
+/// A checked function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FunctionReport {
+    pub name: String,
+    pub arch: Arch,
+    pub regions: Vec<RegionReport>,
+}
+
+impl FunctionReport {
+    pub fn passed(&self) -> bool {
+        self.regions.iter().all(|r| r.findings.is_empty())
+    }
+
+    /// Not passed, but only because some exit is opaque.
+    pub fn inconclusive(&self) -> bool {
+        !self.passed()
+            && self
+                .regions
+                .iter()
+                .flat_map(|r| &r.findings)
+                .all(Finding::is_inconclusive)
+    }
+
+    /// Neither passed nor inconclusive.
+    pub fn failed(&self) -> bool {
+        !self.passed() && !self.inconclusive()
+    }
+}
+
+/// The names of the functions in `reports` that failed, and of those that
+/// were inconclusive, each without repeats: instantiations share a name once
+/// hashes are stripped.
+pub fn failed_and_inconclusive(reports: &[FunctionReport]) -> (Vec<&str>, Vec<&str>) {
+    let names = |keep: fn(&FunctionReport) -> bool| {
+        let mut names: Vec<&str> = Vec::new();
+        for f in reports.iter().filter(|f| keep(f)) {
+            if !names.contains(&f.name.as_str()) {
+                names.push(&f.name);
+            }
+        }
+        names
+    };
+    (
+        names(FunctionReport::failed),
+        names(FunctionReport::inconclusive),
+    )
+}
+
+/// `check-asm`'s verdict on `reports` in a few words, for commands that run
+/// it as one of their steps.
+pub fn summary(reports: &[FunctionReport]) -> String {
+    let (failed, inconclusive) = failed_and_inconclusive(reports);
+    let mut parts = Vec::new();
+    if !failed.is_empty() {
+        parts.push(format!("FAIL: {}", failed.join(", ")));
+    }
+    if !inconclusive.is_empty() {
+        parts.push(format!("inconclusive: {}", inconclusive.join(", ")));
+    }
+    if parts.is_empty() {
+        format!("ok ({} functions)", reports.len())
+    } else {
+        parts.join("; ")
+    }
+}
+
+/// Adds `reports`' verdict to a JSON object: whether every function passed,
+/// and which failed and which were inconclusive.
+pub fn add_verdict(obj: json::Object, reports: &[FunctionReport]) -> json::Object {
+    let (failed, inconclusive) = failed_and_inconclusive(reports);
+    let strings = |names: Vec<&str>| {
+        json::array(names.into_iter().map(|name| {
+            let mut out = String::new();
+            json::write_str(&mut out, name);
+            out
+        }))
+    };
+    obj.u64("checked_functions", reports.len() as u64)
+        .bool("asm_passed", reports.iter().all(FunctionReport::passed))
+        .raw("asm_failed", &strings(failed))
+        .raw("asm_inconclusive", &strings(inconclusive))
+}
+
+/// A basic block: straight-line instructions and where control goes next.
+#[derive(Debug)]
+struct Block<'a> {
+    insns: Vec<&'a Insn>,
+    succs: Vec<usize>,
+    preds: Vec<usize>,
+}
+
+/// Splits `items` into basic blocks and links them.
+///
+/// Indirect jumps (jump tables) get no successors, so loops through them
+/// are not seen.
+fn blocks(items: &[Item], arch: Arch) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut labels: HashMap<&str, usize> = HashMap::new();
+    let mut open = false;
+    for item in items {
+        match item {
+            Item::Label(label) => {
+                let empty = blocks.last().is_some_and(|b| b.insns.is_empty());
+                if !empty {
+                    blocks.push(Block {
+                        insns: Vec::new(),
+                        succs: Vec::new(),
+                        preds: Vec::new(),
+                    });
+                }
+                labels.insert(label, blocks.len() - 1);
+                open = true;
+            }
+            Item::Insn(insn) => {
+                if !open {
+                    blocks.push(Block {
+                        insns: Vec::new(),
+                        succs: Vec::new(),
+                        preds: Vec::new(),
+                    });
+                    open = true;
+                }
+                blocks.last_mut().unwrap().insns.push(insn);
+                if is_branch(insn, arch) || ends_flow(insn, arch) {
+                    open = false;
+                }
+            }
+        }
+    }
+
+    for i in 0..blocks.len() {
+        let next = (i + 1 < blocks.len()).then_some(i + 1);
+        let succs: Vec<usize> = match blocks[i].insns.last() {
+            Some(insn) if ends_flow(insn, arch) => Vec::new(),
+            Some(insn) if is_branch(insn, arch) => {
+                let target = insn
+                    .operands
+                    .last()
+                    .and_then(|l| labels.get(l.as_str()).copied());
+                if is_unconditional(insn, arch) {
+                    target.into_iter().collect()
+                } else {
+                    target.into_iter().chain(next).collect()
+                }
+            }
+            _ => next.into_iter().collect(),
+        };
+        for &s in &succs {
+            blocks[s].preds.push(i);
+        }
+        blocks[i].succs = succs;
+    }
+    blocks
+}
+
+/// Strongly connected components of the block graph with a cycle: the
+/// function's outermost loops.
+fn loops(blocks: &[Block]) -> Vec<Vec<usize>> {
+    struct Tarjan<'b, 'a> {
+        blocks: &'b [Block<'a>],
+        index: Vec<Option<usize>>,
+        low: Vec<usize>,
+        on_stack: Vec<bool>,
+        stack: Vec<usize>,
+        next: usize,
+        sccs: Vec<Vec<usize>>,
+    }
+
+    impl Tarjan<'_, '_> {
+        fn visit(&mut self, v: usize) {
+            self.index[v] = Some(self.next);
+            self.low[v] = self.next;
+            self.next += 1;
+            self.stack.push(v);
+            self.on_stack[v] = true;
+            for &w in &self.blocks[v].succs {
+                match self.index[w] {
+                    None => {
+                        self.visit(w);
+                        self.low[v] = self.low[v].min(self.low[w]);
+                    }
+                    Some(index) if self.on_stack[w] => self.low[v] = self.low[v].min(index),
+                    Some(_) => {}
+                }
+            }
+            if Some(self.low[v]) == self.index[v] {
+                let mut scc = Vec::new();
+                while let Some(w) = self.stack.pop() {
+                    self.on_stack[w] = false;
+                    scc.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                scc.sort_unstable();
+                self.sccs.push(scc);
+            }
+        }
+    }
+
+    let n = blocks.len();
+    let mut tarjan = Tarjan {
+        blocks,
+        index: vec![None; n],
+        low: vec![0; n],
+        on_stack: vec![false; n],
+        stack: Vec::new(),
+        next: 0,
+        sccs: Vec::new(),
+    };
+    for v in 0..n {
+        if tarjan.index[v].is_none() {
+            tarjan.visit(v);
+        }
+    }
+    let mut sccs: Vec<Vec<usize>> = tarjan
+        .sccs
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || blocks[scc[0]].succs.contains(&scc[0]))
+        .collect();
+    sccs.sort();
+    sccs
+}
+
+/// The register a call returns a `bool` or `Choice` in.
+fn return_register(arch: Arch) -> Loc {
+    Loc::Reg(
+        match arch {
+            Arch::X86_64 => "a",
+            Arch::Aarch64 => "r0",
+        }
+        .into(),
+    )
+}
+
+/// What an exit branch was traced back to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Source<'a> {
+    /// An instruction consuming a borrow.
+    Borrow(&'a Insn),
+    /// A call whose result is not looked into, such as an `#[inline(never)]`
+    /// comparison under another name.
+    Call(&'a Insn),
+}
+
+/// Traces the locations the branch ending block `exit` reads backwards
+/// through the loop, across iterations, and on into the code before it,
+/// and returns the borrow-consuming instruction it reaches or, failing
+/// that, the first call whose returned value it reaches.
+///
+/// A borrow from before the loop is a comparison LLVM found invariant, as
+/// in a copy of the loop unswitched for a zero-bit modulus; it still
+/// decides the exit.
+fn trace<'a>(blocks: &[Block<'a>], exit: usize, arch: Arch) -> Option<Source<'a>> {
+    let branch = *blocks[exit].insns.last()?;
+    if consumes_borrow(branch, arch) {
+        return Some(Source::Borrow(branch));
+    }
+    let returned = return_register(arch);
+    let mut call = None;
+    let mut visited: HashSet<(usize, Loc)> = HashSet::new();
+    let start: HashSet<Loc> = effects(branch, arch).reads.into_iter().collect();
+    let mut work = vec![(exit, start, blocks[exit].insns.len() - 1)];
+    while let Some((b, mut deps, end)) = work.pop() {
+        for insn in blocks[b].insns[..end].iter().rev() {
+            let mut e = effects(insn, arch);
+            // A callee may write any stack slot it was given a pointer to.
+            if is_call(insn, arch) {
+                e.writes
+                    .extend(deps.iter().filter(|d| matches!(d, Loc::Mem(_))).cloned());
+            }
+            if !e.writes.iter().any(|w| deps.contains(w)) {
+                continue;
+            }
+            // Only the returned value and memory the callee was given carry
+            // anything out of a call; the other registers it clobbers hold
+            // garbage.
+            let carries = deps.contains(&returned)
+                || deps
+                    .iter()
+                    .any(|d| matches!(d, Loc::Mem(_)) && e.writes.contains(d));
+            if is_call(insn, arch) && !carries {
+                for w in &e.writes {
+                    deps.remove(w);
+                }
+                continue;
+            }
+            if consumes_borrow(insn, arch) {
+                return Some(Source::Borrow(insn));
+            }
+            if is_call(insn, arch) && deps.contains(&returned) {
+                call.get_or_insert(*insn);
+            }
+            for w in &e.writes {
+                deps.remove(w);
+            }
+            deps.extend(e.reads);
+        }
+        for &p in &blocks[b].preds {
+            let fresh: HashSet<Loc> = deps
+                .iter()
+                .filter(|&loc| visited.insert((p, loc.clone())))
+                .cloned()
+                .collect();
+            if !fresh.is_empty() {
+                work.push((p, fresh, blocks[p].insns.len()));
+            }
+        }
+    }
+    call.map(Source::Call)
+}
+
+fn cite(insn: &Insn) -> String {
+    format!("`{}` (line {})", insn.text(), insn.line)
+}
+
+fn check_loop(blocks: &[Block], scc: &[usize], arch: Arch) -> RegionReport {
+    let in_loop: HashSet<usize> = scc.iter().copied().collect();
+    let insns = || scc.iter().flat_map(|&b| blocks[b].insns.iter().copied());
+    let lines = (
+        insns().map(|i| i.line).min().unwrap_or(0),
+        insns().map(|i| i.line).max().unwrap_or(0),
+    );
+    let exits: Vec<usize> = scc
+        .iter()
+        .copied()
+        .filter(|&b| blocks[b].succs.iter().any(|s| !in_loop.contains(s)))
+        .collect();
+
+    let mut findings = Vec::new();
+    let mut evidence = None;
+    if exits.is_empty() {
+        findings.push(Finding::NoExit);
+    }
+    let traced: Vec<(usize, Source)> = exits
+        .iter()
+        .filter_map(|&x| Some((x, trace(blocks, x, arch)?)))
+        .collect();
+    let traced = traced
+        .iter()
+        .find(|(_, s)| matches!(s, Source::Borrow(_)))
+        .or(traced.first())
+        .copied();
+    let calls_out = matches!(traced, Some((_, Source::Call(_))));
+    if !calls_out && !insns().any(|i| compares(i, arch)) {
+        findings.push(Finding::NoComparison);
+    }
+    if !exits.is_empty() {
+        match traced {
+            None => findings.push(Finding::ExitIgnoresBorrow {
+                exits: exits
+                    .iter()
+                    .filter_map(|&x| blocks[x].insns.last().map(|i| cite(i)))
+                    .collect(),
+            }),
+            Some((exit, Source::Call(call))) => {
+                let exit = blocks[exit]
+                    .insns
+                    .last()
+                    .map(|i| cite(i))
+                    .unwrap_or_default();
+                findings.push(Finding::OpaqueExit {
+                    exit,
+                    call: cite(call),
+                });
+            }
+            Some((exit, Source::Borrow(borrow))) => {
+                let masked = !is_branch(borrow, arch)
+                    || insns().any(|i| consumes_borrow(i, arch) && !is_branch(i, arch));
+                if !masked {
+                    findings.push(Finding::BranchOnBorrow {
+                        branch: borrow.text(),
+                    });
+                }
+                let exit = blocks[exit]
+                    .insns
+                    .last()
+                    .map(|i| cite(i))
+                    .unwrap_or_default();
+                let mut borrow_cited = cite(borrow);
+                if !insns().any(|i| core::ptr::eq(i, borrow)) {
+                    borrow_cited.push_str(" before the loop");
+                }
+                evidence = Some((exit, borrow_cited));
+            }
+        }
+    }
+    RegionReport {
+        looped: true,
+        lines,
+        findings,
+        evidence,
+    }
+}
+
+/// The branches in `block` on a borrow from two values. A branch whose flags
+/// come from a comparison against a constant, in the same block, is control
+/// flow rather than a `Choice`; one whose flags come from elsewhere counts.
+fn branches_on_values<'a>(block: &Block<'a>, arch: Arch) -> Vec<&'a Insn> {
+    let mut branches = Vec::new();
+    let mut setter = None;
+    for &insn in &block.insns {
+        if consumes_borrow(insn, arch)
+            && is_branch(insn, arch)
+            && setter.is_none_or(|s| compares(s, arch))
+        {
+            branches.push(insn);
+        }
+        if effects(insn, arch).writes.contains(&Loc::Flags) {
+            setter = Some(insn);
+        }
+    }
+    branches
+}
+
+/// Checks a function without loops, such as an out-of-line `ct_lt`: it
+/// must compute a borrow and make it into a mask.
+fn check_straight(blocks: &[Block], arch: Arch) -> RegionReport {
+    let insns = || blocks.iter().flat_map(|b| b.insns.iter().copied());
+    let mut findings = Vec::new();
+    let masked = insns().any(|i| consumes_borrow(i, arch) && !is_branch(i, arch));
+    if !insns().any(|i| compares(i, arch)) {
+        // A function that only calls out, such as a dispatcher over
+        // samplers, leaves the comparing to its callees.
+        if !insns().any(|i| is_call(i, arch)) {
+            findings.push(Finding::NoComparison);
+        }
+    } else if let Some(branch) = blocks
+        .iter()
+        .flat_map(|b| branches_on_values(b, arch))
+        .find(|_| !masked)
+    {
+        findings.push(Finding::BranchOnBorrow {
+            branch: branch.text(),
+        });
+    }
+    RegionReport {
+        looped: false,
+        lines: (
+            insns().map(|i| i.line).min().unwrap_or(0),
+            insns().map(|i| i.line).max().unwrap_or(0),
+        ),
+        findings,
+        evidence: None,
+    }
+}
+
+/// Checks one function's assembly.
+pub fn check_function(function: &Function) -> FunctionReport {
+    let arch = Arch::detect(&function.body);
+    let items = parse(&function.body, arch);
+    let blocks = blocks(&items, arch);
+    let mut loops = loops(&blocks);
+    // Counted loops, which compare only against constants and call nothing,
+    // such as the walk over the modulus's limbs in unoptimized code, are not
+    // the sampling loop.
+    loops.retain(|scc| {
+        scc.iter().any(|&b| {
+            blocks[b]
+                .insns
+                .iter()
+                .any(|i| compares(i, arch) || is_call(i, arch))
+        })
+    });
+    let mut reports: Vec<RegionReport> = loops
+        .iter()
+        .map(|scc| check_loop(&blocks, scc, arch))
+        .collect();
+    // Loops that never compare (say, an inlined generator) are not the
+    // sampling loop, unless no loop compares at all.
+    if reports
+        .iter()
+        .any(|r| !r.findings.contains(&Finding::NoComparison))
+    {
+        reports.retain(|r| !r.findings.contains(&Finding::NoComparison));
+    }
+    if reports.is_empty() {
+        reports.push(check_straight(&blocks, arch));
+    }
+    FunctionReport {
+        name: function.name.clone(),
+        arch,
+        regions: reports,
+    }
+}
+
+/// Checks every function in `asm` whose symbol contains one of `patterns`.
+pub fn check(asm: &str, patterns: &[String]) -> Vec<FunctionReport> {
+    emit::extract_asm(asm, patterns)
+        .iter()
+        .map(check_function)
+        .collect()
+}
+
+/// A source of assembly and its reports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Checked {
+    /// A file path, or `toolchain profile` for a compiled configuration.
+    pub source: String,
+    pub reports: Result<Vec<FunctionReport>, String>,
+}
+
+fn region_object(l: &RegionReport) -> String {
+    let findings = l.findings.iter().map(|f| {
+        json::Object::new()
+            .str("finding", f.name())
+            .str("explanation", &f.explain())
+            .finish()
+    });
+    let obj = json::Object::new()
+        .bool("loop", l.looped)
+        .u64("first_line", l.lines.0 as u64)
+        .u64("last_line", l.lines.1 as u64)
+        .raw("findings", &json::array(findings));
+    match &l.evidence {
+        Some((exit, borrow)) => obj.str("exit", exit).str("borrow", borrow).finish(),
+        None => obj.finish(),
+    }
+}
+
+fn function_object(source: &str, f: &FunctionReport) -> json::Object {
+    json::Object::new()
+        .str("source", source)
+        .str("function", &f.name)
+        .str(
+            "arch",
+            match f.arch {
+                Arch::X86_64 => "x86_64",
+                Arch::Aarch64 => "aarch64",
+            },
+        )
+        .bool("passed", f.passed())
+        .bool("inconclusive", f.inconclusive())
+        .raw("regions", &json::array(f.regions.iter().map(region_object)))
+}
+
+fn print_text(checked: &[Checked]) {
+    for c in checked {
+        let reports = match &c.reports {
+            Ok(reports) => reports,
+            Err(err) => {
+                println!("{}: error: {err}", c.source);
+                continue;
+            }
+        };
+        if reports.is_empty() {
+            println!("{}: no matching functions", c.source);
+        }
+        for (i, f) in reports.iter().enumerate() {
+            let verdict = if f.passed() {
+                "ok"
+            } else if f.inconclusive() {
+                "inconclusive"
+            } else {
+                "FAIL"
+            };
+            println!("{}: {} #{i}: {verdict}", c.source, f.name);
+            for l in &f.regions {
+                let (first, last) = l.lines;
+                let kind = if l.looped { "loop" } else { "no loop; body" };
+                match &l.evidence {
+                    Some((exit, borrow)) => println!(
+                        "  {kind} at lines {first}-{last}: exit {exit} depends on {borrow}"
+                    ),
+                    None => println!("  {kind} at lines {first}-{last}"),
+                }
+                for finding in &l.findings {
+                    println!("    {}: {}", finding.name(), finding.explain());
+                }
+            }
+        }
+    }
+}
+
+/// Runs the check and prints its findings, returning the process exit code.
+pub fn check_asm(opts: &CheckAsmOptions) -> i32 {
+    let mut checked = Vec::new();
+    if opts.asm.is_empty() {
+        let build = &opts.build;
+        let profiles = build.profiles();
+        if profiles.is_empty() {
+            eprintln!("check-asm has no profiles");
+            return EXIT_USAGE;
+        }
+        for toolchain in matrix::toolchains(&build.toolchains) {
+            let name = toolchain.as_deref().unwrap_or("default");
+            for profile in &profiles {
+                eprintln!("emitting {name} {}", profile.label());
+                let reports = emit::emit_build(
+                    toolchain.as_deref(),
+                    None,
+                    profile,
+                    &build.source,
+                    &build.target_dir(name, profile),
+                    build.offline,
+                    build.build_timeout,
+                )
+                .map(|(_, asm)| check(&asm, &opts.functions));
+                checked.push(Checked {
+                    source: format!("{name} {}", profile.label()),
+                    reports,
+                });
+            }
+        }
+    } else {
+        for path in &opts.asm {
+            let reports = fs::read_to_string(path)
+                .map(|asm| check(&asm, &opts.functions))
+                .map_err(|err| err.to_string());
+            checked.push(Checked {
+                source: path.display().to_string(),
+                reports,
+            });
+        }
+    }
+
+    match opts.build.format {
+        Format::Text => print_text(&checked),
+        Format::Json | Format::Ndjson => {
+            let functions = checked.iter().flat_map(|c| {
+                c.reports
+                    .iter()
+                    .flatten()
+                    .map(|f| function_object(&c.source, f).finish())
+            });
+            if opts.build.format == Format::Json {
+                let errors = checked.iter().filter_map(|c| {
+                    let err = c.reports.as_ref().err()?;
+                    Some(
+                        json::Object::new()
+                            .str("source", &c.source)
+                            .str("error", err)
+                            .finish(),
+                    )
+                });
+                let report = json::Object::new()
+                    .raw("build", &build_info().to_json())
+                    .raw("functions", &json::array(functions))
+                    .raw("errors", &json::array(errors))
+                    .finish();
+                println!("{report}");
+            } else {
+                functions.for_each(|f| println!("{f}"));
+            }
+        }
+    }
+
+    let reports = checked.iter().map(|c| c.reports.as_ref());
+    let functions = || reports.clone().flatten().flatten();
+    if reports.clone().any(|r| r.is_err()) {
+        EXIT_USAGE
+    } else if functions().any(FunctionReport::failed) {
+        EXIT_DIVERGED
+    } else if functions().any(FunctionReport::inconclusive) {
+        EXIT_INCONCLUSIVE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X86_64: &str = include_str!("../testdata/asm/x86_64-random_mod.s");
+    const AARCH64: &str = include_str!("../testdata/asm/aarch64-random_mod.s");
+    const AARCH64_MACHO: &str = include_str!("../testdata/asm/aarch64-apple-darwin-random_mod.s");
+
+    /// The `Shape::InlineNever` loop, which compares in a callee.
+    const X86_64_INLINE_NEVER: &str = include_str!("../testdata/asm/x86_64-inline-never.s");
+
+    fn check_one(asm: &str) -> FunctionReport {
+        check_matching(asm, "random_mod")
+    }
+
+    fn check_matching(asm: &str, pattern: &str) -> FunctionReport {
+        let mut reports = check(asm, &[pattern.into()]);
+        assert_eq!(reports.len(), 1);
+        reports.remove(0)
+    }
+
+    fn findings(report: &FunctionReport) -> Vec<&'static str> {
+        report
+            .regions
+            .iter()
+            .flat_map(|r| r.findings.iter().map(Finding::name))
+            .collect()
+    }
+
+    #[test]
+    fn good_x86_64_loop_passes() {
+        let report = check_one(X86_64);
+        assert_eq!(report.arch, Arch::X86_64);
+        assert!(report.passed(), "{report:?}");
+        let (_, borrow) = report.regions[0].evidence.clone().unwrap();
+        assert!(borrow.starts_with("`sbbq $0, %rdx`"), "{borrow}");
+    }
+
+    #[test]
+    fn good_aarch64_loop_passes() {
+        let report = check_one(AARCH64);
+        assert_eq!(report.arch, Arch::Aarch64);
+        assert!(report.passed(), "{report:?}");
+        let (exit, borrow) = report.regions[0].evidence.clone().unwrap();
+        assert!(exit.starts_with("`tbnz w0, #0, .LBB1_6`"), "{exit}");
+        assert!(borrow.starts_with("`sbc x12, x12, xzr`"), "{borrow}");
+    }
+
+    #[test]
+    fn mach_o_function_ends_at_its_own_end() {
+        let functions = emit::extract_asm(AARCH64_MACHO, &["random_mod".into()]);
+        assert_eq!(functions.len(), 1);
+        assert!(functions[0].body.ends_with("Lfunc_end1:\n"));
+        let report = check_one(AARCH64_MACHO);
+        assert!(report.passed(), "{report:?}");
+        assert_eq!(report.regions.len(), 1);
+    }
+
+    #[test]
+    fn call_result_is_opaque() {
+        let report = check_matching(X86_64_INLINE_NEVER, "shapes");
+        assert!(!report.passed(), "{report:?}");
+        assert!(report.inconclusive(), "{report:?}");
+        assert_eq!(findings(&report), ["opaque-exit"]);
+        let Finding::OpaqueExit { call, .. } = &report.regions[0].findings[0] else {
+            unreachable!();
+        };
+        assert!(call.starts_with("`callq *%r15`"), "{call}");
+    }
+
+    #[test]
+    fn shapes_are_left_out_unless_asked_for() {
+        assert!(check(X86_64_INLINE_NEVER, &["random_mod".into()]).is_empty());
+        let patterns = ["random_mod".into(), "6shapes5Shape".into()];
+        assert_eq!(check(X86_64_INLINE_NEVER, &patterns).len(), 1);
+    }
+
+    #[test]
+    fn inconclusive_is_listed_apart_from_failures() {
+        let ok = check_one(X86_64);
+        let opaque = check_matching(X86_64_INLINE_NEVER, "shapes");
+        let broken = X86_64.replace("\ttestb\t%al, %al\n\tjne\t.LBB1_6\n", "");
+        let failed = check_one(&broken);
+        assert!(!ok.failed() && !ok.inconclusive());
+        assert!(!opaque.failed() && opaque.inconclusive());
+        assert!(failed.failed() && !failed.inconclusive());
+
+        let reports = [ok.clone(), opaque.clone()];
+        assert_eq!(summary(&reports), format!("inconclusive: {}", opaque.name));
+        let reports = [ok, opaque.clone(), failed.clone(), opaque.clone()];
+        assert_eq!(
+            failed_and_inconclusive(&reports),
+            (vec![failed.name.as_str()], vec![opaque.name.as_str()])
+        );
+        assert_eq!(
+            summary(&reports),
+            format!("FAIL: {}; inconclusive: {}", failed.name, opaque.name)
+        );
+        let json = add_verdict(json::Object::new(), &reports).finish();
+        assert!(json.contains("\"asm_passed\":false"), "{json}");
+        let listed = format!("\"asm_inconclusive\":[\"{}\"]", opaque.name);
+        assert!(json.contains(&listed), "{json}");
+    }
+
+    #[test]
+    fn exit_on_generator_error_only_fails() {
+        let asm = X86_64.replace("\ttestb\t%al, %al\n\tjne\t.LBB1_6\n", "");
+        assert_eq!(findings(&check_one(&asm)), ["exit-ignores-borrow"]);
+    }
+
+    #[test]
+    fn loop_without_exit_fails() {
+        let asm = AARCH64
+            .replace("\ttbnz\tw0, #0, .LBB1_6\n", "")
+            .replace("\tb.eq\t.LBB1_2\n", "\tb\t.LBB1_2\n");
+        assert!(findings(&check_one(&asm)).contains(&"no-exit"));
+    }
+
+    #[test]
+    fn branch_on_borrow_fails() {
+        let asm = "\
+_ZN12subtle_repro10random_mod17hE:
+.LBB0_1:
+	callq	next_u64
+	cmpq	%rax, %rbx
+	jbe	.LBB0_1
+	retq
+";
+        assert_eq!(findings(&check_one(asm)), ["branch-on-borrow"]);
+    }
+
+    #[test]
+    fn counted_loop_and_bounds_check_pass() {
+        let asm = "\
+_ZN12subtle_repro10random_mod17hE:
+	xorl	%ecx, %ecx
+	xorl	%eax, %eax
+.LBB0_1:
+	cmpq	$4, %rcx
+	jae	.LBB0_3
+	cmpq	$5, %rcx
+	jae	.LBB0_4
+	orq	(%rdi,%rcx,8), %rax
+	incq	%rcx
+	jmp	.LBB0_1
+.LBB0_3:
+	cmpq	%rsi, %rax
+	sbbq	%rax, %rax
+	retq
+.LBB0_4:
+	callq	_ZN4core9panicking18panic_bounds_check17hE
+";
+        let report = check_one(asm);
+        assert!(report.passed(), "{report:?}");
+    }
+}
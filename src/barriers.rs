@@ -0,0 +1,196 @@
+//! The `barriers` command: runs the sampler with each [`Variant`] of the
+//! comparison, to see which optimization barrier prevents the hang.
+//!
+//! Each variant is tried twice: in-process with an attempt budget and every
+//! decision checked by the oracle, and as the faithful unbounded loop in a
+//! child `run --barrier` process under the watchdog.
+
+use std::process::Command;
+use std::time::Duration;
+
+use rand_core::RngCore;
+
+use crate::build_info::build_info;
+use crate::inline_ct::Variant;
+use crate::oracle::DivergenceHex;
+use crate::process::{self, Exit};
+use crate::report::Format;
+use crate::rng::{RngKind, with_rng};
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE, ModulusSpec};
+use crate::watchdog::EXIT_STALLED;
+use crate::{SamplingError, hex, json, with_limbs};
+
+/// Options for the `barriers` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BarriersOptions {
+    pub seed: u64,
+    pub limbs: usize,
+    pub modulus: ModulusSpec,
+    /// Must be seedable, so child processes can recreate it.
+    pub rng: RngKind,
+    pub max_attempts: u64,
+    /// Watchdog deadline for each child process.
+    pub timeout: Duration,
+    pub format: Format,
+}
+
+impl Default for BarriersOptions {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            limbs: 5,
+            modulus: ModulusSpec::Special,
+            rng: RngKind::ChaCha8,
+            max_attempts: 1 << 20,
+            timeout: Duration::from_secs(10),
+            format: Format::Text,
+        }
+    }
+}
+
+/// Result of the bounded, oracle-checked run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Checked {
+    Pass,
+    Diverged(DivergenceHex),
+    Exhausted,
+}
+
+impl Checked {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pass => "pass",
+            Self::Diverged(_) => "diverged",
+            Self::Exhausted => "exhausted",
+        }
+    }
+}
+
+/// Result of the unbounded run in a child process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Unbounded {
+    Returned,
+    Hung,
+    Failed(String),
+}
+
+impl Unbounded {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Returned => "returned",
+            Self::Hung => "hung",
+            Self::Failed(why) => why,
+        }
+    }
+}
+
+fn checked<const L: usize, R: RngCore>(
+    variant: Variant,
+    mut rng: R,
+    opts: &BarriersOptions,
+) -> Result<Checked, String> {
+    let n = opts
+        .modulus
+        .resolve::<L, _>(&mut rng)
+        .map_err(|err| err.to_string())?;
+    Ok(
+        match variant.try_random_mod(&mut rng, &n, opts.max_attempts) {
+            Ok(_) => Checked::Pass,
+            Err(SamplingError::Diverged(d)) => Checked::Diverged((&d).into()),
+            Err(SamplingError::Exhausted { .. }) => Checked::Exhausted,
+        },
+    )
+}
+
+fn unbounded(variant: Variant, opts: &BarriersOptions) -> Unbounded {
+    let exe = match std::env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return Unbounded::Failed(format!("no current exe: {err}")),
+    };
+    let mut command = Command::new(exe);
+    command
+        .arg("run")
+        .args(["--barrier", variant.name()])
+        .args(["--seed", &opts.seed.to_string()])
+        .args(["--limbs", &opts.limbs.to_string()])
+        .args(["--rng", opts.rng.name()])
+        .args([
+            "--watchdog-secs",
+            &opts.timeout.as_secs().max(1).to_string(),
+        ]);
+    if let ModulusSpec::Hex(words) = &opts.modulus {
+        command.args(["--modulus", &hex::encode_words(words)]);
+    }
+    // The watchdog should fire first; this is a backstop.
+    match process::run(&mut command, opts.timeout * 2 + Duration::from_secs(5)) {
+        Ok(out) => match out.exit {
+            Exit::Code(0) => Unbounded::Returned,
+            Exit::Code(EXIT_STALLED) | Exit::TimedOut => Unbounded::Hung,
+            Exit::Code(code) => Unbounded::Failed(format!("exit {code}")),
+            Exit::Signaled => Unbounded::Failed("signaled".into()),
+        },
+        Err(err) => Unbounded::Failed(err.to_string()),
+    }
+}
+
+/// Runs every variant and prints a grid, returning the process exit code.
+pub fn barriers(opts: &BarriersOptions) -> i32 {
+    let mut results = Vec::new();
+    for variant in Variant::ALL {
+        let checked = with_rng!(opts.rng, opts.seed, |rng| {
+            with_limbs!(opts.limbs, |L| checked::<L, _>(variant, rng, opts), _ => {
+                Err(format!("unsupported limb count {}", opts.limbs))
+            })
+        });
+        let checked = match checked {
+            Ok(checked) => checked,
+            Err(err) => {
+                eprintln!("{err}");
+                return EXIT_USAGE;
+            }
+        };
+        results.push((variant, checked, unbounded(variant, opts)));
+    }
+
+    match opts.format {
+        Format::Text => {
+            println!("{:<10}  {:<9}  unbounded", "barrier", "oracle");
+            for (variant, checked, unbounded) in &results {
+                println!("{variant:<10}  {:<9}  {}", checked.name(), unbounded.name());
+                if let Checked::Diverged(d) = checked {
+                    println!("{:<10}  x = {}", "", d.candidate);
+                    println!("{:<10}  n = {}", "", d.modulus);
+                }
+            }
+        }
+        Format::Json | Format::Ndjson => {
+            let rows = results.iter().map(|(variant, checked, unbounded)| {
+                let obj = json::Object::new()
+                    .str("barrier", variant.name())
+                    .str("oracle", checked.name())
+                    .str("unbounded", unbounded.name());
+                match checked {
+                    Checked::Diverged(d) => obj.raw("divergence", &d.to_json()).finish(),
+                    _ => obj.finish(),
+                }
+            });
+            if opts.format == Format::Json {
+                let report = json::Object::new()
+                    .raw("build", &build_info().to_json())
+                    .u64("seed", opts.seed)
+                    .u64("limbs", opts.limbs as u64)
+                    .str("rng", opts.rng.name())
+                    .raw("barriers", &json::array(rows))
+                    .finish();
+                println!("{report}");
+            } else {
+                rows.for_each(|row| println!("{row}"));
+            }
+        }
+    }
+
+    let bad = results.iter().any(|(_, checked, unbounded)| {
+        *checked != Checked::Pass || *unbounded != Unbounded::Returned
+    });
+    if bad { EXIT_DIVERGED } else { 0 }
+}
@@ -0,0 +1,438 @@
+//! The `bisect` command: finds the first locally installed toolchain whose
+//! release build of the reproduction hangs or diverges.
+//!
+//! Only toolchains rustup already has are used, and cargo always runs
+//! `--offline`, so a bisection never touches the network. Every step is
+//! appended to a log as it finishes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::build_info::build_info;
+use crate::json;
+use crate::matrix::{self, Cell, MatrixOptions, Outcome};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+use crate::toolchain::{self, Version};
+
+/// Options for the `bisect` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BisectOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`.
+    pub build: MatrixOptions,
+    /// Defaults to `bisect.log` in the matrix target directory.
+    pub log: Option<PathBuf>,
+}
+
+impl Default for BisectOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions {
+                opt_levels: vec!["3".into()],
+                offline: true,
+                ..MatrixOptions::default()
+            },
+            log: None,
+        }
+    }
+}
+
+/// How a toolchain fared across every configured profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Good,
+    /// Some profile hung, diverged or exhausted its budget.
+    Bad,
+    /// Some profile failed to build or run, so the toolchain says nothing.
+    Skip,
+}
+
+impl Verdict {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Good => "good",
+            Self::Bad => "bad",
+            Self::Skip => "skip",
+        }
+    }
+
+    fn of(cells: &[Cell]) -> Self {
+        let outcomes = || cells.iter().map(|c| &c.outcome);
+        if outcomes().any(|o| matches!(o, Outcome::Hung | Outcome::Diverged | Outcome::Exhausted)) {
+            Self::Bad
+        } else if outcomes().all(|o| *o == Outcome::Pass) {
+            Self::Good
+        } else {
+            Self::Skip
+        }
+    }
+}
+
+/// One tested toolchain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    pub toolchain: String,
+    pub version: Version,
+    pub verdict: Verdict,
+    pub cells: Vec<Cell>,
+}
+
+/// Where the bisection ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conclusion {
+    /// `first_bad` is the oldest failing toolchain; `last_good` the newest
+    /// passing one before it. Toolchains between them that could not be
+    /// judged are `skipped`; the first bad one may be any of those instead.
+    Found {
+        last_good: String,
+        first_bad: String,
+        skipped: Vec<String>,
+    },
+    /// Even the oldest usable toolchain fails.
+    AlwaysBad { oldest: String },
+    /// Even the newest usable toolchain passes.
+    NeverBad,
+    /// Too many toolchains were skipped to decide.
+    Inconclusive,
+}
+
+impl Conclusion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Found { .. } => "found",
+            Self::AlwaysBad { .. } => "always-bad",
+            Self::NeverBad => "never-bad",
+            Self::Inconclusive => "inconclusive",
+        }
+    }
+}
+
+/// An append-only record of a bisection's steps, echoed to stderr.
+pub struct Log(Option<File>);
+
+impl Log {
+    fn open(path: &Path) -> io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self(Some(file)))
+    }
+
+    /// Opens `path` for appending, or logs to stderr alone if it cannot.
+    pub fn open_or_stderr(path: &Path) -> Self {
+        Self::open(path).unwrap_or_else(|err| {
+            eprintln!("cannot open bisect log {}: {err}", path.display());
+            Self(None)
+        })
+    }
+
+    /// Writes `line` to stderr and the log. A failed write disables the log
+    /// rather than the bisection.
+    pub fn line(&mut self, line: &str) {
+        eprintln!("{line}");
+        let Some(file) = &mut self.0 else {
+            return;
+        };
+        if let Err(err) = writeln!(file, "{line}") {
+            eprintln!("cannot write bisect log: {err}");
+            self.0 = None;
+        }
+    }
+}
+
+/// Seconds since the epoch, for log headers.
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+fn test(opts: &MatrixOptions, toolchain: &str, version: &Version, log: &mut Log) -> Step {
+    let cells: Vec<Cell> = opts
+        .profiles()
+        .iter()
+        .map(|profile| matrix::cell(opts, Some(toolchain), profile))
+        .collect();
+    let verdict = Verdict::of(&cells);
+    let outcomes: Vec<String> = cells
+        .iter()
+        .map(|c| format!("{}: {}", c.profile.label(), c.outcome.name()))
+        .collect();
+    log.line(&format!(
+        "{toolchain} {version}: {} [{}]",
+        verdict.name(),
+        outcomes.join(", ")
+    ));
+    Step {
+        toolchain: toolchain.to_owned(),
+        version: version.clone(),
+        verdict,
+        cells,
+    }
+}
+
+/// Binary-searches `candidates`, oldest first, for the first bad one, with
+/// `judge` testing each. A skipped toolchain is passed over for its nearest
+/// neighbour still in range.
+fn search(
+    candidates: &[(String, Version)],
+    mut judge: impl FnMut(&str, &Version) -> Verdict,
+) -> Conclusion {
+    let mut judge = |i: usize| judge(&candidates[i].0, &candidates[i].1);
+    let name = |i: usize| candidates[i].0.clone();
+
+    // The oldest usable toolchain must pass...
+    let mut good = 0;
+    loop {
+        if good == candidates.len() {
+            return Conclusion::Inconclusive;
+        }
+        match judge(good) {
+            Verdict::Good => break,
+            Verdict::Bad => return Conclusion::AlwaysBad { oldest: name(good) },
+            Verdict::Skip => good += 1,
+        }
+    }
+    // ...and the newest must fail.
+    let mut bad = candidates.len() - 1;
+    loop {
+        if bad == good {
+            return Conclusion::Inconclusive;
+        }
+        match judge(bad) {
+            Verdict::Bad => break,
+            Verdict::Good => return Conclusion::NeverBad,
+            Verdict::Skip => bad -= 1,
+        }
+    }
+
+    let mut skipped = Vec::new();
+    while let Some(i) = nearest_untried(good, bad, &skipped) {
+        match judge(i) {
+            Verdict::Good => good = i,
+            Verdict::Bad => bad = i,
+            Verdict::Skip => skipped.push(i),
+        }
+    }
+    Conclusion::Found {
+        last_good: name(good),
+        first_bad: name(bad),
+        skipped: (good + 1..bad).map(name).collect(),
+    }
+}
+
+/// The index strictly between `good` and `bad` closest to their midpoint
+/// that has not been skipped, if any.
+fn nearest_untried(good: usize, bad: usize, skipped: &[usize]) -> Option<usize> {
+    let mid = good + (bad - good) / 2;
+    (0..bad - good)
+        .flat_map(|d| [mid.checked_sub(d), mid.checked_add(d)])
+        .flatten()
+        .find(|&i| good < i && i < bad && !skipped.contains(&i))
+}
+
+/// Runs the bisection and prints its conclusion, returning the process
+/// exit code.
+pub fn bisect(opts: &BisectOptions) -> i32 {
+    let mut build = opts.build.clone();
+    build.offline = true;
+
+    let names = if build.toolchains.is_empty() {
+        match toolchain::installed() {
+            Ok(names) => names,
+            Err(err) => {
+                eprintln!("cannot list rustup toolchains: {err}");
+                return EXIT_USAGE;
+            }
+        }
+    } else {
+        build.toolchains.clone()
+    };
+
+    let log_path = opts
+        .log
+        .clone()
+        .unwrap_or_else(|| build.target_root().join("bisect.log"));
+    let mut log = Log::open_or_stderr(&log_path);
+    log.line(&format!("# bisect started at unix time {}", unix_time()));
+    let labels: Vec<String> = build.profiles().iter().map(|p| p.label()).collect();
+    log.line(&format!("# profiles: {}", labels.join("; ")));
+    if !build.run_args.is_empty() {
+        log.line(&format!("# run args: {}", build.run_args.join(" ")));
+    }
+
+    let mut candidates = Vec::new();
+    for name in names {
+        match toolchain::version(&name) {
+            Ok(version) => candidates.push((name, version)),
+            Err(err) => log.line(&format!("{name}: dropped: {err}")),
+        }
+    }
+    candidates.sort_by(|a, b| a.1.cmp(&b.1));
+    if candidates.len() < 2 {
+        log.line("# need at least two toolchains with a known version");
+        return EXIT_USAGE;
+    }
+    let order: Vec<String> = candidates
+        .iter()
+        .map(|(name, version)| format!("{name} {version}"))
+        .collect();
+    log.line(&format!("# order: {}", order.join(", ")));
+
+    let mut steps = Vec::new();
+    let conclusion = search(&candidates, |toolchain, version| {
+        let step = test(&build, toolchain, version, &mut log);
+        let verdict = step.verdict;
+        steps.push(step);
+        verdict
+    });
+    let summary = match &conclusion {
+        Conclusion::Found {
+            last_good,
+            first_bad,
+            skipped,
+        } if skipped.is_empty() => {
+            format!("first failing toolchain: {first_bad} (last passing: {last_good})")
+        }
+        Conclusion::Found {
+            last_good,
+            first_bad,
+            skipped,
+        } => format!(
+            "first failing toolchain: {first_bad} or one of {} before it, which were skipped \
+             (last passing: {last_good})",
+            skipped.join(", ")
+        ),
+        Conclusion::AlwaysBad { oldest } => {
+            format!("the oldest toolchain already fails: {oldest}")
+        }
+        Conclusion::NeverBad => "no toolchain fails".to_owned(),
+        Conclusion::Inconclusive => "inconclusive: too many toolchains were skipped".to_owned(),
+    };
+    log.line(&format!("# {summary}"));
+
+    match opts.build.format {
+        Format::Text => println!("{summary}"),
+        Format::Json | Format::Ndjson => {
+            let steps = steps.iter().map(|step| {
+                json::Object::new()
+                    .str("toolchain", &step.toolchain)
+                    .str("version", &step.version.to_string())
+                    .str("verdict", step.verdict.name())
+                    .raw(
+                        "cells",
+                        &json::array(step.cells.iter().map(|c| matrix::cell_object(c).finish())),
+                    )
+                    .finish()
+            });
+            let obj = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .str("log", &log_path.display().to_string())
+                .raw("steps", &json::array(steps))
+                .str("conclusion", conclusion.name());
+            let obj = match &conclusion {
+                Conclusion::Found {
+                    last_good,
+                    first_bad,
+                    skipped,
+                } => obj
+                    .str("last_good", last_good)
+                    .str("first_bad", first_bad)
+                    .raw(
+                        "skipped",
+                        &json::array(skipped.iter().map(|name| {
+                            let mut s = String::new();
+                            json::write_str(&mut s, name);
+                            s
+                        })),
+                    ),
+                Conclusion::AlwaysBad { oldest } => obj.str("first_bad", oldest),
+                Conclusion::NeverBad | Conclusion::Inconclusive => obj,
+            };
+            println!("{}", obj.finish());
+        }
+    }
+
+    match conclusion {
+        Conclusion::Found { .. } | Conclusion::AlwaysBad { .. } => EXIT_DIVERGED,
+        Conclusion::NeverBad => 0,
+        Conclusion::Inconclusive => EXIT_USAGE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toolchain::Channel;
+    use Verdict::{Bad as B, Good as G, Skip as S};
+
+    /// Searches toolchains `t0`, `t1`, ... judged by `verdicts`, returning
+    /// the conclusion and the indices judged, in order.
+    fn run(verdicts: &[Verdict]) -> (Conclusion, Vec<usize>) {
+        let candidates: Vec<(String, Version)> = (0..verdicts.len() as u32)
+            .map(|minor| {
+                let version = Version {
+                    major: 1,
+                    minor,
+                    patch: 0,
+                    channel: Channel::Stable,
+                    date: None,
+                };
+                (format!("t{minor}"), version)
+            })
+            .collect();
+        let mut judged = Vec::new();
+        let conclusion = search(&candidates, |_, version| {
+            let i = version.minor as usize;
+            assert!(!judged.contains(&i), "t{i} judged twice");
+            judged.push(i);
+            verdicts[i]
+        });
+        (conclusion, judged)
+    }
+
+    fn found(last_good: usize, first_bad: usize, skipped: &[usize]) -> Conclusion {
+        Conclusion::Found {
+            last_good: format!("t{last_good}"),
+            first_bad: format!("t{first_bad}"),
+            skipped: skipped.iter().map(|i| format!("t{i}")).collect(),
+        }
+    }
+
+    #[test]
+    fn finds_the_first_bad() {
+        let (conclusion, judged) = run(&[G, G, G, G, B, B, B, B]);
+        assert_eq!(conclusion, found(3, 4, &[]));
+        assert_eq!(judged, [0, 7, 3, 5, 4]);
+    }
+
+    #[test]
+    fn steps_past_skips_in_the_middle() {
+        assert_eq!(run(&[G, G, S, S, B, B]).0, found(1, 4, &[2, 3]));
+        assert_eq!(run(&[G, S, G, S, G, B]).0, found(4, 5, &[]));
+        assert_eq!(run(&[G, S, S, S, B]).0, found(0, 4, &[1, 2, 3]));
+    }
+
+    #[test]
+    fn skips_at_either_end() {
+        assert_eq!(run(&[S, S, G, B]).0, found(2, 3, &[]));
+        assert_eq!(run(&[G, B, S, S]).0, found(0, 1, &[]));
+        assert_eq!(
+            run(&[S, B, G]).0,
+            Conclusion::AlwaysBad {
+                oldest: "t1".into()
+            }
+        );
+        assert_eq!(run(&[G, G, S]).0, Conclusion::NeverBad);
+    }
+
+    #[test]
+    fn too_many_skips_are_inconclusive() {
+        assert_eq!(run(&[S, S, S]).0, Conclusion::Inconclusive);
+        assert_eq!(run(&[S, G, S]).0, Conclusion::Inconclusive);
+        assert_eq!(run(&[]).0, Conclusion::Inconclusive);
+    }
+}
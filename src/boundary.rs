@@ -0,0 +1,297 @@
+//! The `boundary` command: candidates concentrated around the modulus, where
+//! `ct_lt`'s borrow chain decides the outcome in its last limbs.
+//!
+//! With `n = 2^320 - special` nearly every `random_bits` candidate is below
+//! `n`, so the sampler rarely exercises the comparisons that go wrong. This
+//! generator produces them directly, and [`scan`] feeds them to the sampler
+//! through a scripted generator, so every one is decided inside the
+//! rejection loop and checked by the oracle.
+
+use crypto_bigint::{Limb, NonZero, Uint, Word};
+use rand_core::RngCore;
+
+use crate::build_info::build_info;
+use crate::oracle::{self, Divergence, DivergenceHex, Oracle};
+use crate::report::Format;
+use crate::rng::{RngKind, ScriptedRng, Stoppable, with_rng};
+use crate::run::{EXIT_DIVERGED, EXIT_INPUT_STOPPED, EXIT_USAGE, ModulusSpec};
+use crate::{SamplingError, hex, json, sample_observed, with_limbs};
+
+/// Number of fixed candidates [`Boundary`] starts with.
+pub const FIXED: u64 = 3;
+
+/// Number of random strategies [`Boundary`] then cycles through.
+pub const STRATEGIES: u64 = 3;
+
+/// Infinite iterator over candidates near `n`: once each of
+///
+/// - `n`
+/// - `n - 1`
+/// - `n + 1`
+///
+/// and then, in turn,
+///
+/// 0. `n`'s top limb with random lower limbs
+/// 1. `n`'s limbs above a random limb `k`, random from `k` down
+/// 2. as 1, but limb `k` is `n`'s plus or minus one
+///
+/// "Top" means the highest limb `n` uses; limbs above it stay zero and the top
+/// limb is masked to `n`'s bit length, as `random_bits` would.
+#[derive(Clone, Debug)]
+pub struct Boundary<const L: usize, R> {
+    n: Uint<L>,
+    rng: R,
+    index: u64,
+}
+
+impl<const L: usize, R: RngCore> Boundary<L, R> {
+    pub fn new(n: Uint<L>, rng: R) -> Self {
+        Self { n, rng, index: 0 }
+    }
+
+    fn word(&mut self) -> Word {
+        self.rng.next_u64() as Word
+    }
+
+    /// Index of the highest limb in use and the mask for it.
+    fn top(&self) -> (usize, Word) {
+        let bits = self.n.bits_vartime().max(1);
+        let top = ((bits - 1) / Limb::BITS) as usize;
+        let used = bits - top as u32 * Limb::BITS;
+        (top, Word::MAX >> (Limb::BITS - used))
+    }
+
+    /// `n`'s limbs above `k`, `limb_k` at `k`, random below.
+    fn prefix(&mut self, k: usize, limb_k: Word) -> Uint<L> {
+        let (top, mask) = self.top();
+        let mut words = self.n.to_words();
+        words[k] = limb_k;
+        for w in &mut words[..k] {
+            *w = self.word();
+        }
+        words[top] &= mask;
+        Uint::from_words(words)
+    }
+}
+
+impl<const L: usize, R: RngCore> Iterator for Boundary<L, R> {
+    type Item = Uint<L>;
+
+    fn next(&mut self) -> Option<Uint<L>> {
+        let index = self.index;
+        self.index += 1;
+        let (top, _) = self.top();
+        let x = match index {
+            0 => self.n,
+            1 => self.n.wrapping_sub(&Uint::ONE),
+            2 => self.n.wrapping_add(&Uint::ONE),
+            _ => match (index - FIXED) % STRATEGIES {
+                0 => self.prefix(top, self.n.as_words()[top]),
+                1 => {
+                    let k = self.rng.next_u32() as usize % (top + 1);
+                    let limb = self.word();
+                    self.prefix(k, limb)
+                }
+                _ => {
+                    let k = self.rng.next_u32() as usize % (top + 1);
+                    let limb = self.n.as_words()[k];
+                    let limb = if self.rng.next_u32() & 1 == 0 {
+                        limb.wrapping_add(1)
+                    } else {
+                        limb.wrapping_sub(1)
+                    };
+                    self.prefix(k, limb)
+                }
+            },
+        };
+        Some(x)
+    }
+}
+
+/// The bytes `Uint::random_bits(rng, n_bits)` reads to produce `x`: one
+/// big-endian word per limb it uses, lowest first, as crypto-bigint fills
+/// them.
+pub fn random_bits_script<const L: usize>(x: &Uint<L>, n_bits: u32) -> Vec<u8> {
+    let limbs = n_bits.div_ceil(Limb::BITS) as usize;
+    x.as_words()[..limbs]
+        .iter()
+        .flat_map(|w| w.to_be_bytes())
+        .collect()
+}
+
+/// Feeds `count` candidates to the sampler and the oracle, returning the
+/// first divergence, if any.
+///
+/// Each sampler run is scripted to draw candidates up to and including the
+/// next one below `n`, so the loop rejects the rest before accepting it and
+/// the next run starts with the candidate after. Candidates wider than `n`,
+/// which `random_bits` cannot produce, are skipped.
+pub fn scan<const L: usize, I>(
+    n: &NonZero<Uint<L>>,
+    candidates: I,
+    count: u64,
+) -> Option<Divergence<L>>
+where
+    I: IntoIterator<Item = Uint<L>>,
+{
+    let n_bits = n.bits_vartime();
+    let mut candidates = candidates
+        .into_iter()
+        .filter(|x| x.bits_vartime() <= n_bits)
+        .take(count as usize)
+        .peekable();
+    let mut checked = 0;
+    while candidates.peek().is_some() {
+        let mut script = Vec::new();
+        let mut batch = 0;
+        for x in candidates.by_ref() {
+            script.extend(random_bits_script(&x, n_bits));
+            batch += 1;
+            if oracle::reference_lt(&x, n) {
+                break;
+            }
+        }
+        let mut rng = ScriptedRng::new(script.into());
+        if let Err(SamplingError::Diverged(mut d)) = sample_observed(&mut rng, n, batch, Oracle) {
+            d.attempt += checked;
+            return Some(d);
+        }
+        checked += batch;
+    }
+    None
+}
+
+/// Options for the `boundary` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BoundaryOptions {
+    pub seed: u64,
+    pub limbs: usize,
+    pub modulus: ModulusSpec,
+    pub rng: RngKind,
+    pub count: u64,
+    pub format: Format,
+}
+
+impl Default for BoundaryOptions {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            limbs: 5,
+            modulus: ModulusSpec::Special,
+            rng: RngKind::ChaCha8,
+            count: 1 << 20,
+            format: Format::Text,
+        }
+    }
+}
+
+/// Scans boundary candidates and prints the result, returning the process
+/// exit code.
+pub fn boundary(opts: &BoundaryOptions) -> i32 {
+    with_rng!(opts.rng, opts.seed, |rng| {
+        with_limbs!(opts.limbs, |L| boundary_width::<L, _>(rng, opts), _ => {
+            eprintln!("unsupported limb count {}", opts.limbs);
+            EXIT_USAGE
+        })
+    })
+}
+
+fn boundary_width<const L: usize, R>(mut rng: R, opts: &BoundaryOptions) -> i32
+where
+    R: RngCore + Stoppable,
+{
+    let n = match opts.modulus.resolve::<L, _>(&mut rng) {
+        Ok(n) => n,
+        Err(_) if let Some(stopped) = rng.stopped() => {
+            eprintln!("{stopped}");
+            return EXIT_INPUT_STOPPED;
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_USAGE;
+        }
+    };
+    let divergence = scan(&n, Boundary::new(*n, &mut rng), opts.count);
+    // Candidates drawn after the input stopped are zeros, not the input's.
+    if let Some(stopped) = rng.stopped() {
+        eprintln!("{stopped}");
+        return EXIT_INPUT_STOPPED;
+    }
+    let divergence = divergence.map(|d| DivergenceHex::from(&d));
+
+    match opts.format {
+        Format::Text => match &divergence {
+            Some(d) => println!(
+                "ct_lt disagrees on candidate {}: x = {}, n = {}, ct_lt = {}, cmp_vartime = {}, reference = {}",
+                d.attempt, d.candidate, d.modulus, d.ct_lt, d.cmp_vartime, d.reference
+            ),
+            None => println!(
+                "{} boundary candidates around {}: no disagreements",
+                opts.count,
+                hex::encode(&*n)
+            ),
+        },
+        Format::Json | Format::Ndjson => {
+            let checked = divergence.as_ref().map_or(opts.count, |d| d.attempt + 1);
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .u64("seed", opts.seed)
+                .u64("limbs", opts.limbs as u64)
+                .str("rng", opts.rng.name())
+                .str("modulus", &hex::encode(&*n))
+                .u64("checked", checked)
+                .raw(
+                    "disagreements",
+                    &json::array(divergence.iter().map(DivergenceHex::to_json)),
+                )
+                .finish();
+            println!("{report}");
+        }
+    }
+
+    if divergence.is_some() {
+        EXIT_DIVERGED
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::CounterRng;
+    use crypto_bigint::RandomBits;
+    use rand_chacha::ChaCha8Rng;
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn script_round_trips_through_random_bits() {
+        let n = Uint::<2>::from_words([0x1234, 0x5678]);
+        let n_bits = n.bits_vartime();
+        for x in Boundary::new(n, CounterRng(7)).take(64) {
+            if x.bits_vartime() > n_bits {
+                continue;
+            }
+            let mut rng = ScriptedRng::new(random_bits_script(&x, n_bits).into());
+            assert_eq!(Uint::<2>::random_bits(&mut rng, n_bits), x);
+        }
+    }
+
+    #[test]
+    fn fixed_candidates_come_once() {
+        let n = Uint::<2>::from_words([5, 9]);
+        let rng = ChaCha8Rng::seed_from_u64(1);
+        let candidates: Vec<_> = Boundary::new(n, rng).take(30).collect();
+        assert_eq!(
+            candidates[..3],
+            [n, n.wrapping_sub(&Uint::ONE), n.wrapping_add(&Uint::ONE)]
+        );
+        assert!(!candidates[3..].contains(&n));
+    }
+
+    #[test]
+    fn healthy_comparison_does_not_diverge() {
+        let n = NonZero::new(Uint::<2>::from_words([5, 9])).unwrap();
+        assert_eq!(scan(&n, Boundary::new(*n, CounterRng(1)), 1000), None);
+    }
+}
@@ -0,0 +1,80 @@
+//! What compiled this binary, as recorded by `build.rs`.
+
+use crate::json;
+
+/// Compiler, target and codegen settings of this build.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// First line of `rustc -vV`.
+    pub rustc_version: &'static str,
+    /// All of `rustc -vV`.
+    pub rustc_verbose: &'static str,
+    pub rustc_release: &'static str,
+    pub rustc_commit: &'static str,
+    pub llvm_version: &'static str,
+    pub host: &'static str,
+    pub target: &'static str,
+    /// Cargo's `PROFILE`: `debug` or `release`, whatever the profile's name.
+    pub profile: &'static str,
+    pub opt_level: &'static str,
+    /// `lto` from `RUSTFLAGS`, `CARGO_PROFILE_<NAME>_LTO` or the package
+    /// manifest's profile table, for the profile built, else `unknown`.
+    pub lto: &'static str,
+    /// Like `lto`, for `codegen-units`.
+    pub codegen_units: &'static str,
+    pub debug_assertions: bool,
+    pub target_features: &'static [&'static str],
+    pub rustflags: &'static [&'static str],
+    /// Cargo features enabled on this crate.
+    pub features: &'static [&'static str],
+}
+
+const BUILD_INFO: BuildInfo = include!(concat!(env!("OUT_DIR"), "/build_info.rs"));
+
+/// How this binary was built.
+pub fn build_info() -> &'static BuildInfo {
+    &BUILD_INFO
+}
+
+impl BuildInfo {
+    /// One line suitable for the top of any output.
+    pub fn banner(&self) -> String {
+        format!(
+            "subtle-repro {} built by {} (LLVM {}) for {}, profile {}, opt-level {}, lto {}, codegen-units {}",
+            env!("CARGO_PKG_VERSION"),
+            self.rustc_version,
+            self.llvm_version,
+            self.target,
+            self.profile,
+            self.opt_level,
+            self.lto,
+            self.codegen_units,
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        let strs = |values: &[&str]| {
+            json::array(values.iter().map(|v| {
+                let mut s = String::new();
+                json::write_str(&mut s, v);
+                s
+            }))
+        };
+        json::Object::new()
+            .str("rustc", self.rustc_version)
+            .str("rustc_release", self.rustc_release)
+            .str("rustc_commit", self.rustc_commit)
+            .str("llvm", self.llvm_version)
+            .str("host", self.host)
+            .str("target", self.target)
+            .str("profile", self.profile)
+            .str("opt_level", self.opt_level)
+            .str("lto", self.lto)
+            .str("codegen_units", self.codegen_units)
+            .bool("debug_assertions", self.debug_assertions)
+            .raw("target_features", &strs(self.target_features))
+            .raw("rustflags", &strs(self.rustflags))
+            .raw("features", &strs(self.features))
+            .finish()
+    }
+}
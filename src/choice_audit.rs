@@ -0,0 +1,213 @@
+//! Checks that every `Choice` the sampler gets from `ct_lt` holds a
+//! canonical 0 or 1.
+//!
+//! subtle only ever builds a `Choice` from 0 or 1, but a miscompile can
+//! leave another byte in it, and `bool::from` (`!= 0`) and
+//! `unwrap_u8() == 1` then disagree about what it means.
+
+use crypto_bigint::Uint;
+use subtle::Choice;
+
+use crate::{Observer, SamplingError, hex, json};
+
+/// How many anomalies are kept with their operands; the rest are counted.
+pub const RECORDED: usize = 16;
+
+/// A `Choice` that was not canonical, or whose conversions disagreed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChoiceAnomaly<const L: usize> {
+    pub attempt: u64,
+    pub candidate: Uint<L>,
+    pub modulus: Uint<L>,
+    /// `unwrap_u8()`.
+    pub byte: u8,
+    /// `bool::from`.
+    pub as_bool: bool,
+}
+
+/// Observer recording what every `ct_lt` result held.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChoiceAudit<const L: usize> {
+    pub comparisons: u64,
+    pub zeros: u64,
+    pub ones: u64,
+    /// Every anomaly, including those not recorded.
+    pub anomalies: u64,
+    /// The first [`RECORDED`] anomalies.
+    pub recorded: Vec<ChoiceAnomaly<L>>,
+}
+
+impl<const L: usize> ChoiceAudit<L> {
+    /// Checks one `Choice`, recording it if it is an anomaly.
+    pub fn audit(&mut self, attempt: u64, x: &Uint<L>, n: &Uint<L>, choice: Choice) {
+        self.audit_parts(attempt, x, n, choice.unwrap_u8(), bool::from(choice));
+    }
+
+    /// Checks what a `Choice` converted to: its `unwrap_u8()` and its
+    /// `bool::from`.
+    fn audit_parts(&mut self, attempt: u64, x: &Uint<L>, n: &Uint<L>, byte: u8, as_bool: bool) {
+        self.comparisons += 1;
+        match byte {
+            0 => self.zeros += 1,
+            1 => self.ones += 1,
+            _ => {}
+        }
+        if byte <= 1 && as_bool == (byte == 1) {
+            return;
+        }
+        self.anomalies += 1;
+        if self.recorded.len() < RECORDED {
+            self.recorded.push(ChoiceAnomaly {
+                attempt,
+                candidate: *x,
+                modulus: *n,
+                byte,
+                as_bool,
+            });
+        }
+    }
+}
+
+impl<const L: usize> Observer<L> for ChoiceAudit<L> {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        self.audit(attempt, x, n, lt);
+        Ok(())
+    }
+}
+
+/// A [`ChoiceAnomaly`] with its operands already rendered.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChoiceAnomalyHex {
+    pub attempt: u64,
+    pub candidate: String,
+    pub modulus: String,
+    pub byte: u8,
+    pub as_bool: bool,
+}
+
+impl<const L: usize> From<&ChoiceAnomaly<L>> for ChoiceAnomalyHex {
+    fn from(a: &ChoiceAnomaly<L>) -> Self {
+        Self {
+            attempt: a.attempt,
+            candidate: hex::encode(&a.candidate),
+            modulus: hex::encode(&a.modulus),
+            byte: a.byte,
+            as_bool: a.as_bool,
+        }
+    }
+}
+
+impl ChoiceAnomalyHex {
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .u64("attempt", self.attempt)
+            .str("candidate", &self.candidate)
+            .str("modulus", &self.modulus)
+            .u64("unwrap_u8", self.byte.into())
+            .bool("bool_from", self.as_bool)
+            .finish()
+    }
+}
+
+/// A [`ChoiceAudit`] with its anomalies rendered, so audits of different
+/// widths can share a type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChoiceAuditHex {
+    pub comparisons: u64,
+    pub zeros: u64,
+    pub ones: u64,
+    pub anomalies: u64,
+    pub recorded: Vec<ChoiceAnomalyHex>,
+}
+
+impl<const L: usize> From<&ChoiceAudit<L>> for ChoiceAuditHex {
+    fn from(a: &ChoiceAudit<L>) -> Self {
+        Self {
+            comparisons: a.comparisons,
+            zeros: a.zeros,
+            ones: a.ones,
+            anomalies: a.anomalies,
+            recorded: a.recorded.iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl ChoiceAuditHex {
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .u64("comparisons", self.comparisons)
+            .u64("zeros", self.zeros)
+            .u64("ones", self.ones)
+            .u64("anomalies", self.anomalies)
+            .raw(
+                "recorded",
+                &json::array(self.recorded.iter().map(ChoiceAnomalyHex::to_json)),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X: Uint<1> = Uint::from_u64(3);
+    const N: Uint<1> = Uint::from_u64(7);
+
+    #[test]
+    fn counts_zeros_and_ones() {
+        let mut audit = ChoiceAudit::<1>::default();
+        for (attempt, lt) in [true, false, true, true].into_iter().enumerate() {
+            audit.audit(attempt as u64, &X, &N, Choice::from(lt as u8));
+        }
+        assert_eq!(
+            (audit.comparisons, audit.zeros, audit.ones, audit.anomalies),
+            (4, 1, 3, 0)
+        );
+        assert!(audit.recorded.is_empty());
+    }
+
+    #[test]
+    fn records_non_canonical_and_inconsistent_choices() {
+        let mut audit = ChoiceAudit::<1>::default();
+        audit.audit_parts(0, &X, &N, 2, true);
+        // Canonical bytes whose conversions disagree.
+        audit.audit_parts(1, &X, &N, 1, false);
+        audit.audit_parts(2, &X, &N, 0, true);
+        audit.audit_parts(3, &X, &N, 0, false);
+        assert_eq!(
+            (audit.comparisons, audit.zeros, audit.ones, audit.anomalies),
+            (4, 2, 1, 3)
+        );
+        let seen: Vec<(u64, u8, bool)> = audit
+            .recorded
+            .iter()
+            .map(|a| (a.attempt, a.byte, a.as_bool))
+            .collect();
+        assert_eq!(seen, [(0, 2, true), (1, 1, false), (2, 0, true)]);
+        assert_eq!(
+            (audit.recorded[0].candidate, audit.recorded[0].modulus),
+            (X, N)
+        );
+    }
+
+    #[test]
+    fn keeps_counting_past_recorded() {
+        let mut audit = ChoiceAudit::<1>::default();
+        for attempt in 0..RECORDED as u64 + 5 {
+            audit.audit_parts(attempt, &X, &N, 0xff, true);
+        }
+        assert_eq!(audit.anomalies, RECORDED as u64 + 5);
+        assert_eq!(audit.recorded.len(), RECORDED);
+        assert_eq!(audit.recorded.last().unwrap().attempt, RECORDED as u64 - 1);
+        let hex = ChoiceAuditHex::from(&audit);
+        assert_eq!(hex.anomalies, audit.anomalies);
+        assert_eq!(hex.recorded.len(), RECORDED);
+    }
+}
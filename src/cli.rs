@@ -0,0 +1,891 @@
+//! Command-line parsing for the reproduction binary.
+
+use core::fmt;
+use core::ops::Range;
+use core::str::FromStr;
+use std::path::Path;
+use std::time::Duration;
+
+use crypto_bigint::Word;
+
+use crate::LIMB_COUNTS;
+use crate::asm_check::CheckAsmOptions;
+use crate::barriers::BarriersOptions;
+use crate::bisect::BisectOptions;
+use crate::boundary::BoundaryOptions;
+use crate::cross::CrossOptions;
+use crate::emit::EmitOptions;
+use crate::feature_matrix::FeatureMatrixOptions;
+use crate::hex;
+use crate::inline_ct::Variant;
+use crate::matrix::MatrixOptions;
+use crate::minimize::MinimizeOptions;
+use crate::opt_bisect::OptBisectOptions;
+use crate::record::Recording;
+use crate::replay_ir::ReplayIrOptions;
+use crate::report::Format;
+use crate::reproducer::ReproducerOptions;
+use crate::rng::RngKind;
+use crate::run::{ModulusSpec, RunOptions};
+use crate::shapes::ShapesOptions;
+use crate::sweep::SweepOptions;
+use crate::verify::VerifyOptions;
+
+pub const USAGE: &str = "\
+usage: subtle-repro [run] [options]
+       subtle-repro sweep [options]
+       subtle-repro verify-ct [options]
+       subtle-repro boundary [options]
+       subtle-repro minimize --recording <file> [options]
+       subtle-repro reproducer --candidate <hex> --modulus <hex> [options]
+       subtle-repro barriers [options]
+       subtle-repro matrix [options] [-- <run options>]
+       subtle-repro bisect [options] [-- <run options>]
+       subtle-repro emit [options] [-- <run options>]
+       subtle-repro check-asm [--asm <file>]... [options]
+       subtle-repro cross [options] [-- <run options>]
+       subtle-repro opt-bisect [options] [-- <run options>]
+       subtle-repro replay-ir [options]
+       subtle-repro feature-matrix [options] [-- <run options>]
+       subtle-repro shapes [options] [-- <run options>]
+       subtle-repro help
+
+run options:
+  --seed <u64>              seed for the generator (default 1)
+  --limbs <n>               Uint width in limbs: 1, 2, 4, 5, 8 or 16 (default 5)
+  --modulus <hex>           sample below this modulus
+  --modulus-from-special    sample below 0 - rng.next_u64(), as originally (default)
+  --max-attempts <n>        give up after n rejected candidates (default unbounded)
+  --rng <name>              chacha8, chacha12, chacha20, std, counter or
+                            scripted:<file> to serve the file's bytes, or
+                            replay:<file> to replay a --record file (default chacha8)
+  --record <file>           write every rng call and its output to file
+  --barrier <name>          compare with crates (default), or an inlined copy using
+                            volatile, black-box, asm or none as the barrier
+  --shape <name>            sample with another loop shape: loop, while, find,
+                            recursion, unrolled, inline-never, inline-always or
+                            unwrap-u8; unbounded, with the crates' comparison
+  --oracle                  cross-check every ct_lt decision
+  --observe                 report every candidate to the watchdog; runs an
+                            instrumented loop rather than the original
+  --audit-choices           check every ct_lt result is a 0 or 1 Choice and that
+                            bool::from agrees with unwrap_u8
+  --watchdog-secs <n>       report and exit if sampling takes longer (default 20, 0 disables)
+  --format <fmt>            text, json or ndjson (default text)
+
+sweep options:
+  --seeds <a>..<b>          seeds to try, end exclusive (default 0..64)
+  --limbs <n>               Uint width in limbs (default 5)
+  --rng <name>              generator (default chacha8)
+  --max-attempts <n>        per-seed attempt budget (default 10000)
+  --format <fmt>            text (a table), json or ndjson (default text)
+
+verify-ct options:
+  --window-bits <n>         exhaustively check the lowest/highest 2^n values (default 10)
+  --random <n>              random pairs per strategy (default 262144)
+  --seed <u64>              seed for the random pairs (default 1)
+  --specials <n>            seeds whose special modulus is probed (default 16)
+  --format <fmt>            text, json or ndjson (default text)
+
+boundary options:
+  --seed, --limbs, --modulus, --modulus-from-special, --rng, --format
+                            as for run
+  --count <n>               candidates near the modulus to check (default 1048576)
+
+minimize options:
+  --recording <file>        a run --record file from a failing run
+  --modulus <hex>           the run's modulus
+  --modulus-from-special    the modulus was 0 - the recording's first u64 (default)
+  --limbs <n>               the run's width (default 5)
+  --max-attempts <n>        candidates to replay (default 1048576)
+  --out <file>              write the minimized recording here
+  --snippet <file>          write the reproducing snippet here instead of stdout
+  --format <fmt>            text, json or ndjson (default text)
+
+reproducer options:
+  --candidate <hex>         the x that ct_lt got wrong
+  --modulus <hex>           the n it was compared against
+  --limbs <n>               width (default: narrowest that fits)
+  --out <file>              write the file here instead of stdout
+
+barriers options:
+  --seed, --limbs, --modulus, --modulus-from-special, --format
+                            as for run
+  --rng <name>              a seedable generator (default chacha8)
+  --max-attempts <n>        budget for the oracle-checked run (default 1048576)
+  --timeout-secs <n>        watchdog for each unbounded run (default 10)
+
+matrix options (lists are comma-separated; every combination is built):
+  --toolchains <list>       rustup toolchains (default: all installed)
+  --opt-levels <list>       from 0, 1, 2, 3, s, z (default all)
+  --lto <list>              from off, on, thin, fat (default off)
+  --codegen-units <list>    (default 16)
+  --panic <list>            from unwind, abort (default unwind)
+  --target-cpu <list>       -C target-cpu values; default leaves it unset (default default)
+  --features <list>         sets of this crate's cargo features, each joined
+                            with +; none builds with the defaults (default none)
+  --source <dir>            crate to build (default: this one)
+  --target-dir <dir>        parent of the per-configuration target directories
+                            (default <source>/target/matrix)
+  --offline                 pass --offline to cargo
+  --build-timeout-secs <n>  per build (default 900)
+  --timeout-secs <n>        watchdog for each run (default 20)
+  --format <fmt>            text (a grid), json or ndjson (default text)
+  -- <run options>          passed to each binary's run
+
+bisect options:
+  the matrix options, except that --opt-levels defaults to 3 and cargo always
+  runs --offline; a toolchain fails if any configuration hangs or diverges
+  --log <file>              append each step here (default <target-dir>/bisect.log)
+
+emit options:
+  the matrix options, and
+  --functions <list>        symbol substrings to extract
+                            (default random_mod,sample_observed,ct_lt); for
+                            every command, the shapes module's symbols only
+                            match when an item contains `shapes`
+  --out <dir>               per-configuration IR, assembly and diffs
+                            (default <source>/target/emit)
+
+check-asm options:
+  --asm <file>              check this x86_64 or aarch64 assembly; repeatable
+                            (default: compile each matrix configuration and
+                            check that, with --opt-levels defaulting to 3)
+  --functions <list>        symbol substrings to check (default random_mod)
+  the matrix build options and --format
+
+cross options:
+  the matrix options, with --opt-levels defaulting to 3, and
+  --target <triple>         target to build for (default aarch64-unknown-linux-gnu)
+  --functions <list>        symbol substrings to disassemble and check
+                            (default random_mod)
+  --linker <program>        linker for the target (default <arch>-linux-gnu-gcc
+                            if on the path)
+  --runner <program>        emulator to run the binary under (default
+                            qemu-<arch> if on the path; otherwise not run)
+  --sysroot <dir>           passed to the emulator as -L (default
+                            $QEMU_LD_PREFIX or /usr/<arch>-linux-gnu)
+  --out <dir>               per-configuration disassembly and assembly
+                            (default <source>/target/cross)
+
+opt-bisect options:
+  the matrix options, with --opt-levels defaulting to 3 and --codegen-units
+  to 1, and
+  --target <triple>         build for this target instead of the host
+  --check <how>             run: run the binary with --oracle (default on the
+                            host); asm: check-asm the library (default with
+                            --target)
+  --functions <list>        symbol substrings for --check asm (default random_mod)
+  --log <file>              append each step here
+                            (default <target-dir>/opt-bisect.log)
+
+replay-ir options:
+  the matrix build options, with --opt-levels defaulting to 3, and
+  --levels <list>           opt levels to replay: 0, 1, 2, 3, s, z (default all)
+  --target <triple>         triple to compare with the host's
+                            (default aarch64-unknown-linux-gnu); without its
+                            standard library its results are advisory
+  --functions <list>        symbol substrings to diff and check (default random_mod)
+  --opt <program>           opt to replay with (default the toolchain's
+                            llvm-tools, then opt-<llvm major> or opt on the path)
+  --llc <program>           llc to lower with, found like opt; without one
+                            nothing is lowered or checked
+  --out <dir>               captured IR, replays and diffs
+                            (default <source>/target/replay-ir)
+
+feature-matrix options:
+  the matrix options, with --features defaulting to every combination of
+  subtle's barrier features (none, core_hint_black_box); each binary runs
+  with --oracle
+
+shapes options:
+  the matrix options, and
+  --shapes <list>           loop shapes to run, as for run --shape (default all)
+";
+
+/// A parsed command line.
+#[derive(Clone, Debug)]
+pub enum Command {
+    Run(RunOptions),
+    Sweep(SweepOptions),
+    VerifyCt(VerifyOptions),
+    Boundary(BoundaryOptions),
+    Minimize(MinimizeOptions),
+    Reproducer(ReproducerOptions),
+    Barriers(BarriersOptions),
+    Matrix(MatrixOptions),
+    Bisect(BisectOptions),
+    Emit(EmitOptions),
+    CheckAsm(CheckAsmOptions),
+    Cross(CrossOptions),
+    OptBisect(OptBisectOptions),
+    ReplayIr(ReplayIrOptions),
+    FeatureMatrix(FeatureMatrixOptions),
+    Shapes(ShapesOptions),
+    Help,
+}
+
+/// A malformed command line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CliError(String);
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for CliError {}
+
+/// Parses the arguments following the program name.
+pub fn parse<I>(args: I) -> Result<Command, CliError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = Args(args.into_iter().collect::<Vec<_>>().into_iter().peekable());
+    match args.peek() {
+        None => Ok(Command::Run(RunOptions::default())),
+        Some(arg) if arg.starts_with("--") => parse_run(&mut args).map(Command::Run),
+        Some(_) => match args.next().as_deref() {
+            Some("run") => parse_run(&mut args).map(Command::Run),
+            Some("sweep") => parse_sweep(&mut args).map(Command::Sweep),
+            Some("verify-ct") => parse_verify(&mut args).map(Command::VerifyCt),
+            Some("boundary") => parse_boundary(&mut args).map(Command::Boundary),
+            Some("minimize") => parse_minimize(&mut args).map(Command::Minimize),
+            Some("reproducer") => parse_reproducer(&mut args).map(Command::Reproducer),
+            Some("barriers") => parse_barriers(&mut args).map(Command::Barriers),
+            Some("matrix") => parse_matrix(&mut args).map(Command::Matrix),
+            Some("bisect") => parse_bisect(&mut args).map(Command::Bisect),
+            Some("emit") => parse_emit(&mut args).map(Command::Emit),
+            Some("check-asm") => parse_check_asm(&mut args).map(Command::CheckAsm),
+            Some("cross") => parse_cross(&mut args).map(Command::Cross),
+            Some("opt-bisect") => parse_opt_bisect(&mut args).map(Command::OptBisect),
+            Some("replay-ir") => parse_replay_ir(&mut args).map(Command::ReplayIr),
+            Some("feature-matrix") => parse_feature_matrix(&mut args).map(Command::FeatureMatrix),
+            Some("shapes") => parse_shapes(&mut args).map(Command::Shapes),
+            Some("help") => Ok(Command::Help),
+            Some(other) => Err(CliError(format!("unknown command {other:?}"))),
+            None => unreachable!(),
+        },
+    }
+}
+
+fn parse_run(args: &mut Args) -> Result<RunOptions, CliError> {
+    let mut opts = RunOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--seed" => opts.seed = args.parse(&flag)?,
+            "--limbs" => opts.limbs = args.limbs(&flag)?,
+            "--modulus" => opts.modulus = args.modulus(&flag)?,
+            "--modulus-from-special" => opts.modulus = ModulusSpec::Special,
+            "--max-attempts" => opts.max_attempts = Some(args.parse(&flag)?),
+            "--rng" => opts.rng = args.rng(&flag)?,
+            "--record" => opts.record = Some(args.value(&flag)?.into()),
+            "--oracle" => opts.oracle = true,
+            "--barrier" => opts.barrier = args.parse(&flag)?,
+            "--shape" => opts.shape = Some(args.parse(&flag)?),
+            "--audit-choices" => opts.audit_choices = true,
+            "--observe" => opts.observe = true,
+            "--watchdog-secs" => opts.watchdog = args.watchdog(&flag)?,
+            "--format" => opts.format = args.parse::<Format>(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    if opts.audit_choices && (opts.barrier != Variant::Crates || opts.shape.is_some()) {
+        return Err(CliError(
+            "--audit-choices observes the crates' sampler; drop --barrier and --shape".into(),
+        ));
+    }
+    if opts.observe && (opts.barrier != Variant::Crates || opts.shape.is_some()) {
+        return Err(CliError(
+            "--observe instruments the crates' sampler; drop --barrier and --shape".into(),
+        ));
+    }
+    if opts.shape.is_some() && (opts.barrier != Variant::Crates || opts.max_attempts.is_some()) {
+        return Err(CliError(
+            "--shape runs unbounded with the crates' comparison; drop --barrier and \
+             --max-attempts"
+                .into(),
+        ));
+    }
+    Ok(opts)
+}
+
+fn parse_sweep(args: &mut Args) -> Result<SweepOptions, CliError> {
+    let mut opts = SweepOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--seeds" => opts.seeds = args.range(&flag)?,
+            "--limbs" => opts.limbs = args.limbs(&flag)?,
+            "--rng" => opts.rng = args.rng(&flag)?,
+            "--max-attempts" => opts.max_attempts = args.parse(&flag)?,
+            "--format" => opts.format = args.parse(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_verify(args: &mut Args) -> Result<VerifyOptions, CliError> {
+    let mut opts = VerifyOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--window-bits" => {
+                opts.window_bits = args.parse(&flag)?;
+                if opts.window_bits > 16 {
+                    return Err(CliError(format!("{flag} must be at most 16")));
+                }
+            }
+            "--random" => opts.random = args.parse(&flag)?,
+            "--seed" => opts.seed = args.parse(&flag)?,
+            "--specials" => opts.specials = args.parse(&flag)?,
+            "--format" => opts.format = args.parse(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_boundary(args: &mut Args) -> Result<BoundaryOptions, CliError> {
+    let mut opts = BoundaryOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--seed" => opts.seed = args.parse(&flag)?,
+            "--limbs" => opts.limbs = args.limbs(&flag)?,
+            "--modulus" => opts.modulus = args.modulus(&flag)?,
+            "--modulus-from-special" => opts.modulus = ModulusSpec::Special,
+            "--rng" => opts.rng = args.rng(&flag)?,
+            "--count" => opts.count = args.parse(&flag)?,
+            "--format" => opts.format = args.parse(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_minimize(args: &mut Args) -> Result<MinimizeOptions, CliError> {
+    let mut opts = MinimizeOptions::default();
+    let mut recording = None;
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--recording" => recording = Some(args.value(&flag)?.into()),
+            "--modulus" => opts.modulus = args.modulus(&flag)?,
+            "--modulus-from-special" => opts.modulus = ModulusSpec::Special,
+            "--limbs" => opts.limbs = args.limbs(&flag)?,
+            "--max-attempts" => opts.max_attempts = args.parse(&flag)?,
+            "--out" => opts.out = Some(args.value(&flag)?.into()),
+            "--snippet" => opts.snippet = Some(args.value(&flag)?.into()),
+            "--format" => opts.format = args.parse(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    opts.recording = recording.ok_or_else(|| CliError("minimize requires --recording".into()))?;
+    Ok(opts)
+}
+
+fn parse_reproducer(args: &mut Args) -> Result<ReproducerOptions, CliError> {
+    let mut opts = ReproducerOptions::default();
+    let (mut candidate, mut modulus) = (None, None);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--candidate" => candidate = Some(args.words(&flag)?),
+            "--modulus" => modulus = Some(args.words(&flag)?),
+            "--limbs" => opts.limbs = Some(args.parse(&flag)?),
+            "--out" => opts.out = Some(args.value(&flag)?.into()),
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    opts.candidate = candidate.ok_or_else(|| CliError("reproducer requires --candidate".into()))?;
+    opts.modulus = modulus.ok_or_else(|| CliError("reproducer requires --modulus".into()))?;
+    Ok(opts)
+}
+
+fn parse_barriers(args: &mut Args) -> Result<BarriersOptions, CliError> {
+    let mut opts = BarriersOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--seed" => opts.seed = args.parse(&flag)?,
+            "--limbs" => opts.limbs = args.limbs(&flag)?,
+            "--modulus" => opts.modulus = args.modulus(&flag)?,
+            "--modulus-from-special" => opts.modulus = ModulusSpec::Special,
+            "--rng" => opts.rng = args.parse(&flag)?,
+            "--max-attempts" => opts.max_attempts = args.parse(&flag)?,
+            "--timeout-secs" => opts.timeout = Duration::from_secs(args.parse(&flag)?),
+            "--format" => opts.format = args.parse(&flag)?,
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_matrix(args: &mut Args) -> Result<MatrixOptions, CliError> {
+    let mut opts = MatrixOptions::default();
+    while let Some(flag) = args.next() {
+        if !matrix_flag(args, &flag, &mut opts)? {
+            return Err(unknown_flag(&flag));
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_bisect(args: &mut Args) -> Result<BisectOptions, CliError> {
+    let mut opts = BisectOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--log" => opts.log = Some(args.value(&flag)?.into()),
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_emit(args: &mut Args) -> Result<EmitOptions, CliError> {
+    let mut opts = EmitOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--functions" => opts.functions = args.list(&flag, &[])?,
+            "--out" => opts.out = Some(args.value(&flag)?.into()),
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_check_asm(args: &mut Args) -> Result<CheckAsmOptions, CliError> {
+    let mut opts = CheckAsmOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--asm" => opts.asm.push(args.value(&flag)?.into()),
+            "--functions" => opts.functions = args.list(&flag, &[])?,
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_cross(args: &mut Args) -> Result<CrossOptions, CliError> {
+    let mut opts = CrossOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--target" => opts.target = args.value(&flag)?,
+            "--functions" => opts.functions = args.list(&flag, &[])?,
+            "--linker" => opts.linker = Some(args.value(&flag)?),
+            "--runner" => opts.runner = Some(args.value(&flag)?),
+            "--sysroot" => opts.sysroot = Some(args.value(&flag)?.into()),
+            "--out" => opts.out = Some(args.value(&flag)?.into()),
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_opt_bisect(args: &mut Args) -> Result<OptBisectOptions, CliError> {
+    let mut opts = OptBisectOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--target" => opts.target = Some(args.value(&flag)?),
+            "--check" => opts.check = Some(args.parse(&flag)?),
+            "--functions" => opts.functions = args.list(&flag, &[])?,
+            "--log" => opts.log = Some(args.value(&flag)?.into()),
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_replay_ir(args: &mut Args) -> Result<ReplayIrOptions, CliError> {
+    let mut opts = ReplayIrOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--levels" => opts.levels = args.list(&flag, &["0", "1", "2", "3", "s", "z"])?,
+            "--target" => opts.target = args.value(&flag)?,
+            "--functions" => opts.functions = args.list(&flag, &[])?,
+            "--opt" => opts.opt = Some(args.value(&flag)?.into()),
+            "--llc" => opts.llc = Some(args.value(&flag)?.into()),
+            "--out" => opts.out = Some(args.value(&flag)?.into()),
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_feature_matrix(args: &mut Args) -> Result<FeatureMatrixOptions, CliError> {
+    let mut opts = FeatureMatrixOptions::default();
+    while let Some(flag) = args.next() {
+        if !matrix_flag(args, &flag, &mut opts.build)? {
+            return Err(unknown_flag(&flag));
+        }
+    }
+    Ok(opts)
+}
+
+fn parse_shapes(args: &mut Args) -> Result<ShapesOptions, CliError> {
+    let mut opts = ShapesOptions::default();
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--shapes" => {
+                opts.shapes = args
+                    .list(&flag, &[])?
+                    .iter()
+                    .map(|name| name.parse().map_err(CliError))
+                    .collect::<Result<_, _>>()?
+            }
+            _ if matrix_flag(args, &flag, &mut opts.build)? => {}
+            _ => return Err(unknown_flag(&flag)),
+        }
+    }
+    Ok(opts)
+}
+
+/// Handles a flag shared by the commands that build the crate, returning
+/// whether `flag` was one of them.
+fn matrix_flag(args: &mut Args, flag: &str, opts: &mut MatrixOptions) -> Result<bool, CliError> {
+    match flag {
+        "--toolchains" => opts.toolchains = args.list(flag, &[])?,
+        "--opt-levels" => opts.opt_levels = args.list(flag, &["0", "1", "2", "3", "s", "z"])?,
+        "--lto" => opts.lto = args.list(flag, &["off", "on", "thin", "fat"])?,
+        "--codegen-units" => {
+            opts.codegen_units = args
+                .list(flag, &[])?
+                .iter()
+                .map(|n| {
+                    n.parse()
+                        .map_err(|err| CliError(format!("invalid {flag} {n:?}: {err}")))
+                })
+                .collect::<Result<_, _>>()?
+        }
+        "--panic" => opts.panic = args.list(flag, &["unwind", "abort"])?,
+        "--target-cpu" => {
+            opts.target_cpus = args
+                .list(flag, &[])?
+                .into_iter()
+                .map(|cpu| (cpu != "default").then_some(cpu))
+                .collect()
+        }
+        "--features" => {
+            opts.features = args
+                .list(flag, &[])?
+                .into_iter()
+                .map(|set| match set.as_str() {
+                    "none" => Vec::new(),
+                    _ => set.split('+').map(str::to_owned).collect(),
+                })
+                .collect()
+        }
+        "--source" => opts.source = args.value(flag)?.into(),
+        "--target-dir" => opts.target_dir = Some(args.value(flag)?.into()),
+        "--offline" => opts.offline = true,
+        "--build-timeout-secs" => opts.build_timeout = Duration::from_secs(args.parse(flag)?),
+        "--timeout-secs" => opts.timeout = Duration::from_secs(args.parse(flag)?),
+        "--format" => opts.format = args.parse(flag)?,
+        "--" => opts.run_args.extend(args.rest()),
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn unknown_flag(flag: &str) -> CliError {
+    CliError(format!("unknown option {flag:?}"))
+}
+
+struct Args(std::iter::Peekable<std::vec::IntoIter<String>>);
+
+impl Args {
+    fn peek(&mut self) -> Option<&String> {
+        self.0.peek()
+    }
+
+    fn next(&mut self) -> Option<String> {
+        self.0.next()
+    }
+
+    fn rest(&mut self) -> Vec<String> {
+        self.0.by_ref().collect()
+    }
+
+    fn value(&mut self, flag: &str) -> Result<String, CliError> {
+        self.next()
+            .ok_or_else(|| CliError(format!("{flag} requires a value")))
+    }
+
+    fn parse<T>(&mut self, flag: &str) -> Result<T, CliError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        let value = self.value(flag)?;
+        value
+            .parse()
+            .map_err(|err| CliError(format!("invalid {flag} {value:?}: {err}")))
+    }
+
+    /// A nonempty comma-separated list, each item one of `allowed` unless
+    /// that is empty.
+    fn list(&mut self, flag: &str, allowed: &[&str]) -> Result<Vec<String>, CliError> {
+        let value = self.value(flag)?;
+        let items: Vec<String> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if items.is_empty() {
+            return Err(CliError(format!("{flag} requires at least one value")));
+        }
+        if let Some(bad) = items
+            .iter()
+            .find(|item| !allowed.is_empty() && !allowed.contains(&item.as_str()))
+        {
+            return Err(CliError(format!(
+                "invalid {flag} {bad:?}; expected one of {allowed:?}"
+            )));
+        }
+        Ok(items)
+    }
+
+    fn limbs(&mut self, flag: &str) -> Result<usize, CliError> {
+        let limbs = self.parse(flag)?;
+        if !LIMB_COUNTS.contains(&limbs) {
+            return Err(CliError(format!(
+                "unsupported {flag} {limbs}; expected one of {LIMB_COUNTS:?}"
+            )));
+        }
+        Ok(limbs)
+    }
+
+    fn range(&mut self, flag: &str) -> Result<Range<u64>, CliError> {
+        let value = self.value(flag)?;
+        let invalid = || CliError(format!("invalid {flag} {value:?}; expected <start>..<end>"));
+        let (start, end) = value.split_once("..").ok_or_else(invalid)?;
+        let start = start.parse().map_err(|_| invalid())?;
+        let end = end.parse().map_err(|_| invalid())?;
+        Ok(start..end)
+    }
+
+    fn rng(&mut self, flag: &str) -> Result<RngKind, CliError> {
+        let value = self.value(flag)?;
+        if let Some(path) = value.strip_prefix("replay:") {
+            return Recording::read(Path::new(path))
+                .map(|recording| RngKind::Replay(recording.into()))
+                .map_err(|err| CliError(format!("cannot read {flag} recording {path:?}: {err}")));
+        }
+        if let Some(path) = value.strip_prefix("scripted:") {
+            return std::fs::read(path)
+                .map(|bytes| RngKind::Scripted(bytes.into()))
+                .map_err(|err| CliError(format!("cannot read {flag} script {path:?}: {err}")));
+        }
+        value
+            .parse()
+            .map_err(|err| CliError(format!("invalid {flag} {value:?}: {err}")))
+    }
+
+    fn words(&mut self, flag: &str) -> Result<Vec<Word>, CliError> {
+        let value = self.value(flag)?;
+        hex::decode_words(&value)
+            .map_err(|err| CliError(format!("invalid {flag} {value:?}: {err}")))
+    }
+
+    fn modulus(&mut self, flag: &str) -> Result<ModulusSpec, CliError> {
+        self.words(flag).map(ModulusSpec::Hex)
+    }
+
+    fn watchdog(&mut self, flag: &str) -> Result<Option<Duration>, CliError> {
+        let secs = self.parse(flag)?;
+        Ok((secs != 0).then(|| Duration::from_secs(secs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::Shape;
+
+    fn parse_line(line: &str) -> Result<Command, CliError> {
+        parse(line.split_whitespace().map(str::to_owned))
+    }
+
+    fn parsed(line: &str) -> Command {
+        parse_line(line).unwrap_or_else(|err| panic!("{line:?}: {err}"))
+    }
+
+    fn rejected(line: &str) -> String {
+        match parse_line(line) {
+            Ok(command) => panic!("{line:?} parsed as {command:?}"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    /// Asserts that each line parses to its command with these options.
+    macro_rules! assert_parses {
+        ($($line:expr => $command:ident($opts:expr)),* $(,)?) => {$(
+            match parsed($line) {
+                Command::$command(opts) => assert_eq!(opts, $opts, "{:?}", $line),
+                other => panic!("{:?} parsed as {other:?}", $line),
+            }
+        )*};
+    }
+
+    #[test]
+    fn every_command_has_its_defaults() {
+        assert_parses! {
+            "" => Run(RunOptions::default()),
+            "run" => Run(RunOptions::default()),
+            "sweep" => Sweep(SweepOptions::default()),
+            "verify-ct" => VerifyCt(VerifyOptions::default()),
+            "boundary" => Boundary(BoundaryOptions::default()),
+            "barriers" => Barriers(BarriersOptions::default()),
+            "matrix" => Matrix(MatrixOptions::default()),
+            "bisect" => Bisect(BisectOptions::default()),
+            "emit" => Emit(EmitOptions::default()),
+            "check-asm" => CheckAsm(CheckAsmOptions::default()),
+            "cross" => Cross(CrossOptions::default()),
+            "opt-bisect" => OptBisect(OptBisectOptions::default()),
+            "replay-ir" => ReplayIr(ReplayIrOptions::default()),
+            "feature-matrix" => FeatureMatrix(FeatureMatrixOptions::default()),
+            "shapes" => Shapes(ShapesOptions::default()),
+            "minimize --recording r.rng" => Minimize(MinimizeOptions {
+                recording: "r.rng".into(),
+                ..MinimizeOptions::default()
+            }),
+            "reproducer --candidate 0x5 --modulus 0x1_0000_0000_0000_0007" => Reproducer(
+                ReproducerOptions {
+                    candidate: vec![5],
+                    modulus: vec![7, 1],
+                    ..ReproducerOptions::default()
+                }
+            ),
+        }
+        assert!(matches!(parsed("help"), Command::Help));
+    }
+
+    #[test]
+    fn run_flags_without_a_command() {
+        assert_parses! {
+            "--seed 7 --limbs 8 --oracle --max-attempts 100" => Run(RunOptions {
+                seed: 7,
+                limbs: 8,
+                oracle: true,
+                max_attempts: Some(100),
+                ..RunOptions::default()
+            }),
+            "run --watchdog-secs 0 --shape while" => Run(RunOptions {
+                watchdog: None,
+                shape: Some(Shape::While),
+                ..RunOptions::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn matrix_flags_are_shared() {
+        let build = MatrixOptions {
+            opt_levels: vec!["0".into(), "3".into()],
+            codegen_units: vec![1, 16],
+            target_cpus: vec![None, Some("native".into())],
+            features: vec![vec![], vec!["a".into(), "b".into()]],
+            offline: true,
+            run_args: vec!["--max-attempts".into(), "10".into()],
+            ..MatrixOptions::default()
+        };
+        let flags = "--opt-levels 0,3 --codegen-units 1,16 --target-cpu default,native \
+                     --features none,a+b --offline -- --max-attempts 10";
+        assert_parses! {
+            &format!("matrix {flags}") => Matrix(build.clone()),
+            &format!("bisect --log b.log {flags}") => Bisect(BisectOptions {
+                build: build.clone(),
+                log: Some("b.log".into()),
+            }),
+        }
+    }
+
+    #[test]
+    fn run_rejects_conflicting_flags() {
+        let barrier = Variant::Volatile.name();
+        for line in [
+            format!("run --audit-choices --barrier {barrier}"),
+            "run --audit-choices --shape while".to_owned(),
+            format!("run --observe --barrier {barrier}"),
+            "run --observe --shape loop".to_owned(),
+            format!("run --shape loop --barrier {barrier}"),
+            "run --shape loop --max-attempts 10".to_owned(),
+        ] {
+            assert!(rejected(&line).contains("drop --barrier"), "{line}");
+        }
+        // Each alone is fine.
+        parsed(&format!("run --barrier {barrier} --max-attempts 10"));
+        parsed("run --audit-choices --observe --max-attempts 10");
+    }
+
+    #[test]
+    fn rejects_bad_values() {
+        for (line, error) in [
+            ("run --limbs 3", "unsupported --limbs 3"),
+            ("sweep --limbs 0", "unsupported --limbs 0"),
+            ("run --limbs five", "invalid --limbs \"five\""),
+            ("run --seed", "--seed requires a value"),
+            ("run --modulus 0xg", "invalid --modulus \"0xg\""),
+            ("run --rng mt19937", "invalid --rng \"mt19937\""),
+            ("run --shape spiral", "unknown shape \"spiral\""),
+            ("sweep --seeds 10", "expected <start>..<end>"),
+            (
+                "verify-ct --window-bits 17",
+                "--window-bits must be at most 16",
+            ),
+            ("matrix --opt-levels 4", "invalid --opt-levels \"4\""),
+            ("matrix --lto ,", "--lto requires at least one value"),
+            ("matrix --codegen-units x", "invalid --codegen-units \"x\""),
+            ("replay-ir --levels 0,fast", "invalid --levels \"fast\""),
+            ("minimize", "minimize requires --recording"),
+            (
+                "reproducer --modulus 0x7",
+                "reproducer requires --candidate",
+            ),
+            (
+                "reproducer --candidate 0x5",
+                "reproducer requires --modulus",
+            ),
+            ("frobnicate", "unknown command \"frobnicate\""),
+        ] {
+            let err = rejected(line);
+            assert!(err.contains(error), "{line:?}: {err}");
+        }
+        assert_parses! {
+            "sweep --limbs 16 --seeds 3..9" => Sweep(SweepOptions {
+                limbs: 16,
+                seeds: 3..9,
+                ..SweepOptions::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn every_command_rejects_unknown_flags() {
+        for command in [
+            "run",
+            "sweep",
+            "verify-ct",
+            "boundary",
+            "minimize",
+            "reproducer",
+            "barriers",
+            "matrix",
+            "bisect",
+            "emit",
+            "check-asm",
+            "cross",
+            "opt-bisect",
+            "replay-ir",
+            "feature-matrix",
+            "shapes",
+        ] {
+            let err = rejected(&format!("{command} --bogus"));
+            assert_eq!(err, "unknown option \"--bogus\"", "{command}");
+        }
+    }
+}
@@ -0,0 +1,419 @@
+//! The `cross` command: building the repro for another target, by default
+//! `aarch64-unknown-linux-gnu` where the hang was reported, and looking at
+//! it from an x86 host.
+//!
+//! Each configuration is built for the target and disassembled. The
+//! library's assembly for the target is checked as by `check-asm`, and when
+//! a user-mode emulator such as `qemu-aarch64` is on the path, the binary is
+//! run under it with the watchdog.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::asm_check::{self, FunctionReport};
+use crate::build_info::build_info;
+use crate::matrix::{self, MatrixOptions, Outcome, Profile};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+use crate::{emit, json, process, toolchain};
+
+/// Options for the `cross` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrossOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`.
+    pub build: MatrixOptions,
+    pub target: String,
+    /// Substrings of the symbols to disassemble and check.
+    pub functions: Vec<String>,
+    /// Defaults to `<arch>-linux-gnu-gcc` when that is on the path.
+    pub linker: Option<String>,
+    /// Defaults to `qemu-<arch>` or `qemu-<arch>-static` on the path.
+    pub runner: Option<String>,
+    /// Passed to the emulator as `-L`; defaults to `QEMU_LD_PREFIX` or
+    /// `/usr/<arch>-linux-gnu`.
+    pub sysroot: Option<PathBuf>,
+    /// Defaults to `target/cross` in the source directory.
+    pub out: Option<PathBuf>,
+}
+
+impl Default for CrossOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions {
+                opt_levels: vec!["3".into()],
+                ..MatrixOptions::default()
+            },
+            target: "aarch64-unknown-linux-gnu".into(),
+            functions: vec!["random_mod".into()],
+            linker: None,
+            runner: None,
+            sysroot: None,
+            out: None,
+        }
+    }
+}
+
+impl CrossOptions {
+    /// The first component of the target triple.
+    fn arch(&self) -> &str {
+        self.target.split('-').next().unwrap_or_default()
+    }
+
+    fn linker(&self) -> Option<String> {
+        self.linker.clone().or_else(|| {
+            let gcc = format!("{}-linux-gnu-gcc", self.arch());
+            process::which(&gcc).map(|_| gcc)
+        })
+    }
+
+    fn runner(&self) -> Option<String> {
+        self.runner.clone().or_else(|| {
+            let qemu = format!("qemu-{}", self.arch());
+            [qemu.clone(), format!("{qemu}-static")]
+                .into_iter()
+                .find(|name| process::which(name).is_some())
+        })
+    }
+
+    fn sysroot(&self) -> Option<PathBuf> {
+        self.sysroot
+            .clone()
+            .or_else(|| std::env::var_os("QEMU_LD_PREFIX").map(PathBuf::from))
+            .or_else(|| {
+                let dir = PathBuf::from(format!("/usr/{}-linux-gnu", self.arch()));
+                dir.is_dir().then_some(dir)
+            })
+    }
+}
+
+/// What was done for one configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Crossed {
+    pub toolchain: String,
+    pub profile: Profile,
+    /// Holds `disasm.txt`, `functions.txt` and `module.s`.
+    pub dir: PathBuf,
+    /// The linked binary, or the tail of the build log.
+    pub binary: Result<PathBuf, String>,
+    /// Number of matching functions in the disassembly.
+    pub disassembly: Result<usize, String>,
+    /// `check-asm` on the library's assembly for the target.
+    pub reports: Result<Vec<FunctionReport>, String>,
+    /// `None` when there was nothing to run or nothing to run it with.
+    pub outcome: Option<Outcome>,
+}
+
+impl Crossed {
+    fn failed(&self) -> bool {
+        self.outcome.as_ref().is_some_and(|o| *o != Outcome::Pass)
+            || self
+                .reports
+                .as_ref()
+                .is_ok_and(|reports| reports.iter().any(FunctionReport::failed))
+    }
+}
+
+/// Runs `llvm-objdump`, or the target's binutils `objdump`, on `binary`.
+fn disassemble(binary: &Path, arch: &str) -> Result<String, String> {
+    let objdump = [
+        "llvm-objdump".to_owned(),
+        format!("{arch}-linux-gnu-objdump"),
+    ]
+    .into_iter()
+    .find(|name| process::which(name).is_some())
+    .ok_or_else(|| format!("neither llvm-objdump nor {arch}-linux-gnu-objdump is on the path"))?;
+    let out = Command::new(&objdump)
+        .args(["-d", "--no-show-raw-insn"])
+        .arg(binary)
+        .output()
+        .map_err(|err| format!("cannot run {objdump}: {err}"))?;
+    if !out.status.success() {
+        return Err(String::from_utf8_lossy(&out.stderr).trim().to_owned());
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// The blocks of `objdump -d` output whose `<symbol>:` header matches
+/// `patterns`, as by [`emit::matches`], with symbol hashes stripped.
+fn disassembled_functions(disassembly: &str, patterns: &[String]) -> Vec<String> {
+    disassembly
+        .split("\n\n")
+        .filter(|block| {
+            block
+                .lines()
+                .next()
+                .and_then(|header| header.split_once('<'))
+                .is_some_and(|(_, symbol)| emit::matches(symbol, patterns))
+        })
+        .map(emit::strip_hashes)
+        .collect()
+}
+
+/// Links, disassembles and writes out one configuration's binary, returning
+/// its path and the number of matching functions.
+fn build_binary(
+    opts: &CrossOptions,
+    toolchain: Option<&str>,
+    profile: &Profile,
+    target_dir: &Path,
+    dir: &Path,
+) -> (Result<PathBuf, String>, Result<usize, String>) {
+    let build = &opts.build;
+    let linker_var = format!(
+        "CARGO_TARGET_{}_LINKER",
+        opts.target.to_uppercase().replace('-', "_")
+    );
+    let mut command = matrix::build_command(
+        toolchain,
+        Some(&opts.target),
+        profile,
+        &build.source,
+        target_dir,
+        build.offline,
+    );
+    // Without a linker we still try, in case cargo's config names one.
+    if let Some(linker) = opts
+        .linker()
+        .filter(|_| std::env::var_os(&linker_var).is_none())
+    {
+        command.env(&linker_var, linker);
+    }
+    let binary = matrix::run_build(command, Some(&opts.target), target_dir, build.build_timeout)
+        .map(|(binary, _)| binary);
+    let disassembly = binary.as_ref().map_err(Clone::clone).and_then(|binary| {
+        let text = disassemble(binary, opts.arch())?;
+        let functions = disassembled_functions(&text, &opts.functions);
+        let io = |err: std::io::Error| err.to_string();
+        fs::create_dir_all(dir).map_err(io)?;
+        fs::write(dir.join("disasm.txt"), &text).map_err(io)?;
+        fs::write(dir.join("functions.txt"), functions.join("\n\n")).map_err(io)?;
+        Ok(functions.len())
+    });
+    (binary, disassembly)
+}
+
+fn cross_one(
+    opts: &CrossOptions,
+    out: &Path,
+    toolchain: Option<&str>,
+    profile: &Profile,
+    runner: Option<&str>,
+) -> Crossed {
+    let build = &opts.build;
+    let name = toolchain.unwrap_or("default");
+    let dir = out.join(name).join(profile.dir_name());
+    let target_dir = build.target_dir(name, profile);
+    eprintln!("building {name} {} for {}", profile.label(), opts.target);
+    // The host's CPU means nothing to the target.
+    let mut profile = profile.clone();
+    profile.target_cpu = profile.target_cpu.filter(|cpu| cpu != "native");
+    let profile = &profile;
+    let (binary, disassembly) = build_binary(opts, toolchain, profile, &target_dir, &dir);
+    if let Err(log) = &binary {
+        eprintln!("{log}");
+    }
+
+    let reports = emit::emit_build(
+        toolchain,
+        Some(&opts.target),
+        profile,
+        &build.source,
+        &target_dir,
+        build.offline,
+        build.build_timeout,
+    )
+    .and_then(|(_, asm)| {
+        fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        fs::write(dir.join("module.s"), &asm).map_err(|err| err.to_string())?;
+        Ok(asm_check::check(&asm, &opts.functions))
+    });
+
+    let outcome = match (&binary, runner) {
+        (Ok(binary), Some(runner)) => {
+            let mut command = Command::new(runner);
+            if let Some(sysroot) = opts.sysroot() {
+                command.arg("-L").arg(sysroot);
+            }
+            command.arg(binary);
+            Some(matrix::run_with(command, &build.run_args, build.timeout))
+        }
+        _ => None,
+    };
+    if let Some(outcome) = &outcome {
+        eprintln!("  {}", outcome.name());
+    }
+    Crossed {
+        toolchain: name.to_owned(),
+        profile: profile.clone(),
+        dir,
+        binary,
+        disassembly,
+        reports,
+        outcome,
+    }
+}
+
+fn crossed_object(c: &Crossed) -> String {
+    let obj = json::Object::new()
+        .str("toolchain", &c.toolchain)
+        .str("profile", &c.profile.label())
+        .str("dir", &c.dir.display().to_string());
+    let obj = match &c.binary {
+        Ok(binary) => obj.str("binary", &binary.display().to_string()),
+        Err(err) => obj.str("build_error", err),
+    };
+    let obj = match &c.disassembly {
+        Ok(n) => obj.u64("disassembled_functions", *n as u64),
+        Err(err) => obj.str("disassembly_error", err),
+    };
+    let obj = match &c.reports {
+        Ok(reports) => asm_check::add_verdict(obj, reports),
+        Err(err) => obj.str("asm_error", err),
+    };
+    match &c.outcome {
+        Some(outcome) => obj.str("outcome", outcome.name()),
+        None => obj.raw("outcome", "null"),
+    }
+    .finish()
+}
+
+fn print_text(crossed: &[Crossed]) {
+    for c in crossed {
+        println!("{} {}:", c.toolchain, c.profile.label());
+        match &c.binary {
+            Ok(binary) => println!("  binary       {}", binary.display()),
+            Err(_) => println!("  binary       build failed"),
+        }
+        match &c.disassembly {
+            Ok(n) => println!("  disassembly  {n} functions in {}", c.dir.display()),
+            Err(err) => println!("  disassembly  {}", err.lines().next().unwrap_or(err)),
+        }
+        match &c.reports {
+            Ok(reports) => println!("  check-asm    {}", asm_check::summary(reports)),
+            Err(err) => println!("  check-asm    {}", matrix::tail(err, 1)),
+        }
+        match &c.outcome {
+            Some(outcome) => println!("  run          {}", outcome.name()),
+            None => println!("  run          not run"),
+        }
+    }
+}
+
+/// Builds, disassembles, checks and, where possible, runs every
+/// configuration for the target. Returns the process exit code.
+pub fn cross(opts: &CrossOptions) -> i32 {
+    let profiles = opts.build.profiles();
+    if profiles.is_empty() {
+        eprintln!("cross has no profiles");
+        return EXIT_USAGE;
+    }
+    let out = opts
+        .out
+        .clone()
+        .unwrap_or_else(|| opts.build.source.join("target").join("cross"));
+    let runner = opts.runner();
+    match &runner {
+        Some(runner) => eprintln!("running under {runner}"),
+        None => eprintln!(
+            "no qemu-{} on the path; building and checking only",
+            opts.arch()
+        ),
+    }
+
+    let mut crossed = Vec::new();
+    let mut missing = Vec::new();
+    for toolchain in matrix::toolchains(&opts.build.toolchains) {
+        let name = toolchain.as_deref().unwrap_or("default");
+        match toolchain::has_target(toolchain.as_deref(), &opts.target) {
+            Ok(true) => {}
+            Ok(false) => {
+                eprintln!(
+                    "{name} has no {target}; run `rustup target add {target} --toolchain {name}`",
+                    target = opts.target
+                );
+                missing.push(name.to_owned());
+                continue;
+            }
+            Err(err) => {
+                eprintln!("cannot find {name}'s sysroot: {err}");
+                missing.push(name.to_owned());
+                continue;
+            }
+        }
+        for profile in &profiles {
+            crossed.push(cross_one(
+                opts,
+                &out,
+                toolchain.as_deref(),
+                profile,
+                runner.as_deref(),
+            ));
+        }
+    }
+
+    match opts.build.format {
+        Format::Text => {
+            print_text(&crossed);
+            for name in &missing {
+                println!("{name}: {} not installed", opts.target);
+            }
+        }
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .str("target", &opts.target)
+                .str("out", &out.display().to_string())
+                .raw("configs", &json::array(crossed.iter().map(crossed_object)))
+                .raw(
+                    "missing_target",
+                    &json::array(missing.iter().map(|name| {
+                        let mut s = String::new();
+                        json::write_str(&mut s, name);
+                        s
+                    })),
+                )
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => crossed
+            .iter()
+            .for_each(|c| println!("{}", crossed_object(c))),
+    }
+
+    if crossed.iter().any(Crossed::failed) {
+        EXIT_DIVERGED
+    } else if crossed.is_empty() {
+        EXIT_USAGE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const X86_64_INLINE_NEVER: &str = include_str!("../testdata/asm/x86_64-inline-never.s");
+
+    #[test]
+    fn inconclusive_asm_is_not_a_failure() {
+        let reports = asm_check::check(X86_64_INLINE_NEVER, &["shapes".into()]);
+        assert!(reports[0].inconclusive());
+        let mut crossed = Crossed {
+            toolchain: "stable".into(),
+            profile: MatrixOptions::default().profiles().remove(0),
+            dir: PathBuf::new(),
+            binary: Err(String::new()),
+            disassembly: Ok(1),
+            reports: Ok(reports),
+            outcome: None,
+        };
+        assert!(!crossed.failed());
+        crossed.outcome = Some(Outcome::Hung);
+        assert!(crossed.failed());
+        let json = crossed_object(&crossed);
+        assert!(json.contains("\"asm_failed\":[]"), "{json}");
+        assert!(!json.contains("\"asm_inconclusive\":[]"), "{json}");
+    }
+}
@@ -0,0 +1,505 @@
+//! The `emit` command: LLVM IR and assembly of the sampling loop for each
+//! configuration of a build matrix, and diffs between them.
+//!
+//! Each configuration is built and run as by `matrix`, then the library is
+//! rebuilt with `--emit=llvm-ir,asm` and the functions whose symbols match
+//! are extracted, with symbol hashes stripped so builds can be diffed. Only
+//! the library is emitted: the generic samplers are instantiated by
+//! `run.rs`, which is part of the library rather than the binary, so their
+//! code is there.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::build_info::build_info;
+use crate::matrix::{self, Cell, MatrixOptions, Outcome, Profile};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+use crate::{json, process, toolchain};
+
+/// Options for the `emit` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EmitOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`.
+    pub build: MatrixOptions,
+    /// Substrings of the symbols to extract.
+    pub functions: Vec<String>,
+    /// Defaults to `target/emit` in the source directory.
+    pub out: Option<PathBuf>,
+}
+
+impl Default for EmitOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions::default(),
+            functions: ["random_mod", "sample_observed", "ct_lt"]
+                .map(String::from)
+                .into(),
+            out: None,
+        }
+    }
+}
+
+/// One extracted function.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Function {
+    /// The symbol, with its hash stripped.
+    pub name: String,
+    pub body: String,
+}
+
+/// Replaces legacy symbol hashes (`17h` and 16 hex digits, then `E`) with
+/// `17hE`, so the same function has the same name in every build.
+pub fn strip_hashes(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hash = bytes.get(i..i + 20).filter(|h| {
+            h.starts_with(b"17h") && h[19] == b'E' && h[3..19].iter().all(u8::is_ascii_hexdigit)
+        });
+        if hash.is_some() {
+            out.push_str("17hE");
+            i += 20;
+        } else {
+            let c = text[i..].chars().next().unwrap_or_default();
+            out.push(c);
+            i += c.len_utf8();
+        }
+    }
+    out
+}
+
+/// Whether `symbol` contains one of `patterns`. The samplers of
+/// [`shapes`](crate::shapes) share the real sampler's names, and one of them
+/// compares in a callee no check can see into, so their symbols are left out
+/// unless a pattern names `shapes`.
+pub fn matches(symbol: &str, patterns: &[String]) -> bool {
+    if symbol.contains("6shapes") && !patterns.iter().any(|p| p.contains("shapes")) {
+        return false;
+    }
+    patterns.iter().any(|p| symbol.contains(p.as_str()))
+}
+
+/// Functions defined in LLVM IR whose names contain one of `patterns`.
+pub fn extract_ir(ir: &str, patterns: &[String]) -> Vec<Function> {
+    extract(
+        ir,
+        patterns,
+        |line| {
+            let rest = line.strip_prefix("define ")?;
+            let name = &rest[rest.find('@')? + 1..];
+            Some(name[..name.find('(')?].trim_matches('"'))
+        },
+        |line| line == "}",
+    )
+}
+
+/// Functions in assembly whose labels contain one of `patterns`. ELF
+/// assembly ends each at `.Lfunc_end<n>:`, Mach-O at `Lfunc_end<n>:`.
+pub fn extract_asm(asm: &str, patterns: &[String]) -> Vec<Function> {
+    extract(
+        asm,
+        patterns,
+        |line| {
+            let label = line.strip_suffix(':')?;
+            let plain =
+                !label.starts_with(['.', ' ', '\t']) && !label.contains(char::is_whitespace);
+            plain.then(|| label.trim_matches('"'))
+        },
+        |line| line.trim_start_matches('.').starts_with("Lfunc_end"),
+    )
+}
+
+fn extract<'a>(
+    text: &'a str,
+    patterns: &[String],
+    start: impl Fn(&'a str) -> Option<&'a str>,
+    end: impl Fn(&str) -> bool,
+) -> Vec<Function> {
+    let mut functions = Vec::new();
+    let mut current: Option<Function> = None;
+    for line in text.lines() {
+        if let Some(function) = &mut current {
+            function.body.push_str(line);
+            function.body.push('\n');
+            if end(line) {
+                functions.extend(current.take());
+            }
+            continue;
+        }
+        let Some(name) = start(line) else {
+            continue;
+        };
+        if matches(name, patterns) {
+            current = Some(Function {
+                name: strip_hashes(name),
+                body: format!("{line}\n"),
+            });
+        }
+    }
+    functions.extend(current);
+    for function in &mut functions {
+        function.body = strip_hashes(&function.body);
+    }
+    // Stable sort: instantiations sharing a name keep their emitted order.
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    functions
+}
+
+/// `subtle_repro-<hash>`, the stem of the library's outputs in `deps`, from
+/// the `compiler-artifact` message cargo printed for it.
+///
+/// Cargo prints the message whether or not it rebuilt the library, so the
+/// stem is this configuration's even in a reused target directory.
+pub fn artifact_stem(messages: &str) -> Option<String> {
+    const LIB: &str = "/libsubtle_repro-";
+    messages
+        .lines()
+        .rev()
+        .filter(|line| line.contains(r#""reason":"compiler-artifact""#))
+        .filter_map(|line| {
+            let hash = &line[line.find(LIB)? + LIB.len()..];
+            let len = hash.find(|c: char| !c.is_ascii_alphanumeric())?;
+            Some(format!("subtle_repro-{}", &hash[..len]))
+        })
+        .next()
+}
+
+/// The emitted `extension` output of the library whose outputs share `stem`.
+///
+/// rustc writes one file per crate, unless it keeps one per codegen unit;
+/// those are concatenated in unit order.
+fn read_emitted(deps: &Path, stem: &str, extension: &str) -> Result<String, String> {
+    let io = |err: std::io::Error| err.to_string();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(deps).map_err(io)? {
+        let name = entry
+            .map_err(io)?
+            .file_name()
+            .to_string_lossy()
+            .into_owned();
+        if name.starts_with(stem) && name.ends_with(extension) {
+            files.push(name);
+        }
+    }
+    let whole = format!("{stem}{extension}");
+    let mut parts: Vec<&String> = files
+        .iter()
+        .filter(|name| **name == whole || name.starts_with(&format!("{stem}.")))
+        .collect();
+    if parts.is_empty() {
+        return Err(format!("no {extension} file was emitted for {stem}"));
+    }
+    if parts.contains(&&whole) {
+        parts.retain(|name| **name == whole);
+    }
+    parts.sort();
+    let mut text = String::new();
+    for part in parts {
+        text.push_str(&fs::read_to_string(deps.join(part)).map_err(io)?);
+    }
+    Ok(text)
+}
+
+/// Rebuilds the library into `target_dir` with `--emit=llvm-ir,asm`, for
+/// the host unless `target` names another, returning the IR and assembly,
+/// or the tail of the build log.
+pub fn emit_build(
+    toolchain: Option<&str>,
+    target: Option<&str>,
+    profile: &Profile,
+    source: &Path,
+    target_dir: &Path,
+    offline: bool,
+    timeout: Duration,
+) -> Result<(String, String), String> {
+    let command = emit_command(toolchain, target, profile, source, target_dir, offline);
+    run_emit(command, target, target_dir, timeout).map(|(ir, asm, _)| (ir, asm))
+}
+
+/// The `cargo rustc` that [`emit_build`] runs, for callers that need to add
+/// to its environment.
+pub fn emit_command(
+    toolchain: Option<&str>,
+    target: Option<&str>,
+    profile: &Profile,
+    source: &Path,
+    target_dir: &Path,
+    offline: bool,
+) -> Command {
+    let mut command = toolchain::cargo(toolchain);
+    command
+        .args(["rustc", "--release", "--lib"])
+        .arg("--message-format=json-render-diagnostics")
+        .arg("--manifest-path")
+        .arg(source.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", target_dir);
+    if offline {
+        command.arg("--offline");
+    }
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    profile.apply(&mut command);
+    command.args(["--", "--emit=llvm-ir,asm"]);
+    command
+}
+
+/// Runs an [`emit_command`], returning the IR, the assembly and the build's
+/// stderr.
+pub fn run_emit(
+    mut command: Command,
+    target: Option<&str>,
+    target_dir: &Path,
+    timeout: Duration,
+) -> Result<(String, String, String), String> {
+    let out = process::run(&mut command, timeout).map_err(|err| err.to_string())?;
+    if !out.success() {
+        return Err(matrix::tail(&out.stderr, 20));
+    }
+    let deps = match target {
+        Some(target) => target_dir.join(target),
+        None => target_dir.to_owned(),
+    }
+    .join("release")
+    .join("deps");
+    let stem = artifact_stem(&out.stdout)
+        .ok_or_else(|| "cargo reported no artifact for the library".to_owned())?;
+    Ok((
+        read_emitted(&deps, &stem, ".ll")?,
+        read_emitted(&deps, &stem, ".s")?,
+        out.stderr,
+    ))
+}
+
+/// One configuration's outcome and extracted code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Emitted {
+    pub cell: Cell,
+    /// Holds `module.ll`, `module.s`, `functions.ll` and `functions.s`.
+    pub dir: PathBuf,
+    /// Functions extracted from the IR, or why nothing was.
+    pub functions: Result<usize, String>,
+}
+
+impl Emitted {
+    fn id(&self) -> String {
+        format!("{}--{}", self.cell.toolchain, self.cell.profile.dir_name())
+    }
+}
+
+/// The bodies of `functions`, separated by blank lines.
+pub fn join(functions: &[Function]) -> String {
+    functions
+        .iter()
+        .map(|f| f.body.as_str())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Emits one built configuration and writes its code to `dir`, returning
+/// the number of functions extracted from the IR.
+fn write_code(
+    opts: &EmitOptions,
+    toolchain: Option<&str>,
+    cell: &Cell,
+    dir: &Path,
+) -> Result<usize, String> {
+    let build = &opts.build;
+    if cell.outcome == Outcome::BuildFailed {
+        return Err("build failed".to_owned());
+    }
+    let target_dir = build.target_dir(&cell.toolchain, &cell.profile);
+    let (ir, asm) = emit_build(
+        toolchain,
+        None,
+        &cell.profile,
+        &build.source,
+        &target_dir,
+        build.offline,
+        build.build_timeout,
+    )?;
+    let io = |err: std::io::Error| err.to_string();
+    let ir_functions = extract_ir(&ir, &opts.functions);
+    let asm_functions = extract_asm(&asm, &opts.functions);
+    fs::create_dir_all(dir).map_err(io)?;
+    fs::write(dir.join("module.ll"), &ir).map_err(io)?;
+    fs::write(dir.join("module.s"), &asm).map_err(io)?;
+    fs::write(dir.join("functions.ll"), join(&ir_functions)).map_err(io)?;
+    fs::write(dir.join("functions.s"), join(&asm_functions)).map_err(io)?;
+    fs::write(dir.join("outcome"), format!("{}\n", cell.outcome.name())).map_err(io)?;
+    Ok(ir_functions.len())
+}
+
+fn emit_one(opts: &EmitOptions, out: &Path, toolchain: Option<&str>, profile: &Profile) -> Emitted {
+    let cell = matrix::cell(&opts.build, toolchain, profile);
+    let dir = out.join(&cell.toolchain).join(profile.dir_name());
+    let functions = write_code(opts, toolchain, &cell, &dir);
+    if let Err(err) = &functions {
+        eprintln!("  emit failed: {err}");
+    }
+    Emitted {
+        cell,
+        dir,
+        functions,
+    }
+}
+
+/// Writes `diff -u old new` to `out`, returning whether they differ.
+pub fn diff(old: &Path, new: &Path, out: &Path) -> Result<bool, String> {
+    let output = Command::new("diff")
+        .arg("-u")
+        .args([old, new])
+        .output()
+        .map_err(|err| format!("cannot run diff: {err}"))?;
+    match output.status.code() {
+        Some(0) | Some(1) => {
+            fs::write(out, &output.stdout).map_err(|err| err.to_string())?;
+            Ok(output.status.code() == Some(1))
+        }
+        _ => Err(String::from_utf8_lossy(&output.stderr).trim().to_owned()),
+    }
+}
+
+/// A diff of one configuration against the baseline.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diff {
+    pub config: String,
+    pub baseline: String,
+    pub ir: PathBuf,
+    pub asm: PathBuf,
+    pub differs: bool,
+}
+
+fn config_object(e: &Emitted, diff: Option<&Diff>) -> json::Object {
+    let obj = matrix::cell_object(&e.cell).str("dir", &e.dir.display().to_string());
+    let obj = match &e.functions {
+        Ok(n) => obj.u64("functions", *n as u64),
+        Err(err) => obj.str("error", err),
+    };
+    match diff {
+        Some(d) => diff_object(obj, d),
+        None => obj,
+    }
+}
+
+/// Adds `d`'s fields to `obj`.
+fn diff_object(obj: json::Object, d: &Diff) -> json::Object {
+    obj.str("config", &d.config)
+        .str("baseline", &d.baseline)
+        .str("ir", &d.ir.display().to_string())
+        .str("asm", &d.asm.display().to_string())
+        .bool("differs", d.differs)
+}
+
+/// Builds, runs and disassembles every configuration, then diffs each one
+/// against the first that passed. Returns the process exit code.
+pub fn emit(opts: &EmitOptions) -> i32 {
+    let profiles = opts.build.profiles();
+    if profiles.is_empty() {
+        eprintln!("emit has no profiles");
+        return EXIT_USAGE;
+    }
+    let out = opts
+        .out
+        .clone()
+        .unwrap_or_else(|| opts.build.source.join("target").join("emit"));
+    let mut emitted = Vec::new();
+    for toolchain in matrix::toolchains(&opts.build.toolchains) {
+        for profile in &profiles {
+            emitted.push(emit_one(opts, &out, toolchain.as_deref(), profile));
+        }
+    }
+
+    let mut diffs = Vec::new();
+    let usable = || emitted.iter().filter(|e| e.functions.is_ok());
+    if let Some(baseline) = usable().find(|e| e.cell.outcome == Outcome::Pass) {
+        let diff_dir = out.join("diff");
+        if let Err(err) = fs::create_dir_all(&diff_dir) {
+            eprintln!("cannot create {}: {err}", diff_dir.display());
+            return EXIT_USAGE;
+        }
+        for e in usable().filter(|e| *e != baseline) {
+            let ir = diff_dir.join(format!("{}.ll.diff", e.id()));
+            let asm = diff_dir.join(format!("{}.s.diff", e.id()));
+            let differs = diff(
+                &baseline.dir.join("functions.ll"),
+                &e.dir.join("functions.ll"),
+                &ir,
+            )
+            .and_then(|a| {
+                diff(
+                    &baseline.dir.join("functions.s"),
+                    &e.dir.join("functions.s"),
+                    &asm,
+                )
+                .map(|b| a || b)
+            });
+            match differs {
+                Ok(differs) => diffs.push(Diff {
+                    config: e.id(),
+                    baseline: baseline.id(),
+                    ir,
+                    asm,
+                    differs,
+                }),
+                Err(err) => eprintln!("cannot diff {}: {err}", e.id()),
+            }
+        }
+    } else {
+        eprintln!("no configuration passed; nothing to diff against");
+    }
+
+    match opts.build.format {
+        Format::Text => {
+            let width = emitted.iter().map(|e| e.id().len()).max().unwrap_or(0);
+            println!("{:<width$}  {:<12}  functions", "configuration", "outcome");
+            for e in &emitted {
+                let functions = match &e.functions {
+                    Ok(n) => n.to_string(),
+                    Err(err) => err.clone(),
+                };
+                println!(
+                    "{:<width$}  {:<12}  {functions}",
+                    e.id(),
+                    e.cell.outcome.name()
+                );
+            }
+            for d in &diffs {
+                let verb = if d.differs { "differs" } else { "same" };
+                println!("{} vs {}: {verb}: {}", d.config, d.baseline, d.ir.display());
+            }
+            println!("output in {}", out.display());
+        }
+        Format::Json => {
+            let configs = emitted.iter().map(|e| config_object(e, None).finish());
+            let diffs = diffs
+                .iter()
+                .map(|d| diff_object(json::Object::new(), d).finish());
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .str("out", &out.display().to_string())
+                .raw("configs", &json::array(configs))
+                .raw("diffs", &json::array(diffs))
+                .finish();
+            println!("{report}");
+        }
+        // One line per configuration, with its diff against the baseline if
+        // it has one.
+        Format::Ndjson => {
+            for e in &emitted {
+                let diff = diffs.iter().find(|d| d.config == e.id());
+                println!("{}", config_object(e, diff).finish());
+            }
+        }
+    }
+
+    if emitted.iter().all(|e| e.cell.outcome == Outcome::Pass) {
+        0
+    } else {
+        EXIT_DIVERGED
+    }
+}
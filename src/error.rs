@@ -0,0 +1,40 @@
+use core::fmt;
+
+use crypto_bigint::Uint;
+
+use crate::hex;
+use crate::oracle::Divergence;
+
+/// Why a bounded sampler gave up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SamplingError<const L: usize> {
+    /// Every one of `attempts` candidates was rejected by `ct_lt`.
+    Exhausted {
+        attempts: u64,
+        last_candidate: Uint<L>,
+        modulus: Uint<L>,
+    },
+    /// `ct_lt` disagreed with a variable-time comparison.
+    Diverged(Divergence<L>),
+}
+
+impl<const L: usize> fmt::Display for SamplingError<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exhausted {
+                attempts,
+                last_candidate,
+                modulus,
+            } => write!(
+                f,
+                "no candidate accepted after {attempts} attempts \
+                 (last candidate {}, modulus {})",
+                hex::encode(last_candidate),
+                hex::encode(modulus),
+            ),
+            Self::Diverged(divergence) => divergence.fmt(f),
+        }
+    }
+}
+
+impl<const L: usize> std::error::Error for SamplingError<L> {}
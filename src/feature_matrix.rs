@@ -0,0 +1,237 @@
+//! The `feature-matrix` command: which of subtle's optimization barriers
+//! keeps `ct_lt` correct on each toolchain.
+//!
+//! subtle hides every `Choice` behind a barrier: a volatile read by
+//! default, or `core::hint::black_box` with its `core_hint_black_box`
+//! feature, which this crate forwards. Every combination of the forwarded
+//! features is built under each profile and toolchain as by `matrix`, and
+//! run with `--oracle`, so each of the sampler's `ct_lt` decisions is
+//! checked against a variable-time comparison. A barrier configuration is
+//! safe on a toolchain when every profile passes.
+
+use crate::build_info::build_info;
+use crate::json;
+use crate::matrix::{self, Cell, MatrixOptions, Outcome};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+
+/// The features of this crate that select subtle's barrier.
+pub const BARRIER_FEATURES: [&str; 1] = ["core_hint_black_box"];
+
+/// Options for the `feature-matrix` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeatureMatrixOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`; `--oracle`
+    /// is always passed.
+    pub build: MatrixOptions,
+}
+
+impl Default for FeatureMatrixOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions {
+                features: feature_sets(&BARRIER_FEATURES),
+                ..MatrixOptions::default()
+            },
+        }
+    }
+}
+
+/// Every subset of `features`, the empty one first.
+pub fn feature_sets(features: &[&str]) -> Vec<Vec<String>> {
+    let mut sets = vec![Vec::new()];
+    for feature in features {
+        let with: Vec<Vec<String>> = sets
+            .iter()
+            .map(|set| {
+                let mut set = set.clone();
+                set.push((*feature).to_owned());
+                set
+            })
+            .collect();
+        sets.extend(with);
+    }
+    sets
+}
+
+/// Whether a barrier configuration held on one toolchain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Safety {
+    /// Every profile passed.
+    Safe,
+    /// These profiles hung, diverged or ran out of attempts.
+    Unsafe(Vec<String>),
+    /// Nothing failed, but these profiles did not build or run.
+    Unknown(Vec<String>),
+}
+
+impl Safety {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Safe => "safe",
+            Self::Unsafe(_) => "unsafe",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+
+    fn of(cells: &[&Cell]) -> Self {
+        let labels = |pick: fn(&Outcome) -> bool| -> Vec<String> {
+            cells
+                .iter()
+                .filter(|c| pick(&c.outcome))
+                .map(|c| format!("{}: {}", c.profile.label(), c.outcome.name()))
+                .collect()
+        };
+        let failed =
+            labels(|o| matches!(o, Outcome::Hung | Outcome::Diverged | Outcome::Exhausted));
+        let unknown = labels(|o| matches!(o, Outcome::Failed(_) | Outcome::BuildFailed));
+        if !failed.is_empty() {
+            Self::Unsafe(failed)
+        } else if !unknown.is_empty() {
+            Self::Unknown(unknown)
+        } else {
+            Self::Safe
+        }
+    }
+}
+
+/// One barrier configuration on one toolchain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Verdict {
+    pub toolchain: String,
+    pub features: Vec<String>,
+    pub safety: Safety,
+}
+
+fn features_name(features: &[String]) -> String {
+    if features.is_empty() {
+        "default".to_owned()
+    } else {
+        features.join("+")
+    }
+}
+
+fn verdict_object(v: &Verdict) -> String {
+    let strs = |items: &[String]| {
+        json::array(items.iter().map(|item| {
+            let mut s = String::new();
+            json::write_str(&mut s, item);
+            s
+        }))
+    };
+    let obj = json::Object::new()
+        .str("toolchain", &v.toolchain)
+        .raw("features", &strs(&v.features))
+        .str("safety", v.safety.name());
+    match &v.safety {
+        Safety::Safe => obj,
+        Safety::Unsafe(failed) => obj.raw("failed", &strs(failed)),
+        Safety::Unknown(unknown) => obj.raw("unknown", &strs(unknown)),
+    }
+    .finish()
+}
+
+/// Barrier configurations down the side, toolchains across the top.
+fn print_verdicts(toolchains: &[&str], sets: &[Vec<String>], verdicts: &[Verdict]) {
+    let width = sets
+        .iter()
+        .map(|set| features_name(set).len())
+        .max()
+        .unwrap_or(0)
+        .max("barrier features".len());
+    let mut header = format!("{:<width$}", "barrier features");
+    for name in toolchains {
+        header.push_str(&format!("  {name:<12}"));
+    }
+    println!("{header}");
+    for set in sets {
+        let mut row = format!("{:<width$}", features_name(set));
+        for name in toolchains {
+            let safety = verdicts
+                .iter()
+                .find(|v| v.toolchain == *name && v.features == *set)
+                .map_or("", |v| v.safety.name());
+            row.push_str(&format!("  {safety:<w$}", w = name.len().max(12)));
+        }
+        println!("{}", row.trim_end());
+    }
+    for v in verdicts {
+        if let Safety::Unsafe(profiles) | Safety::Unknown(profiles) = &v.safety {
+            println!("{} {}:", v.toolchain, features_name(&v.features));
+            for profile in profiles {
+                println!("  {profile}");
+            }
+        }
+    }
+}
+
+/// Builds and runs every barrier configuration and prints which are safe,
+/// returning the process exit code.
+pub fn feature_matrix(opts: &FeatureMatrixOptions) -> i32 {
+    let mut build = opts.build.clone();
+    if !build.run_args.iter().any(|arg| arg == "--oracle") {
+        build.run_args.insert(0, "--oracle".into());
+    }
+    let profiles = build.profiles();
+    if profiles.is_empty() {
+        eprintln!("feature-matrix has no profiles");
+        return EXIT_USAGE;
+    }
+    let toolchains = matrix::toolchains(&build.toolchains);
+    let mut cells = Vec::new();
+    for toolchain in &toolchains {
+        for profile in &profiles {
+            cells.push(matrix::cell(&build, toolchain.as_deref(), profile));
+        }
+    }
+
+    let names: Vec<&str> = toolchains
+        .iter()
+        .map(|t| t.as_deref().unwrap_or("default"))
+        .collect();
+    let mut verdicts = Vec::new();
+    for name in &names {
+        for set in &build.features {
+            let of_config: Vec<&Cell> = cells
+                .iter()
+                .filter(|c| c.toolchain == *name && c.profile.features == *set)
+                .collect();
+            verdicts.push(Verdict {
+                toolchain: (*name).to_owned(),
+                features: set.clone(),
+                safety: Safety::of(&of_config),
+            });
+        }
+    }
+
+    match build.format {
+        Format::Text => {
+            matrix::print_grid(&toolchains, &profiles, &cells);
+            println!();
+            print_verdicts(&names, &build.features, &verdicts);
+        }
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .raw(
+                    "cells",
+                    &json::array(cells.iter().map(|c| matrix::cell_object(c).finish())),
+                )
+                .raw(
+                    "verdicts",
+                    &json::array(verdicts.iter().map(verdict_object)),
+                )
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => verdicts
+            .iter()
+            .for_each(|v| println!("{}", verdict_object(v))),
+    }
+
+    if verdicts.iter().all(|v| v.safety == Safety::Safe) {
+        0
+    } else {
+        EXIT_DIVERGED
+    }
+}
@@ -0,0 +1,72 @@
+//! Fixed-width big-endian hex for `Uint`, independent of crypto-bigint's
+//! formatting impls.
+
+use core::fmt;
+
+use crypto_bigint::{Limb, Uint, Word};
+
+/// Formats `x` as `0x`-prefixed big-endian hex, zero-padded to the full width.
+pub fn encode<const L: usize>(x: &Uint<L>) -> String {
+    encode_words(x.as_words())
+}
+
+/// Like [`encode`], for little-endian words of any count.
+pub fn encode_words(words: &[Word]) -> String {
+    let mut s = String::with_capacity(2 + words.len() * Limb::BYTES * 2);
+    s.push_str("0x");
+    for w in words.iter().rev() {
+        s.push_str(&format!("{w:0width$x}", width = Limb::BYTES * 2));
+    }
+    s
+}
+
+/// Why a hex string could not be parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    Empty,
+    InvalidDigit(char),
+}
+
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("empty hex string"),
+            Self::InvalidDigit(c) => write!(f, "invalid hex digit {c:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ParseHexError {}
+
+/// Parses optionally `0x`-prefixed big-endian hex (with `_` separators
+/// allowed) into little-endian words.
+pub fn decode_words(s: &str) -> Result<Vec<Word>, ParseHexError> {
+    let s = s.strip_prefix("0x").unwrap_or(s);
+    let digits: Vec<char> = s.chars().filter(|&c| c != '_').collect();
+    if digits.is_empty() {
+        return Err(ParseHexError::Empty);
+    }
+    if let Some(&c) = digits.iter().find(|c| !c.is_ascii_hexdigit()) {
+        return Err(ParseHexError::InvalidDigit(c));
+    }
+    let chunks = digits.rchunks(Limb::BYTES * 2);
+    Ok(chunks
+        .map(|chunk| {
+            let chunk: String = chunk.iter().collect();
+            Word::from_str_radix(&chunk, 16).expect("validated hex digits")
+        })
+        .collect())
+}
+
+/// Packs little-endian words into a `Uint<L>`, or `None` if a nonzero word
+/// does not fit.
+pub fn from_words<const L: usize>(words: &[Word]) -> Option<Uint<L>> {
+    if words.iter().skip(L).any(|&w| w != 0) {
+        return None;
+    }
+    let mut out = [0; L];
+    for (o, w) in out.iter_mut().zip(words) {
+        *o = *w;
+    }
+    Some(Uint::from_words(out))
+}
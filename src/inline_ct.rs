@@ -0,0 +1,292 @@
+//! Dependency-free copies of crypto-bigint 0.6's `Uint::lt`/`sbb` path and
+//! subtle 2.6's `Choice`, with the optimization barrier made selectable.
+//!
+//! Comparing [`bad_random_mod`] across [`Barrier`]s separates a bug in
+//! subtle's barrier from one in crypto-bigint's borrow chain.
+
+use core::fmt;
+use core::marker::PhantomData;
+use core::str::FromStr;
+
+use crypto_bigint::{Limb, NonZero, RandomBits, Uint, Word};
+use rand_core::RngCore;
+
+use crate::SamplingError;
+use crate::oracle;
+
+/// What [`InlineChoice::from`] passes its byte through.
+pub trait Barrier {
+    const NAME: &'static str;
+
+    fn barrier(input: u8) -> u8;
+}
+
+/// subtle's default: an `#[inline(never)]` volatile read.
+#[derive(Clone, Copy, Debug)]
+pub enum Volatile {}
+
+impl Barrier for Volatile {
+    const NAME: &'static str = "volatile";
+
+    #[inline(never)]
+    fn barrier(input: u8) -> u8 {
+        unsafe { core::ptr::read_volatile(&input) }
+    }
+}
+
+/// subtle's `core_hint_black_box` feature.
+#[derive(Clone, Copy, Debug)]
+pub enum HintBlackBox {}
+
+impl Barrier for HintBlackBox {
+    const NAME: &'static str = "black-box";
+
+    #[inline]
+    fn barrier(input: u8) -> u8 {
+        core::hint::black_box(input)
+    }
+}
+
+/// An empty `asm!` block the value must pass through in a register.
+#[derive(Clone, Copy, Debug)]
+pub enum InlineAsm {}
+
+impl Barrier for InlineAsm {
+    const NAME: &'static str = "asm";
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[inline]
+    fn barrier(input: u8) -> u8 {
+        let mut value = input as u64;
+        unsafe {
+            core::arch::asm!(
+                "/* {0} */",
+                inout(reg) value,
+                options(pure, nomem, nostack, preserves_flags),
+            );
+        }
+        value as u8
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[inline]
+    fn barrier(input: u8) -> u8 {
+        Volatile::barrier(input)
+    }
+}
+
+/// No barrier at all.
+#[derive(Clone, Copy, Debug)]
+pub enum NoBarrier {}
+
+impl Barrier for NoBarrier {
+    const NAME: &'static str = "none";
+
+    #[inline(always)]
+    fn barrier(input: u8) -> u8 {
+        input
+    }
+}
+
+/// subtle's `Choice`, with barrier `B`.
+pub struct InlineChoice<B>(u8, PhantomData<B>);
+
+impl<B> Clone for InlineChoice<B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<B> Copy for InlineChoice<B> {}
+
+impl<B> fmt::Debug for InlineChoice<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InlineChoice").field(&self.0).finish()
+    }
+}
+
+impl<B> InlineChoice<B> {
+    pub fn unwrap_u8(&self) -> u8 {
+        self.0
+    }
+}
+
+impl<B: Barrier> From<u8> for InlineChoice<B> {
+    #[inline]
+    fn from(input: u8) -> Self {
+        debug_assert!((input == 0u8) | (input == 1u8));
+        Self(B::barrier(input), PhantomData)
+    }
+}
+
+impl<B> From<InlineChoice<B>> for bool {
+    #[inline]
+    fn from(source: InlineChoice<B>) -> bool {
+        debug_assert!((source.0 == 0u8) | (source.0 == 1u8));
+        source.0 != 0
+    }
+}
+
+/// crypto-bigint's `Limb::sbb`.
+#[inline(always)]
+const fn limb_sbb(lhs: Word, rhs: Word, borrow: Word) -> (Word, Word) {
+    let a = lhs as u128;
+    let b = rhs as u128;
+    let borrow = (borrow >> (Limb::BITS - 1)) as u128;
+    let ret = a.wrapping_sub(b + borrow);
+    (ret as Word, (ret >> Limb::BITS) as Word)
+}
+
+/// crypto-bigint's `Uint::sbb`, on words.
+#[inline(always)]
+const fn sbb<const L: usize>(
+    lhs: &[Word; L],
+    rhs: &[Word; L],
+    mut borrow: Word,
+) -> ([Word; L], Word) {
+    let mut limbs = [0; L];
+    let mut i = 0;
+    while i < L {
+        let (w, b) = limb_sbb(lhs[i], rhs[i], borrow);
+        limbs[i] = w;
+        borrow = b;
+        i += 1;
+    }
+    (limbs, borrow)
+}
+
+/// crypto-bigint's `ConstantTimeLess for Uint`: the borrow's word mask from
+/// `Uint::lt`, low bit into a `Choice`.
+#[inline]
+pub fn ct_lt<B: Barrier, const L: usize>(x: &Uint<L>, n: &Uint<L>) -> InlineChoice<B> {
+    let (_res, borrow) = sbb(x.as_words(), n.as_words(), 0);
+    InlineChoice::from((borrow as u8) & 1)
+}
+
+/// The original `bad_random_mod`, comparing with [`ct_lt`] under `B`.
+pub fn bad_random_mod<B, const L: usize, R>(rng: &mut R, n: &NonZero<Uint<L>>) -> Uint<L>
+where
+    B: Barrier,
+    R: RngCore,
+{
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if ct_lt::<B, L>(&x, n).into() {
+            return x;
+        }
+    }
+}
+
+/// [`bad_random_mod`] with an attempt budget, checking every decision
+/// against the oracle.
+pub fn try_bad_random_mod<B, const L: usize, R>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+    max_attempts: u64,
+) -> Result<Uint<L>, SamplingError<L>>
+where
+    B: Barrier,
+    R: RngCore,
+{
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    let mut last_candidate = Uint::ZERO;
+    for attempt in 0..max_attempts {
+        let x = Uint::random_bits(rng, n_bits);
+        let lt = bool::from(ct_lt::<B, L>(&x, n));
+        if let Some(divergence) = oracle::check_bool(attempt, &x, n, lt) {
+            return Err(SamplingError::Diverged(divergence));
+        }
+        if lt {
+            return Ok(x);
+        }
+        last_candidate = x;
+    }
+    Err(SamplingError::Exhausted {
+        attempts: max_attempts,
+        last_candidate,
+        modulus: *n,
+    })
+}
+
+/// Which comparison the sampler uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Variant {
+    /// subtle's and crypto-bigint's own code.
+    #[default]
+    Crates,
+    Volatile,
+    HintBlackBox,
+    InlineAsm,
+    NoBarrier,
+}
+
+impl Variant {
+    pub const ALL: [Self; 5] = [
+        Self::Crates,
+        Self::Volatile,
+        Self::HintBlackBox,
+        Self::InlineAsm,
+        Self::NoBarrier,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Crates => "crates",
+            Self::Volatile => Volatile::NAME,
+            Self::HintBlackBox => HintBlackBox::NAME,
+            Self::InlineAsm => InlineAsm::NAME,
+            Self::NoBarrier => NoBarrier::NAME,
+        }
+    }
+
+    /// The unbounded sampler for this variant.
+    pub fn random_mod<const L: usize, R: RngCore>(
+        self,
+        rng: &mut R,
+        n: &NonZero<Uint<L>>,
+    ) -> Uint<L> {
+        match self {
+            Self::Crates => crate::random_mod(rng, n),
+            Self::Volatile => bad_random_mod::<Volatile, L, R>(rng, n),
+            Self::HintBlackBox => bad_random_mod::<HintBlackBox, L, R>(rng, n),
+            Self::InlineAsm => bad_random_mod::<InlineAsm, L, R>(rng, n),
+            Self::NoBarrier => bad_random_mod::<NoBarrier, L, R>(rng, n),
+        }
+    }
+
+    /// The bounded, oracle-checked sampler for this variant.
+    pub fn try_random_mod<const L: usize, R: RngCore>(
+        self,
+        rng: &mut R,
+        n: &NonZero<Uint<L>>,
+        max_attempts: u64,
+    ) -> Result<Uint<L>, SamplingError<L>> {
+        match self {
+            Self::Crates => crate::sample_observed(rng, n, max_attempts, oracle::Oracle),
+            Self::Volatile => try_bad_random_mod::<Volatile, L, R>(rng, n, max_attempts),
+            Self::HintBlackBox => try_bad_random_mod::<HintBlackBox, L, R>(rng, n, max_attempts),
+            Self::InlineAsm => try_bad_random_mod::<InlineAsm, L, R>(rng, n, max_attempts),
+            Self::NoBarrier => try_bad_random_mod::<NoBarrier, L, R>(rng, n, max_attempts),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|variant| variant.name() == s)
+            .ok_or_else(|| format!("unknown barrier {s:?}"))
+    }
+}
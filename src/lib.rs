@@ -0,0 +1,142 @@
+//! Generic-width reproduction of rust-lang/rust#149522.
+
+pub mod asm_check;
+pub mod barriers;
+pub mod bisect;
+pub mod boundary;
+pub mod build_info;
+pub mod choice_audit;
+pub mod cli;
+pub mod cross;
+pub mod emit;
+pub mod feature_matrix;
+pub mod hex;
+pub mod inline_ct;
+pub mod json;
+pub mod matrix;
+pub mod minimize;
+pub mod opt_bisect;
+pub mod oracle;
+pub mod process;
+pub mod record;
+pub mod replay_ir;
+pub mod report;
+pub mod reproducer;
+pub mod rng;
+pub mod run;
+pub mod shapes;
+pub mod sweep;
+pub mod toolchain;
+pub mod verify;
+pub mod watchdog;
+
+mod error;
+mod observe;
+
+pub use error::SamplingError;
+pub use observe::Observer;
+
+use crypto_bigint::{NonZero, RandomBits, Uint};
+use rand_core::RngCore;
+use subtle::ConstantTimeLess;
+
+/// Limb counts the reproduction is exercised at.
+pub const LIMB_COUNTS: [usize; 6] = [1, 2, 4, 5, 8, 16];
+
+/// Evaluates `$body` with the const `$L` bound to a runtime limb count from
+/// [`LIMB_COUNTS`], or `$fallback` for any other count.
+macro_rules! with_limbs {
+    ($limbs:expr, |$L:ident| $body:expr, _ => $fallback:expr) => {
+        match $limbs {
+            1 => {
+                const $L: usize = 1;
+                $body
+            }
+            2 => {
+                const $L: usize = 2;
+                $body
+            }
+            4 => {
+                const $L: usize = 4;
+                $body
+            }
+            5 => {
+                const $L: usize = 5;
+                $body
+            }
+            8 => {
+                const $L: usize = 8;
+                $body
+            }
+            16 => {
+                const $L: usize = 16;
+                $body
+            }
+            _ => $fallback,
+        }
+    };
+}
+pub(crate) use with_limbs;
+
+/// Rejection-samples a value in `[0, n)` by drawing `n.bits_vartime()` random
+/// bits until `ct_lt` accepts the candidate.
+///
+/// When `ct_lt` is miscompiled this never returns.
+pub fn random_mod<const L: usize, R>(rng: &mut R, n: &NonZero<Uint<L>>) -> Uint<L>
+where
+    R: RngCore,
+{
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if x.ct_lt(n).into() {
+            return x;
+        }
+    }
+}
+
+/// Like [`random_mod`], but gives up after `max_attempts` rejected candidates.
+///
+/// A miscompiled `ct_lt` then surfaces as [`SamplingError::Exhausted`] rather
+/// than a hang.
+pub fn try_random_mod<const L: usize, R>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+    max_attempts: u64,
+) -> Result<Uint<L>, SamplingError<L>>
+where
+    R: RngCore,
+{
+    sample_observed(rng, n, max_attempts, ())
+}
+
+/// Like [`try_random_mod`], reporting every candidate to `observer`.
+pub fn sample_observed<const L: usize, R, O>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+    max_attempts: u64,
+    mut observer: O,
+) -> Result<Uint<L>, SamplingError<L>>
+where
+    R: RngCore,
+    O: Observer<L>,
+{
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    let mut last_candidate = Uint::ZERO;
+    for attempt in 0..max_attempts {
+        let x = Uint::random_bits(rng, n_bits);
+        let lt = x.ct_lt(n);
+        observer.observe(attempt, &x, n, lt)?;
+        if lt.into() {
+            return Ok(x);
+        }
+        last_candidate = x;
+    }
+    Err(SamplingError::Exhausted {
+        attempts: max_attempts,
+        last_candidate,
+        modulus: *n,
+    })
+}
@@ -1,27 +1,41 @@
-use crypto_bigint::{NonZero, RandomBits, Uint};
-use rand_chacha::ChaCha8Rng;
-use rand_core::{RngCore, SeedableRng};
-use subtle::ConstantTimeLess;
-
-fn bad_random_mod<R>(rng: &mut R, n: &Uint<5>) -> Uint<5>
-where
-    R: RngCore,
-{
-    let n_bits = n.bits_vartime();
-    loop {
-        let x = Uint::random_bits(rng, n_bits);
-        if x.ct_lt(n).into() {
-            return x;
-        }
-    }
-}
+use subtle_repro::build_info::build_info;
+use subtle_repro::cli::{self, Command};
+use subtle_repro::run::{self, EXIT_USAGE};
+use subtle_repro::{
+    asm_check, barriers, bisect, boundary, cross, emit, feature_matrix, matrix, minimize,
+    opt_bisect, replay_ir, reproducer, shapes, sweep, verify,
+};
 
 fn main() {
-    let mut rng = ChaCha8Rng::seed_from_u64(1);
-    let special = rng.next_u64();
-    let n = NonZero::new(Uint::<5>::ZERO.wrapping_sub(&Uint::from(special))).unwrap();
-
-    let a = bad_random_mod(&mut rng, &n);
-
-    println!("Hello, {a:?}");
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("error: {err}\n\n{}", cli::USAGE);
+            std::process::exit(EXIT_USAGE);
+        }
+    };
+    eprintln!("{}", build_info().banner());
+    let code = match command {
+        Command::Run(opts) => run::run(&opts),
+        Command::Sweep(opts) => sweep::sweep(&opts),
+        Command::VerifyCt(opts) => verify::verify(&opts),
+        Command::Boundary(opts) => boundary::boundary(&opts),
+        Command::Minimize(opts) => minimize::minimize(&opts),
+        Command::Reproducer(opts) => reproducer::reproducer(&opts),
+        Command::Barriers(opts) => barriers::barriers(&opts),
+        Command::Matrix(opts) => matrix::matrix(&opts),
+        Command::Bisect(opts) => bisect::bisect(&opts),
+        Command::Emit(opts) => emit::emit(&opts),
+        Command::CheckAsm(opts) => asm_check::check_asm(&opts),
+        Command::Cross(opts) => cross::cross(&opts),
+        Command::OptBisect(opts) => opt_bisect::opt_bisect(&opts),
+        Command::ReplayIr(opts) => replay_ir::replay_ir(&opts),
+        Command::FeatureMatrix(opts) => feature_matrix::feature_matrix(&opts),
+        Command::Shapes(opts) => shapes::shapes(&opts),
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            0
+        }
+    };
+    std::process::exit(code);
 }
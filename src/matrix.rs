@@ -0,0 +1,452 @@
+//! The `matrix` command: builds the reproduction under a grid of Cargo
+//! profiles and installed toolchains, and runs each binary under a timeout.
+//!
+//! Profiles are applied through `CARGO_PROFILE_RELEASE_*` and `RUSTFLAGS`,
+//! so `Cargo.toml` is left alone, and every configuration gets its own
+//! target directory so builds never invalidate each other.
+
+use std::env::consts::EXE_SUFFIX;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::build_info::build_info;
+use crate::process::{self, Exit};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_EXHAUSTED, EXIT_USAGE};
+use crate::watchdog::EXIT_STALLED;
+use crate::{json, toolchain};
+
+/// One Cargo profile configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// `0`, `1`, `2`, `3`, `s` or `z`.
+    pub opt_level: String,
+    /// `off`, `on`, `thin` or `fat`.
+    pub lto: String,
+    pub codegen_units: u32,
+    /// `unwind` or `abort`.
+    pub panic: String,
+    /// Passed as `-C target-cpu`; `None` leaves the target's default.
+    pub target_cpu: Option<String>,
+    /// Cargo features of this crate to enable.
+    pub features: Vec<String>,
+}
+
+impl Profile {
+    /// Short description, e.g. `O3 lto=off cgu=16 unwind`.
+    pub fn label(&self) -> String {
+        let mut label = format!(
+            "O{} lto={} cgu={} {}",
+            self.opt_level, self.lto, self.codegen_units, self.panic
+        );
+        if let Some(cpu) = &self.target_cpu {
+            label.push_str(&format!(" cpu={cpu}"));
+        }
+        if !self.features.is_empty() {
+            label.push_str(&format!(" features={}", self.features.join("+")));
+        }
+        label
+    }
+
+    /// [`label`](Self::label) made safe for a directory name.
+    pub fn dir_name(&self) -> String {
+        self.label()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect()
+    }
+
+    /// Sets the environment and arguments that make `cargo build --release`
+    /// use this profile. Arguments are appended, so this must come before
+    /// any `--`.
+    pub fn apply(&self, command: &mut std::process::Command) {
+        let lto = match self.lto.as_str() {
+            "on" => "true",
+            "off" => "false",
+            other => other,
+        };
+        command
+            .env("CARGO_PROFILE_RELEASE_OPT_LEVEL", &self.opt_level)
+            .env("CARGO_PROFILE_RELEASE_LTO", lto)
+            .env(
+                "CARGO_PROFILE_RELEASE_CODEGEN_UNITS",
+                self.codegen_units.to_string(),
+            )
+            .env("CARGO_PROFILE_RELEASE_PANIC", &self.panic);
+        if !self.features.is_empty() {
+            command.args(["--features", &self.features.join(",")]);
+        }
+        if let Some(cpu) = &self.target_cpu {
+            let mut flags = std::env::var_os("RUSTFLAGS").unwrap_or_default();
+            if !flags.is_empty() {
+                flags.push(" ");
+            }
+            flags.push(format!("-C target-cpu={cpu}"));
+            // An encoded value would take precedence over RUSTFLAGS.
+            command
+                .env_remove("CARGO_ENCODED_RUSTFLAGS")
+                .env("RUSTFLAGS", flags);
+        }
+    }
+}
+
+/// Options for the `matrix` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MatrixOptions {
+    /// Toolchains to build with; empty means every installed toolchain.
+    pub toolchains: Vec<String>,
+    pub opt_levels: Vec<String>,
+    pub lto: Vec<String>,
+    pub codegen_units: Vec<u32>,
+    pub panic: Vec<String>,
+    /// `None` entries build without `-C target-cpu`.
+    pub target_cpus: Vec<Option<String>>,
+    /// Sets of Cargo features; an empty set builds with the defaults.
+    pub features: Vec<Vec<String>>,
+    /// The crate to build.
+    pub source: PathBuf,
+    /// Parent of the per-configuration target directories.
+    pub target_dir: Option<PathBuf>,
+    pub offline: bool,
+    pub build_timeout: Duration,
+    /// Watchdog for each run; the process is killed shortly after.
+    pub timeout: Duration,
+    /// Extra arguments for each binary's `run`.
+    pub run_args: Vec<String>,
+    pub format: Format,
+}
+
+impl Default for MatrixOptions {
+    fn default() -> Self {
+        Self {
+            toolchains: Vec::new(),
+            opt_levels: ["0", "1", "2", "3", "s", "z"].map(String::from).into(),
+            lto: vec!["off".into()],
+            codegen_units: vec![16],
+            panic: vec!["unwind".into()],
+            target_cpus: vec![None],
+            features: vec![Vec::new()],
+            source: PathBuf::from(env!("CARGO_MANIFEST_DIR")),
+            target_dir: None,
+            offline: false,
+            build_timeout: Duration::from_secs(900),
+            timeout: Duration::from_secs(20),
+            run_args: Vec::new(),
+            format: Format::Text,
+        }
+    }
+}
+
+impl MatrixOptions {
+    /// Every combination of the profile axes.
+    pub fn profiles(&self) -> Vec<Profile> {
+        let mut profiles = Vec::new();
+        for opt_level in &self.opt_levels {
+            for lto in &self.lto {
+                for &codegen_units in &self.codegen_units {
+                    for panic in &self.panic {
+                        for target_cpu in &self.target_cpus {
+                            for features in &self.features {
+                                profiles.push(Profile {
+                                    opt_level: opt_level.clone(),
+                                    lto: lto.clone(),
+                                    codegen_units,
+                                    panic: panic.clone(),
+                                    target_cpu: target_cpu.clone(),
+                                    features: features.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        profiles
+    }
+
+    pub fn target_root(&self) -> PathBuf {
+        self.target_dir
+            .clone()
+            .unwrap_or_else(|| self.source.join("target").join("matrix"))
+    }
+
+    /// The target directory of one configuration.
+    pub fn target_dir(&self, toolchain: &str, profile: &Profile) -> PathBuf {
+        self.target_root().join(toolchain).join(profile.dir_name())
+    }
+}
+
+/// What happened to one configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    Hung,
+    Diverged,
+    Exhausted,
+    /// The binary exited some other way.
+    Failed(String),
+    BuildFailed,
+}
+
+impl Outcome {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Pass => "pass",
+            Self::Hung => "hung",
+            Self::Diverged => "diverged",
+            Self::Exhausted => "exhausted",
+            Self::Failed(why) => why,
+            Self::BuildFailed => "build-failed",
+        }
+    }
+
+    /// Classifies a finished `run`.
+    pub fn of_run(exit: &Exit) -> Self {
+        match exit {
+            Exit::Code(0) => Self::Pass,
+            Exit::Code(EXIT_STALLED) | Exit::TimedOut => Self::Hung,
+            Exit::Code(EXIT_DIVERGED) => Self::Diverged,
+            Exit::Code(EXIT_EXHAUSTED) => Self::Exhausted,
+            Exit::Code(code) => Self::Failed(format!("exit {code}")),
+            Exit::Signaled => Self::Failed("signaled".into()),
+        }
+    }
+}
+
+/// Builds the `subtle-repro` binary of `source` into `target_dir`, for the
+/// host unless `target` names another, returning its path, or the tail of
+/// the build log on failure.
+pub fn build(
+    toolchain: Option<&str>,
+    target: Option<&str>,
+    profile: &Profile,
+    source: &Path,
+    target_dir: &Path,
+    offline: bool,
+    timeout: Duration,
+) -> Result<PathBuf, String> {
+    let command = build_command(toolchain, target, profile, source, target_dir, offline);
+    run_build(command, target, target_dir, timeout).map(|(binary, _)| binary)
+}
+
+/// The `cargo build` that [`build`] runs, for callers that need to add to
+/// its environment.
+pub fn build_command(
+    toolchain: Option<&str>,
+    target: Option<&str>,
+    profile: &Profile,
+    source: &Path,
+    target_dir: &Path,
+    offline: bool,
+) -> std::process::Command {
+    let mut command = toolchain::cargo(toolchain);
+    command
+        .args([
+            "build",
+            "--release",
+            "--bin",
+            "subtle-repro",
+            "--manifest-path",
+        ])
+        .arg(source.join("Cargo.toml"))
+        .env("CARGO_TARGET_DIR", target_dir);
+    if offline {
+        command.arg("--offline");
+    }
+    if let Some(target) = target {
+        command.args(["--target", target]);
+    }
+    profile.apply(&mut command);
+    command
+}
+
+/// Runs a [`build_command`], returning the binary's path and the build's
+/// stderr.
+pub fn run_build(
+    mut command: std::process::Command,
+    target: Option<&str>,
+    target_dir: &Path,
+    timeout: Duration,
+) -> Result<(PathBuf, String), String> {
+    let out = process::run(&mut command, timeout).map_err(|err| err.to_string())?;
+    if !out.success() {
+        return Err(tail(&out.stderr, 20));
+    }
+    let (dir, suffix) = match target {
+        Some(target) if target.contains("windows") => (target_dir.join(target), ".exe"),
+        Some(target) => (target_dir.join(target), ""),
+        None => (target_dir.to_owned(), EXE_SUFFIX),
+    };
+    let binary = dir.join("release").join(format!("subtle-repro{suffix}"));
+    Ok((binary, out.stderr))
+}
+
+/// Runs a built binary's `run` command under `timeout`.
+pub fn run_binary(binary: &Path, run_args: &[String], timeout: Duration) -> Outcome {
+    run_with(std::process::Command::new(binary), run_args, timeout)
+}
+
+/// Like [`run_binary`], for a binary started by `command`, such as an
+/// emulator given the binary as its argument.
+pub fn run_with(
+    mut command: std::process::Command,
+    run_args: &[String],
+    timeout: Duration,
+) -> Outcome {
+    command
+        .arg("run")
+        .args(["--watchdog-secs", &timeout.as_secs().max(1).to_string()])
+        .args(run_args);
+    // The watchdog should fire first; this is a backstop.
+    match process::run(&mut command, timeout * 2 + Duration::from_secs(5)) {
+        Ok(out) => Outcome::of_run(&out.exit),
+        Err(err) => Outcome::Failed(err.to_string()),
+    }
+}
+
+/// The last `lines` lines of `text`.
+pub fn tail(text: &str, lines: usize) -> String {
+    let all: Vec<&str> = text.lines().collect();
+    all[all.len().saturating_sub(lines)..].join("\n")
+}
+
+/// One cell of the grid.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub toolchain: String,
+    pub profile: Profile,
+    pub outcome: Outcome,
+}
+
+/// Toolchains named in `opts`, or every installed one. An empty list means
+/// rustup is unavailable and cargo from the environment is used.
+pub fn toolchains(opts: &[String]) -> Vec<Option<String>> {
+    if !opts.is_empty() {
+        return opts.iter().cloned().map(Some).collect();
+    }
+    match toolchain::installed() {
+        Ok(names) if !names.is_empty() => names.into_iter().map(Some).collect(),
+        Ok(_) | Err(_) => vec![None],
+    }
+}
+
+/// Builds and runs one configuration.
+pub fn cell(opts: &MatrixOptions, toolchain: Option<&str>, profile: &Profile) -> Cell {
+    let name = toolchain.unwrap_or("default");
+    let target_dir = opts.target_dir(name, profile);
+    eprintln!("building {name} {}", profile.label());
+    let outcome = match build(
+        toolchain,
+        None,
+        profile,
+        &opts.source,
+        &target_dir,
+        opts.offline,
+        opts.build_timeout,
+    ) {
+        Ok(binary) => run_binary(&binary, &opts.run_args, opts.timeout),
+        Err(log) => {
+            eprintln!("{log}");
+            Outcome::BuildFailed
+        }
+    };
+    eprintln!("  {}", outcome.name());
+    Cell {
+        toolchain: name.to_owned(),
+        profile: profile.clone(),
+        outcome,
+    }
+}
+
+/// Builds and runs every configuration and prints the grid, returning the
+/// process exit code.
+pub fn matrix(opts: &MatrixOptions) -> i32 {
+    let profiles = opts.profiles();
+    if profiles.is_empty() {
+        eprintln!("matrix has no profiles");
+        return EXIT_USAGE;
+    }
+    let toolchains = toolchains(&opts.toolchains);
+    let mut cells = Vec::new();
+    for toolchain in &toolchains {
+        for profile in &profiles {
+            cells.push(cell(opts, toolchain.as_deref(), profile));
+        }
+    }
+
+    match opts.format {
+        Format::Text => print_grid(&toolchains, &profiles, &cells),
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .raw(
+                    "cells",
+                    &json::array(cells.iter().map(|c| cell_object(c).finish())),
+                )
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => cells
+            .iter()
+            .for_each(|c| println!("{}", cell_object(c).finish())),
+    }
+
+    if cells.iter().all(|c| c.outcome == Outcome::Pass) {
+        0
+    } else {
+        EXIT_DIVERGED
+    }
+}
+
+/// Profiles down the side, toolchains across the top.
+pub fn print_grid(toolchains: &[Option<String>], profiles: &[Profile], cells: &[Cell]) {
+    let names: Vec<&str> = toolchains
+        .iter()
+        .map(|t| t.as_deref().unwrap_or("default"))
+        .collect();
+    let width = profiles.iter().map(|p| p.label().len()).max().unwrap_or(0);
+    let mut header = format!("{:<width$}", "profile");
+    for name in &names {
+        header.push_str(&format!("  {name:<12}"));
+    }
+    println!("{header}");
+    for profile in profiles {
+        let mut row = format!("{:<width$}", profile.label());
+        for name in &names {
+            let outcome = cells
+                .iter()
+                .find(|c| c.toolchain == *name && c.profile == *profile)
+                .map_or("", |c| c.outcome.name());
+            row.push_str(&format!("  {outcome:<w$}", w = name.len().max(12)));
+        }
+        println!("{}", row.trim_end());
+    }
+}
+
+/// Starts a JSON object describing one cell.
+pub fn cell_object(cell: &Cell) -> json::Object {
+    let profile = &cell.profile;
+    let obj = json::Object::new()
+        .str("toolchain", &cell.toolchain)
+        .str("opt_level", &profile.opt_level)
+        .str("lto", &profile.lto)
+        .u64("codegen_units", profile.codegen_units.into())
+        .str("panic", &profile.panic);
+    let obj = match &profile.target_cpu {
+        Some(cpu) => obj.str("target_cpu", cpu),
+        None => obj,
+    };
+    let obj = if profile.features.is_empty() {
+        obj
+    } else {
+        obj.raw(
+            "features",
+            &json::array(profile.features.iter().map(|f| {
+                let mut s = String::new();
+                json::write_str(&mut s, f);
+                s
+            })),
+        )
+    };
+    obj.str("outcome", cell.outcome.name())
+}
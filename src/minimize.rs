@@ -0,0 +1,524 @@
+//! The `minimize` command: shrinks a failing run to the fewest and smallest
+//! candidates, and the smallest modulus, on which the sampler's rejection
+//! loop still accepts a different candidate than `cmp_vartime` would.
+//!
+//! Three stages, each keeping the failure:
+//!
+//! 1. delta debugging over the recorded rng calls,
+//! 2. delta debugging over the candidates the loop draws, the narrowest width
+//!    in [`LIMB_COUNTS`] whose low or high limbs still fail, then delta
+//!    debugging over which limbs stay nonzero,
+//! 3. clearing single bits until none can be cleared.
+//!
+//! Stages 2 and 3 run the loop over a fixed list of candidates rather than a
+//! generator, as the snippet does. If the miscompile does not show there,
+//! only the stream is minimized and no snippet is written.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crypto_bigint::{Limb, RandomBits, Uint, Word};
+use subtle::ConstantTimeLess;
+
+use crate::oracle;
+use crate::record::{Call, Recording, ReplayRng};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE, ModulusSpec};
+use crate::{LIMB_COUNTS, build_info::build_info, hex, json, with_limbs};
+
+/// Process exit code when the input does not fail in this build.
+pub const EXIT_NOT_FAILING: i32 = 6;
+/// Process exit code when the failing pair does not fail outside the loop.
+pub const EXIT_NOT_ISOLATED: i32 = 7;
+
+/// Options for the `minimize` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MinimizeOptions {
+    pub recording: PathBuf,
+    pub modulus: ModulusSpec,
+    pub limbs: usize,
+    /// Candidates to replay from the recording before giving up.
+    pub max_attempts: u64,
+    /// Where to write the minimized recording.
+    pub out: Option<PathBuf>,
+    /// Where to write the reproducing snippet; stdout if unset.
+    pub snippet: Option<PathBuf>,
+    pub format: Format,
+}
+
+impl Default for MinimizeOptions {
+    fn default() -> Self {
+        Self {
+            recording: PathBuf::new(),
+            modulus: ModulusSpec::Special,
+            limbs: 5,
+            max_attempts: 1 << 20,
+            out: None,
+            snippet: None,
+            format: Format::Text,
+        }
+    }
+}
+
+/// Classic ddmin: a 1-minimal subsequence of `items` for which `test` holds,
+/// assuming it holds for `items`.
+pub fn ddmin<T: Clone>(items: &[T], mut test: impl FnMut(&[T]) -> bool) -> Vec<T> {
+    let mut current = items.to_vec();
+    let mut granularity = 2;
+    while current.len() >= 2 {
+        let chunk = current.len().div_ceil(granularity);
+        let subsets: Vec<&[T]> = current.chunks(chunk).collect();
+        let mut next = None;
+        if let Some(subset) = subsets.iter().find(|subset| test(subset)) {
+            next = Some((subset.to_vec(), 2));
+        } else if subsets.len() > 2 {
+            for i in 0..subsets.len() {
+                let complement: Vec<T> = subsets
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .flat_map(|(_, subset)| subset.iter().cloned())
+                    .collect();
+                if test(&complement) {
+                    next = Some((complement, (granularity - 1).max(2)));
+                    break;
+                }
+            }
+        }
+        match next {
+            Some((reduced, g)) => {
+                current = reduced;
+                granularity = g;
+            }
+            None if granularity >= current.len() => break,
+            None => granularity = (granularity * 2).min(current.len()),
+        }
+    }
+    current
+}
+
+/// The inputs of one run of the sampler's rejection loop: the candidates it
+/// draws, in order, and the modulus, all little-endian words of one width.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Case {
+    pub candidates: Vec<Vec<Word>>,
+    pub modulus: Vec<Word>,
+}
+
+impl Case {
+    fn from_uints<const L: usize>(candidates: &[Uint<L>], modulus: &Uint<L>) -> Self {
+        Self {
+            candidates: candidates.iter().map(|x| x.as_words().to_vec()).collect(),
+            modulus: modulus.as_words().to_vec(),
+        }
+    }
+
+    /// Width in limbs.
+    pub fn width(&self) -> usize {
+        self.modulus.len()
+    }
+
+    /// Limbs `lo..hi` of every value.
+    fn limbs(&self, lo: usize, hi: usize) -> Self {
+        Self {
+            candidates: self.candidates.iter().map(|x| x[lo..hi].to_vec()).collect(),
+            modulus: self.modulus[lo..hi].to_vec(),
+        }
+    }
+
+    /// Every word, the candidates' first and the modulus's last.
+    fn words(&self) -> Vec<Word> {
+        self.candidates
+            .iter()
+            .chain([&self.modulus])
+            .flatten()
+            .copied()
+            .collect()
+    }
+
+    /// The case of the same shape holding `words`, as laid out by
+    /// [`words`](Self::words).
+    fn with_words(&self, words: &[Word]) -> Self {
+        let mut values = words.chunks(self.width()).map(<[Word]>::to_vec);
+        let candidates = values.by_ref().take(self.candidates.len()).collect();
+        Self {
+            candidates,
+            modulus: values.next().unwrap_or_default(),
+        }
+    }
+}
+
+/// Replays `calls` through the sampler's draws up to the first candidate on
+/// which `ct_lt` disagrees with the oracle, and returns the candidates the
+/// loop should reject on the way, then that one, with the modulus.
+///
+/// Unlike the sampler this does not stop at the first accepted candidate;
+/// candidates below the modulus before the disagreement are left out.
+pub fn first_divergence<const L: usize>(
+    calls: &[Call],
+    modulus: &ModulusSpec,
+    max_attempts: u64,
+) -> Option<(Vec<Uint<L>>, Uint<L>)> {
+    let mut rng = ReplayRng::lenient(calls);
+    let n = *modulus.resolve::<L, _>(&mut rng).ok()?;
+    let n_bits = n.bits_vartime();
+    let mut rejected = Vec::new();
+    for attempt in 0..max_attempts {
+        if rng.is_stopped() {
+            break;
+        }
+        let x = Uint::<L>::random_bits(&mut rng, n_bits);
+        if rng.is_stopped() {
+            break;
+        }
+        if oracle::check(attempt, &x, &n, x.ct_lt(&n)).is_some() {
+            rejected.push(x);
+            return Some((rejected, n));
+        }
+        if x.cmp_vartime(&n).is_ge() {
+            rejected.push(x);
+        }
+    }
+    None
+}
+
+/// The sampler's rejection loop, drawing from `candidates` instead of a
+/// generator. The snippet holds a copy.
+#[inline(never)]
+fn first_accepted<const L: usize>(candidates: &[Uint<L>], n: &Uint<L>) -> Option<Uint<L>> {
+    let mut candidates = candidates.iter().copied();
+    loop {
+        let x = candidates.next()?;
+        if x.ct_lt(n).into() {
+            return Some(x);
+        }
+    }
+}
+
+/// Whether the rejection loop over `case` accepts a different candidate than
+/// a variable-time comparison would, at one of the [`LIMB_COUNTS`] widths.
+pub fn loop_diverges(case: &Case) -> bool {
+    with_limbs!(case.width(), |L| loop_diverges_at::<L>(case), _ => false)
+}
+
+fn loop_diverges_at<const L: usize>(case: &Case) -> bool {
+    let Some(n) = hex::from_words::<L>(&case.modulus) else {
+        return false;
+    };
+    let Some(candidates) = case
+        .candidates
+        .iter()
+        .map(|x| hex::from_words::<L>(x))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return false;
+    };
+    let expected = candidates
+        .iter()
+        .find(|x| x.cmp_vartime(&n).is_lt())
+        .copied();
+    let (candidates, n) = core::hint::black_box((candidates, n));
+    first_accepted(&candidates, &n) != expected
+}
+
+/// Shrinks a failing [`Case`], keeping `test` true, or returns `None` if
+/// `test` does not hold for it to begin with.
+pub fn shrink_case(mut case: Case, test: impl Fn(&Case) -> bool) -> Option<Case> {
+    if !test(&case) {
+        return None;
+    }
+
+    // Fewest candidates.
+    let modulus = case.modulus.clone();
+    case.candidates = ddmin(&case.candidates, |candidates| {
+        test(&Case {
+            candidates: candidates.to_vec(),
+            modulus: modulus.clone(),
+        })
+    });
+
+    // Narrowest width whose low or high limbs still fail.
+    let width = case.width();
+    for &narrow in LIMB_COUNTS.iter().filter(|&&w| w < width) {
+        let windows = [(0, narrow), (width - narrow, width)];
+        if let Some(narrowed) = windows
+            .into_iter()
+            .map(|(lo, hi)| case.limbs(lo, hi))
+            .find(&test)
+        {
+            case = narrowed;
+            break;
+        }
+    }
+
+    // Which limbs need to be nonzero.
+    let words = case.words();
+    let keep_only = |keep: &[usize]| {
+        let mut kept = vec![0; words.len()];
+        for &i in keep {
+            kept[i] = words[i];
+        }
+        case.with_words(&kept)
+    };
+    let nonzero: Vec<usize> = (0..words.len()).filter(|&i| words[i] != 0).collect();
+    let keep = ddmin(&nonzero, |keep| test(&keep_only(keep)));
+    case = keep_only(&keep);
+
+    // Clear single bits until none can be.
+    let mut words = case.words();
+    loop {
+        let mut changed = false;
+        for i in 0..words.len() {
+            for bit in 0..Limb::BITS {
+                let mask: Word = 1 << bit;
+                if words[i] & mask == 0 {
+                    continue;
+                }
+                words[i] &= !mask;
+                if test(&case.with_words(&words)) {
+                    changed = true;
+                } else {
+                    words[i] |= mask;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    Some(case.with_words(&words))
+}
+
+fn words_literal(words: &[Word]) -> String {
+    let words: Vec<String> = words
+        .iter()
+        .map(|w| format!("{w:#0width$x}", width = Limb::BYTES * 2 + 2))
+        .collect();
+    format!("[{}]", words.join(", "))
+}
+
+/// A Rust snippet that runs the body of the original `bad_random_mod` over
+/// the case's candidates and checks which one it accepts.
+pub fn snippet(case: &Case) -> String {
+    let limbs = case.width();
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "// Minimized by `subtle-repro minimize` on {}.",
+        build_info().banner()
+    );
+    let _ = writeln!(out, "use core::hint::black_box;");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "use crypto_bigint::Uint;");
+    let _ = writeln!(out, "use subtle::ConstantTimeLess;");
+    let _ = writeln!(out);
+    let _ = writeln!(
+        out,
+        "/// `bad_random_mod`'s loop, drawing from `candidates` instead of an rng."
+    );
+    let _ = writeln!(out, "#[inline(never)]");
+    let _ = writeln!(
+        out,
+        "fn bad_random_mod(candidates: &[Uint<{limbs}>], n: &Uint<{limbs}>) -> Option<Uint<{limbs}>> {{"
+    );
+    let _ = writeln!(out, "    let mut candidates = candidates.iter().copied();");
+    let _ = writeln!(out, "    loop {{");
+    let _ = writeln!(out, "        let x = candidates.next()?;");
+    let _ = writeln!(out, "        if x.ct_lt(n).into() {{");
+    let _ = writeln!(out, "            return Some(x);");
+    let _ = writeln!(out, "        }}");
+    let _ = writeln!(out, "    }}");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "fn main() {{");
+    let _ = writeln!(
+        out,
+        "    let n = black_box(Uint::<{limbs}>::from_words({}));",
+        words_literal(&case.modulus)
+    );
+    let _ = writeln!(out, "    let candidates = black_box([");
+    for x in &case.candidates {
+        let _ = writeln!(
+            out,
+            "        Uint::<{limbs}>::from_words({}),",
+            words_literal(x)
+        );
+    }
+    let _ = writeln!(out, "    ]);");
+    let _ = writeln!(
+        out,
+        "    let expected = candidates.iter().find(|x| x.cmp_vartime(&n).is_lt()).copied();"
+    );
+    let _ = writeln!(out, "    let accepted = bad_random_mod(&candidates, &n);");
+    let _ = writeln!(
+        out,
+        "    assert_eq!(accepted, expected, \"the loop accepted {{accepted:?}}\");"
+    );
+    let _ = writeln!(out, "}}");
+    out
+}
+
+/// Runs the minimizer and prints its result, returning the process exit code.
+pub fn minimize(opts: &MinimizeOptions) -> i32 {
+    let recording = match Recording::read(&opts.recording) {
+        Ok(recording) => recording,
+        Err(err) => {
+            eprintln!("cannot read {}: {err}", opts.recording.display());
+            return EXIT_USAGE;
+        }
+    };
+    let found = |calls: &[Call]| {
+        with_limbs!(opts.limbs, |L| {
+            first_divergence::<L>(calls, &opts.modulus, opts.max_attempts)
+                .map(|(candidates, n)| Case::from_uints(&candidates, &n))
+        }, _ => None)
+    };
+    let Some(_) = found(&recording.calls) else {
+        eprintln!(
+            "no ct_lt disagreement replaying {} in this build; nothing to minimize",
+            opts.recording.display()
+        );
+        return EXIT_NOT_FAILING;
+    };
+
+    let calls = ddmin(&recording.calls, |calls| found(calls).is_some());
+    let case = found(&calls).expect("ddmin keeps the failure");
+    let shrunk = shrink_case(case.clone(), loop_diverges);
+    let snippet = shrunk.as_ref().map(snippet);
+    let case = shrunk.unwrap_or(case);
+
+    if let Some(path) = &opts.out {
+        let minimized = Recording {
+            calls: calls.clone(),
+        };
+        if let Err(err) = minimized.write(path) {
+            eprintln!("cannot write {}: {err}", path.display());
+            return EXIT_USAGE;
+        }
+    }
+    if let (Some(path), Some(snippet)) = (&opts.snippet, &snippet) {
+        let written = std::fs::write(path, snippet);
+        if let Err(err) = written {
+            eprintln!("cannot write {}: {err}", path.display());
+            return EXIT_USAGE;
+        }
+    }
+
+    let words_hex = |words: &[Word]| {
+        with_limbs!(words.len(), |L| {
+            hex::from_words::<L>(words).map(|w| hex::encode(&w)).unwrap_or_default()
+        }, _ => String::new())
+    };
+    let candidates: Vec<String> = case.candidates.iter().map(|x| words_hex(x)).collect();
+    match opts.format {
+        Format::Text => {
+            eprintln!(
+                "stream: {} of {} calls; width: {} limbs; {} candidates",
+                calls.len(),
+                recording.calls.len(),
+                case.width(),
+                candidates.len()
+            );
+            for x in &candidates {
+                eprintln!("x = {x}");
+            }
+            eprintln!("n = {}", words_hex(&case.modulus));
+            match &snippet {
+                Some(snippet) if opts.snippet.is_none() => print!("{snippet}"),
+                Some(_) => {}
+                None => eprintln!(
+                    "the rejection loop over these candidates accepts the right one outside \
+                     the sampler; it does not reproduce in isolation, so no snippet was written"
+                ),
+            }
+        }
+        Format::Json | Format::Ndjson => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .u64("calls", recording.calls.len() as u64)
+                .u64("minimized_calls", calls.len() as u64)
+                .u64("limbs", case.width() as u64)
+                .raw(
+                    "candidates",
+                    &json::array(candidates.iter().map(|x| {
+                        let mut s = String::new();
+                        json::write_str(&mut s, x);
+                        s
+                    })),
+                )
+                .str("modulus", &words_hex(&case.modulus))
+                .bool("isolated", snippet.is_some());
+            let report = match &snippet {
+                Some(snippet) => report.str("snippet", snippet),
+                None => report.raw("snippet", "null"),
+            };
+            println!("{}", report.finish());
+        }
+    }
+    if snippet.is_some() {
+        EXIT_DIVERGED
+    } else {
+        EXIT_NOT_ISOLATED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ddmin_keeps_only_what_the_test_needs() {
+        let items: Vec<u32> = (0..32).collect();
+        let kept = ddmin(&items, |items| items.contains(&5) && items.contains(&20));
+        assert_eq!(kept, [5, 20]);
+        // 1-minimal: dropping any one item loses the failure.
+        let test = |items: &[u32]| items.iter().sum::<u32>() >= 31;
+        let kept = ddmin(&items, test);
+        assert!(test(&kept));
+        for i in 0..kept.len() {
+            let mut fewer = kept.clone();
+            fewer.remove(i);
+            assert!(!test(&fewer), "{kept:?}");
+        }
+    }
+
+    #[test]
+    fn shrink_case_narrows_and_clears() {
+        let case = Case {
+            candidates: vec![
+                vec![0x1234, 7, 0, 9],
+                vec![0xff0f, 1, 2, 3],
+                vec![0x0008, 0, 0, 1],
+            ],
+            modulus: vec![0x55, 0, 6, 0],
+        };
+        // Fails while some candidate has bit 3 set and the modulus is odd.
+        let test = |case: &Case| {
+            case.candidates.iter().any(|x| x[0] & 0b1000 != 0) && case.modulus[0] & 1 == 1
+        };
+        let shrunk = shrink_case(case, test).unwrap();
+        assert_eq!(shrunk.candidates, [vec![0b1000]]);
+        assert_eq!(shrunk.modulus, [1]);
+    }
+
+    #[test]
+    fn shrink_case_needs_a_failing_case() {
+        let case = Case {
+            candidates: vec![vec![1]],
+            modulus: vec![2],
+        };
+        assert_eq!(shrink_case(case, |_| false), None);
+    }
+
+    #[test]
+    fn healthy_loop_does_not_diverge() {
+        let case = Case {
+            candidates: vec![vec![u64::MAX, 1], vec![3, 1], vec![2, 1]],
+            modulus: vec![3, 1],
+        };
+        assert!(!loop_diverges(&case));
+        let snippet = snippet(&case);
+        assert_eq!(snippet.matches("Uint::<2>::from_words([").count(), 4);
+        assert!(snippet.contains("fn bad_random_mod("));
+    }
+}
@@ -0,0 +1,70 @@
+use crypto_bigint::Uint;
+use subtle::Choice;
+
+use crate::SamplingError;
+
+/// Hook called by [`sample_observed`](crate::sample_observed) with every
+/// candidate and the `ct_lt` decision made about it. Returning an error stops
+/// sampling with that error.
+pub trait Observer<const L: usize> {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>>;
+}
+
+impl<const L: usize> Observer<L> for () {
+    #[inline(always)]
+    fn observe(
+        &mut self,
+        _: u64,
+        _: &Uint<L>,
+        _: &Uint<L>,
+        _: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        Ok(())
+    }
+}
+
+impl<const L: usize, O: Observer<L> + ?Sized> Observer<L> for &mut O {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        (**self).observe(attempt, x, n, lt)
+    }
+}
+
+impl<const L: usize, O: Observer<L>> Observer<L> for Option<O> {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        match self {
+            Some(observer) => observer.observe(attempt, x, n, lt),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<const L: usize, A: Observer<L>, B: Observer<L>> Observer<L> for (A, B) {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        self.0.observe(attempt, x, n, lt)?;
+        self.1.observe(attempt, x, n, lt)
+    }
+}
@@ -0,0 +1,711 @@
+//! The `opt-bisect` command: finds the LLVM pass after which the sampling
+//! loop goes wrong, using LLVM's `-opt-bisect-limit`.
+//!
+//! Passes are numbered in the order they run on a module; with a limit of
+//! `N`, only the first `N` run. The limit is applied to the library alone,
+//! where the samplers are instantiated, through a `RUSTC_WORKSPACE_WRAPPER`
+//! script, and codegen units default to 1 so the numbering is the same from
+//! build to build. Each limit is judged by running the binary with
+//! `--oracle`, or, for a target the host cannot run, by `check-asm` on the
+//! library's assembly.
+
+use core::str::FromStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::asm_check::{self, FunctionReport};
+use crate::bisect::{self, Log, Verdict};
+use crate::build_info::build_info;
+use crate::matrix::{self, MatrixOptions, Outcome, Profile};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+use crate::{emit, json, process, toolchain};
+
+/// The environment variable the wrapper reads the limit from.
+const LIMIT_VAR: &str = "SUBTLE_REPRO_OPT_BISECT_LIMIT";
+
+/// Appends the limit to the library's rustc invocations and nothing else's.
+const WRAPPER: &str = r#"#!/bin/sh
+# Written by subtle-repro opt-bisect.
+rustc="$1"
+shift
+case " $* " in
+*" --crate-name subtle_repro "*" --crate-type lib "*)
+    exec "$rustc" "$@" -C "llvm-args=-opt-bisect-limit=$SUBTLE_REPRO_OPT_BISECT_LIMIT" ;;
+esac
+exec "$rustc" "$@"
+"#;
+
+/// A limit past any real pass count; LLVM parses the option as an `int`.
+const UNLIMITED: u64 = i32::MAX as u64;
+
+/// How each limit is judged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Run the binary with `--oracle`; bad if it hangs, diverges or runs
+    /// out of attempts.
+    Run,
+    /// Run `check-asm` on the library's assembly; bad if a function fails.
+    Asm,
+}
+
+impl Check {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Run => "run",
+            Self::Asm => "asm",
+        }
+    }
+}
+
+impl FromStr for Check {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Run, Self::Asm]
+            .into_iter()
+            .find(|check| check.name() == s)
+            .ok_or_else(|| format!("unknown check {s:?}"))
+    }
+}
+
+/// Options for the `opt-bisect` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OptBisectOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`; each
+    /// configuration is bisected separately.
+    pub build: MatrixOptions,
+    /// Builds for this target instead of the host.
+    pub target: Option<String>,
+    /// Defaults to [`Check::Run`] on the host and [`Check::Asm`] otherwise.
+    pub check: Option<Check>,
+    /// Symbol substrings for [`Check::Asm`].
+    pub functions: Vec<String>,
+    /// Defaults to `opt-bisect.log` in the matrix target directory.
+    pub log: Option<PathBuf>,
+}
+
+impl Default for OptBisectOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions {
+                opt_levels: vec!["3".into()],
+                codegen_units: vec![1],
+                ..MatrixOptions::default()
+            },
+            target: None,
+            check: None,
+            functions: vec!["random_mod".into()],
+            log: None,
+        }
+    }
+}
+
+impl OptBisectOptions {
+    fn check(&self) -> Check {
+        self.check.unwrap_or(match self.target {
+            Some(_) => Check::Asm,
+            None => Check::Run,
+        })
+    }
+}
+
+/// One line of LLVM's `-opt-bisect-limit` output.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pass {
+    pub index: u64,
+    pub name: String,
+    /// The function, or `[module]`, it ran on.
+    pub on: String,
+    pub ran: bool,
+}
+
+impl Pass {
+    /// Parses `BISECT: running pass (N) <pass> on <function>`, or the same
+    /// with `NOT running`.
+    pub fn parse(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("BISECT: ")?;
+        let (ran, rest) = match rest.strip_prefix("NOT ") {
+            Some(rest) => (false, rest),
+            None => (true, rest),
+        };
+        let rest = rest.strip_prefix("running pass (")?;
+        let (index, rest) = rest.split_once(") ")?;
+        let (name, on) = rest.split_once(" on ")?;
+        Some(Self {
+            index: index.parse().ok()?,
+            name: name.to_owned(),
+            on: emit::strip_hashes(on),
+            ran,
+        })
+    }
+}
+
+/// One tested limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    pub limit: u64,
+    pub verdict: Verdict,
+    /// The run's outcome, the failing functions, or why it was skipped.
+    pub detail: String,
+    /// The highest pass number LLVM reported.
+    pub passes: u64,
+    /// The last pass that ran: the one numbered `limit`, or the final pass
+    /// when the limit is past them all.
+    pub last: Option<Pass>,
+}
+
+/// Where one configuration's bisection ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Conclusion {
+    /// Running pass `first_bad` breaks the loop.
+    Found { first_bad: u64, pass: Option<Pass> },
+    /// Bad even with no passes at all.
+    AlwaysBad,
+    /// Good even with every pass.
+    NeverBad,
+    /// A limit that decides the search could not be judged: limit 0, the
+    /// unlimited build, or the lowest of the skipped limits left between the
+    /// last good and first bad ones.
+    Inconclusive { limit: u64 },
+}
+
+impl Conclusion {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Found { .. } => "found",
+            Self::AlwaysBad => "always-bad",
+            Self::NeverBad => "never-bad",
+            Self::Inconclusive { .. } => "inconclusive",
+        }
+    }
+}
+
+/// One configuration's bisection.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bisection {
+    pub toolchain: String,
+    pub profile: Profile,
+    pub probes: Vec<Probe>,
+    pub conclusion: Conclusion,
+}
+
+/// What stays fixed across one configuration's probes.
+struct Config<'a> {
+    opts: &'a OptBisectOptions,
+    toolchain: Option<&'a str>,
+    profile: &'a Profile,
+    target_dir: PathBuf,
+    wrapper: &'a Path,
+}
+
+impl Config<'_> {
+    /// Removes the library's build products, since cargo cannot see that the
+    /// limit changed.
+    fn clean(&self) -> Result<(), String> {
+        let build = &self.opts.build;
+        let mut command = toolchain::cargo(self.toolchain);
+        command
+            .args([
+                "clean",
+                "--release",
+                "-p",
+                "subtle-repro",
+                "--manifest-path",
+            ])
+            .arg(build.source.join("Cargo.toml"))
+            .env("CARGO_TARGET_DIR", &self.target_dir);
+        if build.offline {
+            command.arg("--offline");
+        }
+        if let Some(target) = &self.opts.target {
+            command.args(["--target", target]);
+        }
+        let out = process::run(&mut command, Duration::from_secs(60))
+            .map_err(|err| format!("cannot run cargo clean: {err}"))?;
+        if !out.success() {
+            return Err(matrix::tail(&out.stderr, 5));
+        }
+        Ok(())
+    }
+
+    fn limited(&self, mut command: std::process::Command, limit: u64) -> std::process::Command {
+        command
+            .env("RUSTC_WORKSPACE_WRAPPER", self.wrapper)
+            .env(LIMIT_VAR, limit.to_string());
+        command
+    }
+
+    /// Builds with `limit` and judges the result, returning the verdict, its
+    /// detail and the build's stderr.
+    fn judge(&self, limit: u64) -> Result<(Verdict, String, String), String> {
+        let opts = self.opts;
+        let build = &opts.build;
+        let target = opts.target.as_deref();
+        match opts.check() {
+            Check::Run => {
+                let command = matrix::build_command(
+                    self.toolchain,
+                    target,
+                    self.profile,
+                    &build.source,
+                    &self.target_dir,
+                    build.offline,
+                );
+                let (binary, stderr) = matrix::run_build(
+                    self.limited(command, limit),
+                    target,
+                    &self.target_dir,
+                    build.build_timeout,
+                )?;
+                let mut run_args = build.run_args.clone();
+                if !run_args.iter().any(|arg| arg == "--oracle") {
+                    run_args.insert(0, "--oracle".into());
+                }
+                let outcome = matrix::run_binary(&binary, &run_args, build.timeout);
+                let verdict = match outcome {
+                    Outcome::Pass => Verdict::Good,
+                    Outcome::Hung | Outcome::Diverged | Outcome::Exhausted => Verdict::Bad,
+                    Outcome::Failed(_) | Outcome::BuildFailed => Verdict::Skip,
+                };
+                Ok((verdict, outcome.name().to_owned(), stderr))
+            }
+            Check::Asm => {
+                let command = emit::emit_command(
+                    self.toolchain,
+                    target,
+                    self.profile,
+                    &build.source,
+                    &self.target_dir,
+                    build.offline,
+                );
+                let (_, asm, stderr) = emit::run_emit(
+                    self.limited(command, limit),
+                    target,
+                    &self.target_dir,
+                    build.build_timeout,
+                )?;
+                let (verdict, detail) = judge_asm(&asm_check::check(&asm, &opts.functions));
+                Ok((verdict, detail, stderr))
+            }
+        }
+    }
+
+    fn probe(&self, limit: u64, log: &mut Log) -> Probe {
+        let judged = self.clean().and_then(|()| self.judge(limit));
+        let (verdict, detail, stderr) = match judged {
+            Ok(judged) => judged,
+            Err(err) => {
+                eprintln!("{err}");
+                (Verdict::Skip, "build failed".to_owned(), String::new())
+            }
+        };
+        let mut passes = 0;
+        let mut last = None;
+        for pass in stderr.lines().filter_map(Pass::parse) {
+            passes = passes.max(pass.index);
+            if pass.ran {
+                last = Some(pass);
+            }
+        }
+        let limit_name = if limit == UNLIMITED {
+            "unlimited".to_owned()
+        } else {
+            limit.to_string()
+        };
+        log.line(&format!(
+            "limit {limit_name}: {} ({detail}; {passes} passes)",
+            verdict.name()
+        ));
+        Probe {
+            limit,
+            verdict,
+            detail,
+            passes,
+            last,
+        }
+    }
+}
+
+/// The verdict on `check-asm`'s reports and its detail. A function the
+/// check cannot see into says nothing either way, so a limit whose only
+/// non-passing functions are inconclusive is skipped.
+fn judge_asm(reports: &[FunctionReport]) -> (Verdict, String) {
+    let (failed, inconclusive) = asm_check::failed_and_inconclusive(reports);
+    let count = |keep: fn(&FunctionReport) -> bool| reports.iter().filter(|f| keep(f)).count();
+    if reports.is_empty() {
+        (Verdict::Skip, "no matching functions".to_owned())
+    } else if !failed.is_empty() {
+        let detail = format!(
+            "{} of {} failed: {}",
+            count(FunctionReport::failed),
+            reports.len(),
+            failed.join(", ")
+        );
+        (Verdict::Bad, detail)
+    } else if !inconclusive.is_empty() {
+        let detail = format!(
+            "{} of {} inconclusive: {}",
+            count(FunctionReport::inconclusive),
+            reports.len(),
+            inconclusive.join(", ")
+        );
+        (Verdict::Skip, detail)
+    } else {
+        (Verdict::Good, format!("{} functions ok", reports.len()))
+    }
+}
+
+/// Binary-searches the pass limit for one configuration, judging each limit
+/// with `judge`. A limit that cannot be judged is passed over for its nearest
+/// neighbour still in range.
+fn search(mut judge: impl FnMut(u64) -> Probe) -> (Vec<Probe>, Conclusion) {
+    let mut probes = Vec::new();
+    let mut probe = |limit: u64| {
+        let probe = judge(limit);
+        let verdict = probe.verdict;
+        let passes = probe.passes;
+        probes.push(probe);
+        (verdict, passes)
+    };
+
+    // With no passes the loop must be good...
+    match probe(0) {
+        (Verdict::Good, _) => {}
+        (Verdict::Bad, _) => return (probes, Conclusion::AlwaysBad),
+        (Verdict::Skip, _) => return (probes, Conclusion::Inconclusive { limit: 0 }),
+    }
+    // ...and with every pass it must be bad.
+    let total = match probe(UNLIMITED) {
+        (Verdict::Bad, passes) if passes > 0 => passes,
+        (Verdict::Bad, _) => return (probes, Conclusion::Inconclusive { limit: UNLIMITED }),
+        (Verdict::Good, _) => return (probes, Conclusion::NeverBad),
+        (Verdict::Skip, _) => return (probes, Conclusion::Inconclusive { limit: UNLIMITED }),
+    };
+
+    let (mut good, mut bad) = (0, total);
+    let mut skipped = Vec::new();
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        let Some(limit) = nearest_untried(good, bad, mid, &skipped) else {
+            let limit = skipped.iter().copied().filter(|&l| l > good).min();
+            let limit = limit.unwrap_or(mid);
+            return (probes, Conclusion::Inconclusive { limit });
+        };
+        match probe(limit) {
+            (Verdict::Good, _) => good = limit,
+            (Verdict::Bad, _) => bad = limit,
+            (Verdict::Skip, _) => skipped.push(limit),
+        }
+    }
+    let pass = probes
+        .iter()
+        .filter_map(|p| p.last.as_ref())
+        .find(|pass| pass.index == bad)
+        .cloned();
+    (
+        probes,
+        Conclusion::Found {
+            first_bad: bad,
+            pass,
+        },
+    )
+}
+
+/// The limit strictly between `good` and `bad` closest to `mid` that has not
+/// been skipped, if any.
+fn nearest_untried(good: u64, bad: u64, mid: u64, skipped: &[u64]) -> Option<u64> {
+    (0..bad - good)
+        .flat_map(|d| [mid.checked_sub(d), mid.checked_add(d)])
+        .flatten()
+        .find(|&limit| good < limit && limit < bad && !skipped.contains(&limit))
+}
+
+fn describe(conclusion: &Conclusion) -> String {
+    match conclusion {
+        Conclusion::Found {
+            first_bad,
+            pass: Some(pass),
+        } => format!("first bad pass: ({first_bad}) {} on {}", pass.name, pass.on),
+        Conclusion::Found { first_bad, .. } => format!("first bad pass: ({first_bad})"),
+        Conclusion::AlwaysBad => "bad even with no optimization passes".to_owned(),
+        Conclusion::NeverBad => "good with every pass; nothing to bisect".to_owned(),
+        Conclusion::Inconclusive { limit } => {
+            format!("inconclusive: limit {limit} could not be judged")
+        }
+    }
+}
+
+fn write_wrapper(path: &Path) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, WRAPPER)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(())
+}
+
+fn bisection_object(b: &Bisection) -> String {
+    let probes = b.probes.iter().map(|p| {
+        let obj = json::Object::new()
+            .u64("limit", p.limit)
+            .str("verdict", p.verdict.name())
+            .str("detail", &p.detail)
+            .u64("passes", p.passes);
+        match &p.last {
+            Some(pass) => obj.str("last_pass", &pass.name).str("last_on", &pass.on),
+            None => obj,
+        }
+        .finish()
+    });
+    let obj = json::Object::new()
+        .str("toolchain", &b.toolchain)
+        .str("profile", &b.profile.label())
+        .raw("probes", &json::array(probes))
+        .str("conclusion", b.conclusion.name());
+    match &b.conclusion {
+        Conclusion::Found { first_bad, pass } => {
+            let obj = obj.u64("first_bad", *first_bad);
+            match pass {
+                Some(pass) => obj.str("pass", &pass.name).str("function", &pass.on),
+                None => obj,
+            }
+        }
+        Conclusion::Inconclusive { limit } => obj.u64("limit", *limit),
+        Conclusion::AlwaysBad | Conclusion::NeverBad => obj,
+    }
+    .finish()
+}
+
+/// Bisects every configuration and prints the passes found, returning the
+/// process exit code.
+pub fn opt_bisect(opts: &OptBisectOptions) -> i32 {
+    let build = &opts.build;
+    let profiles = build.profiles();
+    if profiles.is_empty() {
+        eprintln!("opt-bisect has no profiles");
+        return EXIT_USAGE;
+    }
+    if opts.check() == Check::Run && opts.target.is_some() {
+        eprintln!("--check run needs a host build; use --check asm with --target");
+        return EXIT_USAGE;
+    }
+    let root = build.target_root().join("opt-bisect");
+    let wrapper = root.join("rustc-wrapper.sh");
+    if let Err(err) = write_wrapper(&wrapper) {
+        eprintln!("cannot write {}: {err}", wrapper.display());
+        return EXIT_USAGE;
+    }
+    let log_path = opts
+        .log
+        .clone()
+        .unwrap_or_else(|| build.target_root().join("opt-bisect.log"));
+    let mut log = Log::open_or_stderr(&log_path);
+    log.line(&format!(
+        "# opt-bisect started at unix time {}",
+        bisect::unix_time()
+    ));
+    log.line(&format!("# check: {}", opts.check().name()));
+    if profiles.iter().any(|p| p.codegen_units != 1) {
+        log.line("# warning: pass numbers are only stable with one codegen unit");
+    }
+
+    let mut bisections = Vec::new();
+    for toolchain in matrix::toolchains(&build.toolchains) {
+        let name = toolchain.as_deref().unwrap_or("default");
+        for profile in &profiles {
+            log.line(&format!("# {name} {}", profile.label()));
+            let config = Config {
+                opts,
+                toolchain: toolchain.as_deref(),
+                profile,
+                target_dir: root.join(name).join(profile.dir_name()),
+                wrapper: &wrapper,
+            };
+            let (probes, conclusion) = search(|limit| config.probe(limit, &mut log));
+            log.line(&format!("# {}", describe(&conclusion)));
+            bisections.push(Bisection {
+                toolchain: name.to_owned(),
+                profile: profile.clone(),
+                probes,
+                conclusion,
+            });
+        }
+    }
+
+    match build.format {
+        Format::Text => {
+            for b in &bisections {
+                println!(
+                    "{} {}: {}",
+                    b.toolchain,
+                    b.profile.label(),
+                    describe(&b.conclusion)
+                );
+            }
+        }
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .str("check", opts.check().name())
+                .str("log", &log_path.display().to_string())
+                .raw(
+                    "bisections",
+                    &json::array(bisections.iter().map(bisection_object)),
+                )
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => bisections
+            .iter()
+            .for_each(|b| println!("{}", bisection_object(b))),
+    }
+
+    let conclusions = || bisections.iter().map(|b| &b.conclusion);
+    if conclusions().any(|c| matches!(c, Conclusion::Found { .. } | Conclusion::AlwaysBad)) {
+        EXIT_DIVERGED
+    } else if conclusions().any(|c| matches!(c, Conclusion::Inconclusive { .. })) {
+        EXIT_USAGE
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inconclusive_asm_is_skipped() {
+        const X86_64_INLINE_NEVER: &str = include_str!("../testdata/asm/x86_64-inline-never.s");
+        let opaque = asm_check::check(X86_64_INLINE_NEVER, &["shapes".into()]);
+        let (verdict, detail) = judge_asm(&opaque);
+        assert_eq!(verdict, Verdict::Skip);
+        assert!(detail.starts_with("1 of 1 inconclusive: "), "{detail}");
+        assert_eq!(judge_asm(&[]).0, Verdict::Skip);
+    }
+
+    #[test]
+    fn parses_captured_lines() {
+        // From `rustc -O -C llvm-args=-opt-bisect-limit=3`.
+        assert_eq!(
+            Pass::parse("BISECT: running pass (3) inferattrs on [module]"),
+            Some(Pass {
+                index: 3,
+                name: "inferattrs".into(),
+                on: "[module]".into(),
+                ran: true,
+            })
+        );
+        assert_eq!(
+            Pass::parse(
+                "BISECT: NOT running pass (15) simplifycfg on \
+                 _ZN12subtle_repro10random_mod17he054d949c9d40610E\n"
+            ),
+            Some(Pass {
+                index: 15,
+                name: "simplifycfg".into(),
+                on: "_ZN12subtle_repro10random_mod17hE".into(),
+                ran: false,
+            })
+        );
+        assert_eq!(Pass::parse("warning: unused variable: `x`"), None);
+        assert_eq!(Pass::parse("BISECT: running pass (x) sroa on f"), None);
+    }
+
+    #[test]
+    fn nearest_untried_prefers_mid_then_below() {
+        assert_eq!(nearest_untried(0, 8, 4, &[]), Some(4));
+        assert_eq!(nearest_untried(0, 8, 4, &[4]), Some(3));
+        // Skipped on both sides of mid.
+        assert_eq!(nearest_untried(0, 8, 4, &[3, 4, 5]), Some(2));
+        assert_eq!(nearest_untried(0, 8, 4, &[2, 3, 4, 5]), Some(6));
+    }
+
+    #[test]
+    fn nearest_untried_stays_strictly_inside() {
+        assert_eq!(nearest_untried(0, 4, 2, &[1, 2, 3]), None);
+        assert_eq!(nearest_untried(3, 4, 3, &[]), None);
+        assert_eq!(nearest_untried(5, 7, 6, &[6]), None);
+    }
+
+    /// Searches `total` passes that go bad from `first_bad` on, and cannot
+    /// be judged at the limits in `skips`, returning the conclusion and the
+    /// limits probed, in order.
+    fn run(total: u64, first_bad: u64, skips: &[u64]) -> (Conclusion, Vec<u64>) {
+        let (probes, conclusion) = search(|limit| {
+            let ran = limit.min(total);
+            let verdict = if skips.contains(&limit) {
+                Verdict::Skip
+            } else if ran >= first_bad {
+                Verdict::Bad
+            } else {
+                Verdict::Good
+            };
+            Probe {
+                limit,
+                verdict,
+                detail: String::new(),
+                passes: total,
+                last: (ran > 0).then(|| pass(ran)),
+            }
+        });
+        (conclusion, probes.iter().map(|p| p.limit).collect())
+    }
+
+    fn pass(index: u64) -> Pass {
+        Pass {
+            index,
+            name: format!("pass{index}"),
+            on: "[module]".into(),
+            ran: true,
+        }
+    }
+
+    #[test]
+    fn finds_the_first_bad_pass() {
+        let (conclusion, probed) = run(10, 6, &[]);
+        assert_eq!(
+            conclusion,
+            Conclusion::Found {
+                first_bad: 6,
+                pass: Some(pass(6)),
+            }
+        );
+        assert_eq!(probed, [0, UNLIMITED, 5, 7, 6]);
+    }
+
+    #[test]
+    fn steps_past_skipped_limits() {
+        let (conclusion, probed) = run(10, 8, &[5]);
+        assert_eq!(
+            conclusion,
+            Conclusion::Found {
+                first_bad: 8,
+                pass: Some(pass(8)),
+            }
+        );
+        assert_eq!(probed, [0, UNLIMITED, 5, 4, 7, 8]);
+        // Everything between the last good and first bad limits is skipped.
+        assert_eq!(
+            run(10, 6, &[5, 6, 7]).0,
+            Conclusion::Inconclusive { limit: 5 }
+        );
+    }
+
+    #[test]
+    fn checks_both_ends_first() {
+        assert_eq!(run(10, 0, &[]).0, Conclusion::AlwaysBad);
+        assert_eq!(run(10, 11, &[]).0, Conclusion::NeverBad);
+        assert_eq!(run(10, 6, &[0]).0, Conclusion::Inconclusive { limit: 0 });
+        assert_eq!(
+            run(10, 6, &[UNLIMITED]).0,
+            Conclusion::Inconclusive { limit: UNLIMITED }
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Cross-checks every `ct_lt` decision against variable-time comparisons.
+
+use core::cmp::Ordering;
+use core::fmt;
+
+use crypto_bigint::Uint;
+use subtle::Choice;
+
+use crate::{Observer, SamplingError, hex, json};
+
+/// A candidate on which `ct_lt` disagreed with a variable-time comparison.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence<const L: usize> {
+    pub attempt: u64,
+    pub candidate: Uint<L>,
+    pub modulus: Uint<L>,
+    pub ct_lt: bool,
+    pub cmp_vartime: bool,
+    pub reference: bool,
+}
+
+impl<const L: usize> fmt::Display for Divergence<L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ct_lt disagrees on attempt {}: x = {}, n = {}, \
+             ct_lt = {}, cmp_vartime = {}, reference = {}",
+            self.attempt,
+            hex::encode(&self.candidate),
+            hex::encode(&self.modulus),
+            self.ct_lt,
+            self.cmp_vartime,
+            self.reference,
+        )
+    }
+}
+
+/// Limb-by-limb `x < n`, most significant limb first.
+pub fn reference_lt<const L: usize>(x: &Uint<L>, n: &Uint<L>) -> bool {
+    for (a, b) in x.as_words().iter().zip(n.as_words()).rev() {
+        if a != b {
+            return a < b;
+        }
+    }
+    false
+}
+
+/// Checks one `ct_lt` decision, returning the disagreement if there is one.
+pub fn check<const L: usize>(
+    attempt: u64,
+    x: &Uint<L>,
+    n: &Uint<L>,
+    lt: Choice,
+) -> Option<Divergence<L>> {
+    check_bool(attempt, x, n, lt.into())
+}
+
+/// Like [`check`], for a decision already converted to `bool`.
+pub fn check_bool<const L: usize>(
+    attempt: u64,
+    x: &Uint<L>,
+    n: &Uint<L>,
+    ct_lt: bool,
+) -> Option<Divergence<L>> {
+    let cmp_vartime = x.cmp_vartime(n) == Ordering::Less;
+    let reference = reference_lt(x, n);
+    if ct_lt == cmp_vartime && ct_lt == reference {
+        return None;
+    }
+    Some(Divergence {
+        attempt,
+        candidate: *x,
+        modulus: *n,
+        ct_lt,
+        cmp_vartime,
+        reference,
+    })
+}
+
+/// Observer that stops sampling at the first [`Divergence`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Oracle;
+
+impl<const L: usize> Observer<L> for Oracle {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        n: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        match check(attempt, x, n, lt) {
+            Some(divergence) => Err(SamplingError::Diverged(divergence)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// A [`Divergence`] with its operands already rendered, so divergences of
+/// different widths can share a type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DivergenceHex {
+    pub attempt: u64,
+    pub candidate: String,
+    pub modulus: String,
+    pub ct_lt: bool,
+    pub cmp_vartime: bool,
+    pub reference: bool,
+}
+
+impl<const L: usize> From<&Divergence<L>> for DivergenceHex {
+    fn from(d: &Divergence<L>) -> Self {
+        Self {
+            attempt: d.attempt,
+            candidate: hex::encode(&d.candidate),
+            modulus: hex::encode(&d.modulus),
+            ct_lt: d.ct_lt,
+            cmp_vartime: d.cmp_vartime,
+            reference: d.reference,
+        }
+    }
+}
+
+impl DivergenceHex {
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .u64("attempt", self.attempt)
+            .str("candidate", &self.candidate)
+            .str("modulus", &self.modulus)
+            .bool("ct_lt", self.ct_lt)
+            .bool("cmp_vartime", self.cmp_vartime)
+            .bool("reference", self.reference)
+            .finish()
+    }
+}
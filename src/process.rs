@@ -0,0 +1,136 @@
+//! Running child processes under a deadline.
+
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How a child process ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Exit {
+    /// Exited with this code.
+    Code(i32),
+    /// Killed by a signal or otherwise ended without a code.
+    Signaled,
+    /// Still running at the deadline, and killed.
+    TimedOut,
+}
+
+/// A finished child process and what it printed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Output {
+    pub exit: Exit,
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed: Duration,
+}
+
+impl Output {
+    pub fn success(&self) -> bool {
+        self.exit == Exit::Code(0)
+    }
+}
+
+/// A thread reading a pipe to its end, keeping what it has read so far.
+struct Drain {
+    read: Arc<Mutex<Vec<u8>>>,
+    handle: thread::JoinHandle<()>,
+}
+
+impl Drain {
+    fn spawn<R: Read + Send + 'static>(pipe: Option<R>) -> Self {
+        let read = Arc::new(Mutex::new(Vec::new()));
+        let sink = read.clone();
+        let handle = thread::spawn(move || {
+            let Some(mut pipe) = pipe else { return };
+            let mut chunk = [0; 8192];
+            while let Ok(len @ 1..) = pipe.read(&mut chunk) {
+                let mut sink = sink.lock().unwrap_or_else(PoisonError::into_inner);
+                sink.extend_from_slice(&chunk[..len]);
+            }
+        });
+        Self { read, handle }
+    }
+
+    /// What was read, after waiting for the pipe to close if `wait`.
+    fn finish(self, wait: bool) -> String {
+        if wait {
+            let _ = self.handle.join();
+        }
+        let read = self.read.lock().unwrap_or_else(PoisonError::into_inner);
+        String::from_utf8_lossy(&read).into_owned()
+    }
+}
+
+/// Runs `command` with captured output, killing it after `timeout`.
+///
+/// Only the child itself is killed. Its own children, such as cargo's
+/// rustc processes, may keep the pipes open, so after a timeout the output
+/// is what was read by then rather than waited for.
+pub fn run(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    let start = Instant::now();
+    let mut child: Child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = Drain::spawn(child.stdout.take());
+    let stderr = Drain::spawn(child.stderr.take());
+    let exit = loop {
+        if let Some(status) = child.try_wait()? {
+            break status.code().map_or(Exit::Signaled, Exit::Code);
+        }
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            break Exit::TimedOut;
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let wait = exit != Exit::TimedOut;
+    Ok(Output {
+        exit,
+        stdout: stdout.finish(wait),
+        stderr: stderr.finish(wait),
+        elapsed: start.elapsed(),
+    })
+}
+
+/// The first executable called `name` on `PATH`.
+pub fn which(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_does_not_wait_for_grandchildren() {
+        let mut command = Command::new("sh");
+        // The grandchild holds the pipes open past the timeout, but not so
+        // long that it outlives the test run by much.
+        command.args(["-c", "sleep 3 & echo started; sleep 3"]);
+        let out = run(&mut command, Duration::from_millis(500)).unwrap();
+        assert_eq!(out.exit, Exit::TimedOut);
+        assert_eq!(out.stdout, "started\n");
+        assert!(out.elapsed < Duration::from_secs(2), "{:?}", out.elapsed);
+    }
+
+    #[test]
+    fn captures_output_and_code() {
+        let mut command = Command::new("sh");
+        command.args(["-c", "echo out; echo err >&2; exit 3"]);
+        let out = run(&mut command, Duration::from_secs(30)).unwrap();
+        assert_eq!(out.exit, Exit::Code(3));
+        assert_eq!(
+            (out.stdout.as_str(), out.stderr.as_str()),
+            ("out\n", "err\n")
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Capturing the exact stream a sampler drew, and replaying it.
+//!
+//! A recording is a text file with one call per line:
+//!
+//! ```text
+//! # subtle-repro rng recording v1
+//! u64 8d5cbbd7a4b2c8e1
+//! u32 1f00ba42
+//! fill 00ff10
+//! ```
+
+use core::fmt;
+use std::fs::File;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+
+use rand_core::RngCore;
+
+use crate::rng::{InputStopped, Stoppable};
+
+const HEADER: &str = "# subtle-repro rng recording v1";
+
+/// One `RngCore` call and what it returned.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Call {
+    U32(u32),
+    U64(u64),
+    Fill(Vec<u8>),
+}
+
+impl Call {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::U32(_) => "u32",
+            Self::U64(_) => "u64",
+            Self::Fill(_) => "fill",
+        }
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::U32(v) => write!(f, "u32 {v:08x}"),
+            Self::U64(v) => write!(f, "u64 {v:016x}"),
+            Self::Fill(bytes) => {
+                f.write_str("fill ")?;
+                bytes.iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+        }
+    }
+}
+
+/// A parsed recording.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+    pub calls: Vec<Call>,
+}
+
+impl Recording {
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Self::parse(&text).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut calls = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let bad = |what: &str| format!("line {}: {what}: {line:?}", i + 1);
+            let (kind, value) = line.split_once(' ').ok_or_else(|| bad("missing value"))?;
+            let call = match kind {
+                "u32" => Call::U32(u32::from_str_radix(value, 16).map_err(|_| bad("bad u32"))?),
+                "u64" => Call::U64(u64::from_str_radix(value, 16).map_err(|_| bad("bad u64"))?),
+                "fill" => Call::Fill(decode_bytes(value).ok_or_else(|| bad("bad bytes"))?),
+                _ => return Err(bad("unknown call")),
+            };
+            calls.push(call);
+        }
+        Ok(Self { calls })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut out = String::new();
+        out.push_str(HEADER);
+        out.push('\n');
+        for call in &self.calls {
+            out.push_str(&call.to_string());
+            out.push('\n');
+        }
+        std::fs::write(path, out)
+    }
+}
+
+fn decode_bytes(s: &str) -> Option<Vec<u8>> {
+    if s.len() % 2 == 1 {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Wraps a generator and writes every call it serves to `W`.
+///
+/// The first write error is kept and reported by [`finish`](Self::finish);
+/// later calls still succeed but are not recorded.
+#[derive(Debug)]
+pub struct RecordingRng<R, W: Write = LineWriter<File>> {
+    inner: R,
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<R: RngCore> RecordingRng<R> {
+    /// Records to a new file at `path`, flushed line by line so the
+    /// recording survives the watchdog exiting the process.
+    pub fn create(inner: R, path: &Path) -> io::Result<Self> {
+        Self::new(inner, LineWriter::new(File::create(path)?))
+    }
+}
+
+impl<R: RngCore, W: Write> RecordingRng<R, W> {
+    pub fn new(inner: R, mut out: W) -> io::Result<Self> {
+        writeln!(out, "{HEADER}")?;
+        Ok(Self {
+            inner,
+            out,
+            error: None,
+        })
+    }
+
+    /// The wrapped generator.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    fn log(&mut self, call: Call) {
+        if self.error.is_some() {
+            return;
+        }
+        if let Err(err) = writeln!(self.out, "{call}") {
+            self.error = Some(err);
+        }
+    }
+
+    /// Flushes the recording and returns the wrapped generator.
+    pub fn finish(mut self) -> io::Result<R> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<R: Stoppable, W: Write> Stoppable for RecordingRng<R, W> {
+    fn stopped(&self) -> Option<InputStopped> {
+        self.inner.stopped()
+    }
+}
+
+impl<R: RngCore, W: Write> RngCore for RecordingRng<R, W> {
+    fn next_u32(&mut self) -> u32 {
+        let v = self.inner.next_u32();
+        self.log(Call::U32(v));
+        v
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let v = self.inner.next_u64();
+        self.log(Call::U64(v));
+        v
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.inner.fill_bytes(dest);
+        self.log(Call::Fill(dest.to_vec()));
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.inner.try_fill_bytes(dest)?;
+        self.log(Call::Fill(dest.to_vec()));
+        Ok(())
+    }
+}
+
+/// Serves the calls of a [`Recording`] back in order.
+///
+/// # Panics
+///
+/// Unless built with [`lenient`](Self::lenient), panics if the consumer asks
+/// for a different call than the one recorded next, or for more calls than
+/// were recorded.
+#[derive(Clone, Debug)]
+pub struct ReplayRng<'a> {
+    calls: std::slice::Iter<'a, Call>,
+    served: usize,
+    lenient: bool,
+    stopped: bool,
+}
+
+impl<'a> ReplayRng<'a> {
+    pub fn new(recording: &'a Recording) -> Self {
+        Self::from_calls(&recording.calls)
+    }
+
+    pub fn from_calls(calls: &'a [Call]) -> Self {
+        Self {
+            calls: calls.iter(),
+            served: 0,
+            lenient: false,
+            stopped: false,
+        }
+    }
+
+    /// Like [`from_calls`](Self::from_calls), but once the recording runs out
+    /// or stops matching, serves zeros and sets [`is_stopped`](Self::is_stopped)
+    /// instead of panicking.
+    pub fn lenient(calls: &'a [Call]) -> Self {
+        Self {
+            lenient: true,
+            ..Self::from_calls(calls)
+        }
+    }
+
+    /// Number of calls served so far.
+    pub fn served(&self) -> usize {
+        self.served
+    }
+
+    /// Whether a lenient replay has run out of matching calls.
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+
+    fn next_call(&mut self, wanted: &str) -> Option<&'a Call> {
+        if self.stopped {
+            return None;
+        }
+        let Some(call) = self.calls.next() else {
+            if self.lenient {
+                self.stopped = true;
+                return None;
+            }
+            panic!(
+                "replay exhausted after {} calls; wanted {wanted}",
+                self.served
+            );
+        };
+        self.served += 1;
+        Some(call)
+    }
+
+    fn mismatch(&mut self, wanted: &str, call: &Call) {
+        if self.lenient {
+            self.stopped = true;
+            return;
+        }
+        panic!(
+            "replay diverged at call {}: wanted {wanted}, recording has {}",
+            self.served,
+            call.kind()
+        );
+    }
+}
+
+impl Stoppable for ReplayRng<'_> {
+    fn stopped(&self) -> Option<InputStopped> {
+        self.stopped.then_some(InputStopped::Replay {
+            served: self.served,
+        })
+    }
+}
+
+impl RngCore for ReplayRng<'_> {
+    fn next_u32(&mut self) -> u32 {
+        match self.next_call("u32") {
+            Some(Call::U32(v)) => *v,
+            Some(call) => {
+                self.mismatch("u32", call);
+                0
+            }
+            None => 0,
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self.next_call("u64") {
+            Some(Call::U64(v)) => *v,
+            Some(call) => {
+                self.mismatch("u64", call);
+                0
+            }
+            None => 0,
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let wanted = format!("fill of {} bytes", dest.len());
+        match self.next_call(&wanted) {
+            Some(Call::Fill(bytes)) if bytes.len() == dest.len() => dest.copy_from_slice(bytes),
+            Some(call) => {
+                self.mismatch(&wanted, call);
+                dest.fill(0);
+            }
+            None => dest.fill(0),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crypto_bigint::{NonZero, Uint};
+    use rand_chacha::ChaCha8Rng;
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    /// Accepts `room` bytes, then fails every write.
+    struct Full {
+        room: usize,
+    }
+
+    impl Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.room == 0 {
+                return Err(io::ErrorKind::StorageFull.into());
+            }
+            let len = buf.len().min(self.room);
+            self.room -= len;
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Records a run of the original sampler, returning the recording and
+    /// the value sampled.
+    fn record_run() -> (String, Uint<2>) {
+        let mut out = Vec::new();
+        let mut rng = RecordingRng::new(ChaCha8Rng::seed_from_u64(1), &mut out).unwrap();
+        let n = NonZero::new(Uint::from_u64(1000)).unwrap();
+        rng.next_u32();
+        let x = crate::random_mod(&mut rng, &n);
+        rng.finish().unwrap();
+        (String::from_utf8(out).unwrap(), x)
+    }
+
+    #[test]
+    fn replay_round_trips() {
+        let (text, x) = record_run();
+        assert!(text.starts_with(HEADER));
+        let recording = Recording::parse(&text).unwrap();
+        assert_eq!(recording.calls[0].kind(), "u32");
+        let mut replay = ReplayRng::new(&recording);
+        let mut seeded = ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(replay.next_u32(), seeded.next_u32());
+        let n = NonZero::new(Uint::from_u64(1000)).unwrap();
+        assert_eq!(crate::random_mod(&mut replay, &n), x);
+        assert_eq!(replay.served(), recording.calls.len());
+    }
+
+    #[test]
+    fn truncated_replay_stops() {
+        let (text, _) = record_run();
+        let text: String = text.lines().take(2).map(|l| format!("{l}\n")).collect();
+        let recording = Recording::parse(&text).unwrap();
+        assert_eq!(recording.calls.len(), 1);
+
+        let mut replay = ReplayRng::lenient(&recording.calls);
+        replay.next_u32();
+        assert!(!replay.is_stopped());
+        assert_eq!(replay.next_u64(), 0);
+        assert!(replay.is_stopped());
+        assert_eq!(replay.served(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "replay exhausted after 1 calls; wanted u64")]
+    fn strict_replay_panics_when_truncated() {
+        let recording = Recording::parse("u32 00000001\n").unwrap();
+        let mut replay = ReplayRng::new(&recording);
+        replay.next_u32();
+        replay.next_u64();
+    }
+
+    #[test]
+    fn finish_reports_a_lost_write() {
+        let out = Full {
+            room: HEADER.len() + 1,
+        };
+        let mut rng = RecordingRng::new(ChaCha8Rng::seed_from_u64(1), out).unwrap();
+        rng.next_u64();
+        rng.next_u64();
+        let err = rng.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::StorageFull);
+    }
+}
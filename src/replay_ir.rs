@@ -0,0 +1,709 @@
+//! The `replay-ir` command: the sampling loop's unoptimized LLVM IR, run
+//! through a local `opt` for the host and for another target, by default
+//! `aarch64-unknown-linux-gnu`, so the two pipelines can be compared on an
+//! x86 machine.
+//!
+//! The library is emitted with `-C no-prepopulate-passes`, which leaves the
+//! IR as rustc's frontend wrote it. When the toolchain has the target's
+//! standard library the target's IR is emitted the same way; otherwise the
+//! host's IR is retargeted by swapping its triple and data layout and
+//! dropping the x86 CPU attributes and inline-assembly details. Retargeted
+//! IR still lowers arguments and returns as the host's ABI does, so the
+//! target's results from it are printed as advisory and left out of the
+//! verdict.
+//!
+//! Each `-O` level is replayed for both triples, the matching functions are
+//! extracted and diffed, and when `llc` is found the result is lowered and
+//! checked as by `check-asm`. `opt` has to read the IR of the toolchain's
+//! LLVM, so the toolchain's own `llvm-tools` are preferred.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::asm_check::{self, FunctionReport};
+use crate::build_info::build_info;
+use crate::matrix::{self, MatrixOptions, Profile};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+use crate::{emit, json, process, toolchain};
+
+/// Options for the `replay-ir` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayIrOptions {
+    /// Toolchains and profiles to capture IR with, as for `matrix`.
+    pub build: MatrixOptions,
+    /// `opt` levels to replay: `0`, `1`, `2`, `3`, `s` or `z`.
+    pub levels: Vec<String>,
+    /// The triple to compare with the host's.
+    pub target: String,
+    /// Substrings of the symbols to diff and check.
+    pub functions: Vec<String>,
+    /// Defaults to the toolchain's `llvm-tools`, then `opt-<major>` and
+    /// `opt` on the path.
+    pub opt: Option<PathBuf>,
+    /// Found like `opt`; without one, nothing is lowered.
+    pub llc: Option<PathBuf>,
+    /// Defaults to `target/replay-ir` in the source directory.
+    pub out: Option<PathBuf>,
+}
+
+impl Default for ReplayIrOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions {
+                opt_levels: vec!["3".into()],
+                codegen_units: vec![1],
+                ..MatrixOptions::default()
+            },
+            levels: ["0", "1", "2", "3", "s", "z"].map(String::from).into(),
+            target: "aarch64-unknown-linux-gnu".into(),
+            functions: vec!["random_mod".into()],
+            opt: None,
+            llc: None,
+            out: None,
+        }
+    }
+}
+
+/// Where the target's unoptimized IR came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// rustc emitted it for the target.
+    Emitted,
+    /// The host's IR, given the target's triple and data layout. It keeps
+    /// the host's ABI.
+    Retargeted,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Emitted => "emitted",
+            Self::Retargeted => "retargeted",
+        }
+    }
+}
+
+/// The LLVM tools used for one toolchain.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tools {
+    pub opt: PathBuf,
+    pub llc: Option<PathBuf>,
+    /// The LLVM major version of the toolchain, and of `opt`, when known.
+    pub llvm: (Option<String>, Option<String>),
+}
+
+impl Tools {
+    /// The toolchain's and `opt`'s LLVM versions, when they differ.
+    fn mismatch(&self) -> Option<(&str, &str)> {
+        let (Some(rustc), Some(opt)) = &self.llvm else {
+            return None;
+        };
+        (rustc != opt).then_some((rustc, opt))
+    }
+}
+
+/// One triple's pipeline at one level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pipeline {
+    pub triple: String,
+    /// Functions extracted from `opt`'s output, or why there are none.
+    pub functions: Result<usize, String>,
+    /// `check-asm` on `llc`'s output; `None` without `llc` or when `opt`
+    /// failed.
+    pub reports: Option<Result<Vec<FunctionReport>, String>>,
+    /// Replayed from retargeted IR, so left out of the verdict.
+    pub advisory: bool,
+}
+
+impl Pipeline {
+    fn failed(&self) -> bool {
+        !self.advisory
+            && matches!(&self.reports, Some(Ok(reports)) if reports.iter().any(FunctionReport::failed))
+    }
+}
+
+/// Both pipelines at one level.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Level {
+    pub level: String,
+    pub host: Pipeline,
+    pub target: Pipeline,
+    /// `diff -u` of the host's functions against the target's.
+    pub diff: PathBuf,
+    pub differs: Result<bool, String>,
+}
+
+/// One configuration's replay.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Replayed {
+    pub toolchain: String,
+    pub profile: Profile,
+    /// Holds the inputs, one directory of output per triple, and `diff`.
+    pub dir: PathBuf,
+    /// Where the target's IR came from, or why nothing was captured.
+    pub source: Result<Source, String>,
+    pub levels: Vec<Level>,
+}
+
+/// The LLVM major version `tool --version` reports.
+fn tool_llvm_major(tool: &Path) -> Option<String> {
+    let out = Command::new(tool).arg("--version").output().ok()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    let version = text.split("LLVM version ").nth(1)?;
+    version
+        .split('.')
+        .next()
+        .map(|major| major.trim().to_owned())
+}
+
+/// `given`, else `name` from the toolchain's `llvm-tools`, else
+/// `<name>-<major>` or `name` on the path.
+fn find_tool(
+    given: Option<&Path>,
+    name: &str,
+    toolchain: Option<&str>,
+    major: Option<&str>,
+) -> Option<PathBuf> {
+    if let Some(given) = given {
+        return Some(given.to_owned());
+    }
+    let bundled = toolchain::sysroot(toolchain).ok().and_then(|sysroot| {
+        let host = toolchain::verbose_field(toolchain, "host").ok()?;
+        let tool = sysroot
+            .join("lib")
+            .join("rustlib")
+            .join(host)
+            .join("bin")
+            .join(name);
+        tool.is_file().then_some(tool)
+    });
+    bundled
+        .or_else(|| process::which(&format!("{name}-{}", major?)))
+        .or_else(|| process::which(name))
+}
+
+fn tools(opts: &ReplayIrOptions, toolchain: Option<&str>) -> Result<Tools, String> {
+    let major = toolchain::verbose_field(toolchain, "LLVM version")
+        .ok()
+        .and_then(|v| v.split('.').next().map(str::to_owned));
+    let opt =
+        find_tool(opts.opt.as_deref(), "opt", toolchain, major.as_deref()).ok_or_else(|| {
+            let name = toolchain.unwrap_or("default");
+            format!("no opt found; run `rustup component add llvm-tools --toolchain {name}`")
+        })?;
+    let llc = find_tool(opts.llc.as_deref(), "llc", toolchain, major.as_deref());
+    let opt_major = tool_llvm_major(&opt);
+    Ok(Tools {
+        opt,
+        llc,
+        llvm: (major, opt_major),
+    })
+}
+
+/// Removes every `"key"="value"` string attribute from `line`.
+fn strip_attribute(line: &str, key: &str) -> String {
+    let needle = format!("\"{key}\"=\"");
+    let mut out = line.to_owned();
+    while let Some(start) = out.find(&needle) {
+        let value = start + needle.len();
+        let Some(len) = out[value..].find('"') else {
+            break;
+        };
+        let mut end = value + len + 1;
+        if out[end..].starts_with(' ') {
+            end += 1;
+        }
+        out.replace_range(start..end, "");
+    }
+    out
+}
+
+/// Gives host IR `triple` and `data_layout`, and drops what only makes
+/// sense on x86: CPU and feature attributes, Intel-dialect inline assembly,
+/// its `q` operand modifiers and its flag clobbers.
+pub fn retarget(ir: &str, triple: &str, data_layout: &str) -> String {
+    let mut out = String::with_capacity(ir.len());
+    for line in ir.lines() {
+        if line.starts_with("target datalayout = ") {
+            out.push_str(&format!("target datalayout = \"{data_layout}\""));
+        } else if line.starts_with("target triple = ") {
+            out.push_str(&format!("target triple = \"{triple}\""));
+        } else if line.starts_with("attributes #") {
+            let mut line = line.to_owned();
+            for key in ["target-cpu", "target-features", "tune-cpu"] {
+                line = strip_attribute(&line, key);
+            }
+            out.push_str(&line);
+        } else if line.starts_with(' ') && line.contains(" asm ") {
+            let mut line = line.replace(" asm inteldialect ", " asm ");
+            line = line.replace(":q}", "}");
+            for clobber in [",~{dirflag}", ",~{fpsr}", ",~{flags}"] {
+                line = line.replace(clobber, "");
+            }
+            out.push_str(&line);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Emits the library's IR without running any LLVM passes.
+fn capture(
+    opts: &ReplayIrOptions,
+    toolchain: Option<&str>,
+    target: Option<&str>,
+    profile: &Profile,
+    target_dir: &Path,
+) -> Result<String, String> {
+    let build = &opts.build;
+    let mut command = emit::emit_command(
+        toolchain,
+        target,
+        profile,
+        &build.source,
+        target_dir,
+        build.offline,
+    );
+    command.args(["-C", "no-prepopulate-passes"]);
+    emit::run_emit(command, target, target_dir, build.build_timeout).map(|(ir, _, _)| ir)
+}
+
+/// Writes the host's and the target's unoptimized IR to `dir`, returning
+/// their paths and where the target's came from.
+fn capture_inputs(
+    opts: &ReplayIrOptions,
+    toolchain: Option<&str>,
+    profile: &Profile,
+    target_dir: &Path,
+    dir: &Path,
+) -> Result<(PathBuf, PathBuf, Source), String> {
+    let io = |err: std::io::Error| err.to_string();
+    let host_ir = capture(opts, toolchain, None, profile, target_dir)?;
+    let native = toolchain::has_target(toolchain, &opts.target).unwrap_or(false);
+    let (target_ir, source) = if native {
+        // The host's CPU means nothing to the target.
+        let mut profile = profile.clone();
+        profile.target_cpu = profile.target_cpu.filter(|cpu| cpu != "native");
+        let ir = capture(opts, toolchain, Some(&opts.target), &profile, target_dir)?;
+        (ir, Source::Emitted)
+    } else {
+        let layout = toolchain::data_layout(toolchain, &opts.target)
+            .map_err(|err| format!("cannot find the data layout of {}: {err}", opts.target))?;
+        (
+            retarget(&host_ir, &opts.target, &layout),
+            Source::Retargeted,
+        )
+    };
+    fs::create_dir_all(dir).map_err(io)?;
+    let host = dir.join("input-host.ll");
+    let target = dir.join("input-target.ll");
+    fs::write(&host, host_ir).map_err(io)?;
+    fs::write(&target, target_ir).map_err(io)?;
+    Ok((host, target, source))
+}
+
+/// Runs `tool args` under `timeout`, returning its error on failure.
+fn run_tool(tool: &Path, args: &[&std::ffi::OsStr], timeout: Duration) -> Result<(), String> {
+    let mut command = Command::new(tool);
+    command.args(args);
+    let out = process::run(&mut command, timeout)
+        .map_err(|err| format!("cannot run {}: {err}", tool.display()))?;
+    if !out.success() {
+        // The error itself, rather than the line it quotes after it.
+        let error = out.stderr.lines().find(|line| line.contains("error:"));
+        return Err(error.map_or_else(|| matrix::tail(&out.stderr, 5), str::to_owned));
+    }
+    Ok(())
+}
+
+/// Runs `opt` and, with `llc`, lowers its output, for one triple and level.
+fn replay(
+    opts: &ReplayIrOptions,
+    tools: &Tools,
+    input: &Path,
+    triple: &str,
+    level: &str,
+    dir: &Path,
+) -> Pipeline {
+    let timeout = opts.build.build_timeout;
+    let optimized = dir.join(format!("O{level}.ll"));
+    let functions_path = dir.join(format!("O{level}.functions.ll"));
+    let mtriple = format!("-mtriple={triple}");
+    let olevel = format!("-O{level}");
+    let functions = fs::create_dir_all(dir)
+        .map_err(|err| err.to_string())
+        .and_then(|()| {
+            run_tool(
+                &tools.opt,
+                &[
+                    "-S".as_ref(),
+                    olevel.as_ref(),
+                    mtriple.as_ref(),
+                    input.as_os_str(),
+                    "-o".as_ref(),
+                    optimized.as_os_str(),
+                ],
+                timeout,
+            )
+        })
+        .and_then(|()| {
+            let ir = fs::read_to_string(&optimized).map_err(|err| err.to_string())?;
+            let functions = emit::extract_ir(&ir, &opts.functions);
+            fs::write(&functions_path, emit::join(&functions)).map_err(|err| err.to_string())?;
+            Ok(functions.len())
+        });
+
+    let reports = tools.llc.as_ref().filter(|_| functions.is_ok()).map(|llc| {
+        let asm = dir.join(format!("O{level}.s"));
+        // llc has no size levels.
+        let llc_level = match level {
+            "s" | "z" => "-O2".to_owned(),
+            _ => olevel.clone(),
+        };
+        run_tool(
+            llc,
+            &[
+                llc_level.as_ref(),
+                mtriple.as_ref(),
+                optimized.as_os_str(),
+                "-o".as_ref(),
+                asm.as_os_str(),
+            ],
+            timeout,
+        )?;
+        let text = fs::read_to_string(&asm).map_err(|err| err.to_string())?;
+        Ok(asm_check::check(&text, &opts.functions))
+    });
+
+    Pipeline {
+        triple: triple.to_owned(),
+        functions,
+        reports,
+        advisory: false,
+    }
+}
+
+fn replay_one(
+    opts: &ReplayIrOptions,
+    out: &Path,
+    toolchain: Option<&str>,
+    tools: &Tools,
+    profile: &Profile,
+) -> Replayed {
+    let build = &opts.build;
+    let name = toolchain.unwrap_or("default");
+    // One module, so the IR can be read back as a whole.
+    let mut profile = profile.clone();
+    profile.codegen_units = 1;
+    let dir = out.join(name).join(profile.dir_name());
+    let target_dir = build
+        .target_root()
+        .join("replay-ir")
+        .join(name)
+        .join(profile.dir_name());
+    eprintln!("capturing {name} {}", profile.label());
+    let mut replayed = Replayed {
+        toolchain: name.to_owned(),
+        profile: profile.clone(),
+        dir: dir.clone(),
+        source: Err(String::new()),
+        levels: Vec::new(),
+    };
+    let (host_input, target_input, source) =
+        match capture_inputs(opts, toolchain, &profile, &target_dir, &dir) {
+            Ok(inputs) => inputs,
+            Err(err) => {
+                eprintln!("{err}");
+                replayed.source = Err(err);
+                return replayed;
+            }
+        };
+    replayed.source = Ok(source);
+    if source == Source::Retargeted {
+        eprintln!(
+            "{name} has no {target} standard library, so its IR is the host's retargeted, \
+             with the host's ABI; the {target} results are advisory. Run \
+             `rustup target add {target} --toolchain {name}` for a verdict",
+            target = opts.target
+        );
+    }
+    let host_triple = toolchain::verbose_field(toolchain, "host")
+        .unwrap_or_else(|_| "x86_64-unknown-linux-gnu".to_owned());
+    let diff_dir = dir.join("diff");
+    for level in &opts.levels {
+        eprintln!("  O{level}");
+        let host = replay(
+            opts,
+            tools,
+            &host_input,
+            &host_triple,
+            level,
+            &dir.join(&host_triple),
+        );
+        let mut target = replay(
+            opts,
+            tools,
+            &target_input,
+            &opts.target,
+            level,
+            &dir.join(&opts.target),
+        );
+        target.advisory = source == Source::Retargeted;
+        let diff = diff_dir.join(format!("O{level}.ll.diff"));
+        let differs = match (&host.functions, &target.functions) {
+            (Ok(_), Ok(_)) => fs::create_dir_all(&diff_dir)
+                .map_err(|err| err.to_string())
+                .and_then(|()| {
+                    emit::diff(
+                        &dir.join(&host_triple)
+                            .join(format!("O{level}.functions.ll")),
+                        &dir.join(&opts.target)
+                            .join(format!("O{level}.functions.ll")),
+                        &diff,
+                    )
+                }),
+            (Err(err), _) | (_, Err(err)) => Err(err.clone()),
+        };
+        replayed.levels.push(Level {
+            level: level.clone(),
+            host,
+            target,
+            diff,
+            differs,
+        });
+    }
+    replayed
+}
+
+fn pipeline_summary(p: &Pipeline) -> String {
+    let functions = match &p.functions {
+        Ok(n) => format!("{n} functions"),
+        Err(err) => return format!("opt failed: {}", err.lines().next().unwrap_or(err)),
+    };
+    let summary = match &p.reports {
+        None => functions,
+        Some(Ok(reports)) => format!("{functions}, check-asm {}", asm_check::summary(reports)),
+        Some(Err(err)) => format!(
+            "{functions}, llc failed: {}",
+            err.lines().next().unwrap_or(err)
+        ),
+    };
+    if p.advisory {
+        format!("{summary} (advisory: host ABI)")
+    } else {
+        summary
+    }
+}
+
+fn print_text(replayed: &[Replayed], tools: &[(String, Result<Tools, String>)]) {
+    for (name, tools) in tools {
+        match tools {
+            Ok(tools) => {
+                let llc = tools
+                    .llc
+                    .as_ref()
+                    .map_or("none".to_owned(), |llc| llc.display().to_string());
+                println!("{name}: opt {}, llc {llc}", tools.opt.display());
+            }
+            Err(err) => println!("{name}: {err}"),
+        }
+    }
+    for r in replayed {
+        println!("{} {}:", r.toolchain, r.profile.label());
+        let source = match &r.source {
+            Ok(source) => source,
+            Err(err) => {
+                println!("  capture failed: {}", matrix::tail(err, 1));
+                continue;
+            }
+        };
+        println!(
+            "  target IR {}; output in {}",
+            source.name(),
+            r.dir.display()
+        );
+        for l in &r.levels {
+            let verb = match &l.differs {
+                Ok(true) => "differs",
+                Ok(false) => "same",
+                Err(_) => "not diffed",
+            };
+            println!("  O{}: {verb}", l.level);
+            for p in [&l.host, &l.target] {
+                println!("    {:<28}  {}", p.triple, pipeline_summary(p));
+            }
+        }
+    }
+}
+
+fn pipeline_object(p: &Pipeline) -> String {
+    let obj = json::Object::new()
+        .str("triple", &p.triple)
+        .bool("advisory", p.advisory);
+    let obj = match &p.functions {
+        Ok(n) => obj.u64("functions", *n as u64),
+        Err(err) => obj.str("opt_error", err),
+    };
+    match &p.reports {
+        None => obj.raw("asm_passed", "null"),
+        Some(Ok(reports)) => asm_check::add_verdict(obj, reports),
+        Some(Err(err)) => obj.str("llc_error", err),
+    }
+    .finish()
+}
+
+fn replayed_object(r: &Replayed) -> String {
+    let obj = json::Object::new()
+        .str("toolchain", &r.toolchain)
+        .str("profile", &r.profile.label())
+        .str("dir", &r.dir.display().to_string());
+    let obj = match &r.source {
+        Ok(source) => obj.str("source", source.name()),
+        Err(err) => obj.str("capture_error", err),
+    };
+    let levels = r.levels.iter().map(|l| {
+        let obj = json::Object::new()
+            .str("level", &l.level)
+            .raw("host", &pipeline_object(&l.host))
+            .raw("target", &pipeline_object(&l.target))
+            .str("diff", &l.diff.display().to_string());
+        match &l.differs {
+            Ok(differs) => obj.bool("differs", *differs),
+            Err(err) => obj.str("diff_error", err),
+        }
+        .finish()
+    });
+    obj.raw("levels", &json::array(levels)).finish()
+}
+
+/// Captures, replays and diffs every configuration, returning the process
+/// exit code.
+pub fn replay_ir(opts: &ReplayIrOptions) -> i32 {
+    let profiles = opts.build.profiles();
+    if profiles.is_empty() || opts.levels.is_empty() {
+        eprintln!("replay-ir has no profiles or no levels");
+        return EXIT_USAGE;
+    }
+    let out = opts
+        .out
+        .clone()
+        .unwrap_or_else(|| opts.build.source.join("target").join("replay-ir"));
+
+    let mut replayed = Vec::new();
+    let mut found = Vec::new();
+    for toolchain in matrix::toolchains(&opts.build.toolchains) {
+        let name = toolchain.as_deref().unwrap_or("default");
+        let tools = tools(opts, toolchain.as_deref());
+        match &tools {
+            Ok(tools) => {
+                if let Some((rustc, opt)) = tools.mismatch() {
+                    eprintln!(
+                        "{} is LLVM {opt} but {name} emits LLVM {rustc} IR, which it may not \
+                         read; run `rustup component add llvm-tools --toolchain {name}`",
+                        tools.opt.display()
+                    );
+                }
+                for profile in &profiles {
+                    replayed.push(replay_one(opts, &out, toolchain.as_deref(), tools, profile));
+                }
+            }
+            Err(err) => eprintln!("{name}: {err}"),
+        }
+        found.push((name.to_owned(), tools));
+    }
+
+    match opts.build.format {
+        Format::Text => print_text(&replayed, &found),
+        Format::Json => {
+            let tools = found.iter().map(|(name, tools)| {
+                let obj = json::Object::new().str("toolchain", name);
+                match tools {
+                    Ok(tools) => {
+                        let obj = obj.str("opt", &tools.opt.display().to_string());
+                        match &tools.llc {
+                            Some(llc) => obj.str("llc", &llc.display().to_string()),
+                            None => obj.raw("llc", "null"),
+                        }
+                    }
+                    Err(err) => obj.str("error", err),
+                }
+                .finish()
+            });
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .str("target", &opts.target)
+                .str("out", &out.display().to_string())
+                .raw("tools", &json::array(tools))
+                .raw(
+                    "configs",
+                    &json::array(replayed.iter().map(replayed_object)),
+                )
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => replayed
+            .iter()
+            .for_each(|r| println!("{}", replayed_object(r))),
+    }
+
+    let levels = || replayed.iter().flat_map(|r| &r.levels);
+    if levels().any(|l| l.host.failed() || l.target.failed()) {
+        EXIT_DIVERGED
+    } else if levels().any(|l| l.host.functions.is_ok() && l.target.functions.is_ok()) {
+        0
+    } else {
+        EXIT_USAGE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm_check::{Arch, Finding, RegionReport};
+
+    #[test]
+    fn advisory_failures_are_not_failures() {
+        let report = FunctionReport {
+            name: "random_mod".into(),
+            arch: Arch::Aarch64,
+            regions: vec![RegionReport {
+                looped: true,
+                lines: (1, 2),
+                findings: vec![Finding::NoExit],
+                evidence: None,
+            }],
+        };
+        let mut pipeline = Pipeline {
+            triple: "aarch64-unknown-linux-gnu".into(),
+            functions: Ok(1),
+            reports: Some(Ok(vec![report])),
+            advisory: false,
+        };
+        assert!(pipeline.failed());
+        pipeline.advisory = true;
+        assert!(!pipeline.failed());
+        assert!(pipeline_summary(&pipeline).ends_with("(advisory: host ABI)"));
+    }
+
+    #[test]
+    fn inconclusive_is_not_failed() {
+        const X86_64_INLINE_NEVER: &str = include_str!("../testdata/asm/x86_64-inline-never.s");
+        let reports = asm_check::check(X86_64_INLINE_NEVER, &["shapes".into()]);
+        let pipeline = Pipeline {
+            triple: "x86_64-unknown-linux-gnu".into(),
+            functions: Ok(1),
+            reports: Some(Ok(reports)),
+            advisory: false,
+        };
+        assert!(!pipeline.failed());
+        let summary = pipeline_summary(&pipeline);
+        assert!(summary.contains("check-asm inconclusive: "), "{summary}");
+        let json = pipeline_object(&pipeline);
+        assert!(json.contains("\"asm_failed\":[]"), "{json}");
+    }
+}
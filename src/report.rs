@@ -0,0 +1,110 @@
+//! Machine-readable run reports.
+
+use core::fmt;
+use core::str::FromStr;
+
+use crate::build_info::build_info;
+use crate::choice_audit::ChoiceAuditHex;
+use crate::json;
+use crate::oracle::DivergenceHex;
+use crate::rng::RngKind;
+
+/// How results are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    /// Human-readable output.
+    #[default]
+    Text,
+    /// A single JSON document.
+    Json,
+    /// One JSON object per line.
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            _ => Err(format!("unknown format {s:?}")),
+        }
+    }
+}
+
+/// How a run ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RunResult {
+    Ok,
+    Diverged,
+    Exhausted,
+    Stalled,
+    /// A `Choice` held something other than 0 or 1.
+    BadChoice,
+    /// The script or replayed recording ran out, or the recording stopped
+    /// matching.
+    InputStopped,
+}
+
+impl RunResult {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Diverged => "diverged",
+            Self::Exhausted => "exhausted",
+            Self::Stalled => "stalled",
+            Self::BadChoice => "bad-choice",
+            Self::InputStopped => "input-stopped",
+        }
+    }
+}
+
+impl fmt::Display for RunResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Everything known about one `run`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunReport {
+    pub seed: u64,
+    pub limbs: usize,
+    pub rng: RngKind,
+    pub modulus: String,
+    pub attempts: u64,
+    pub result: RunResult,
+    /// The accepted sample, if any.
+    pub value: Option<String>,
+    pub disagreements: Vec<DivergenceHex>,
+    /// Set when every `Choice` was audited.
+    pub choice_audit: Option<ChoiceAuditHex>,
+}
+
+impl RunReport {
+    pub fn to_json(&self) -> String {
+        let obj = json::Object::new()
+            .raw("build", &build_info().to_json())
+            .u64("seed", self.seed)
+            .u64("limbs", self.limbs as u64)
+            .str("rng", self.rng.name())
+            .str("modulus", &self.modulus)
+            .u64("attempts", self.attempts)
+            .str("result", self.result.name());
+        let obj = match &self.value {
+            Some(value) => obj.str("value", value),
+            None => obj.raw("value", "null"),
+        };
+        let obj = obj.raw(
+            "disagreements",
+            &json::array(self.disagreements.iter().map(DivergenceHex::to_json)),
+        );
+        match &self.choice_audit {
+            Some(audit) => obj.raw("choice_audit", &audit.to_json()),
+            None => obj,
+        }
+        .finish()
+    }
+}
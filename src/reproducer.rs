@@ -0,0 +1,242 @@
+//! The `reproducer` command: writes a dependency-free Rust file that checks
+//! one `(x, n)` comparison, for upstream bug reports.
+//!
+//! The file inlines crypto-bigint 0.6's `Uint::lt`/`sbb` borrow chain and
+//! subtle 2.6's `Choice` with its volatile-read `black_box`, so it shows
+//! whether the miscompile survives without either crate.
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+
+use crypto_bigint::{Limb, Word};
+
+use crate::build_info::build_info;
+use crate::run::EXIT_USAGE;
+
+/// Options for the `reproducer` command.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReproducerOptions {
+    /// The candidate, as little-endian words.
+    pub candidate: Vec<Word>,
+    /// The modulus, as little-endian words.
+    pub modulus: Vec<Word>,
+    /// Width in limbs; the narrowest that fits both values if unset.
+    pub limbs: Option<usize>,
+    /// Where to write the file; stdout if unset.
+    pub out: Option<PathBuf>,
+}
+
+const TEMPLATE: &str = r#"//! Standalone reproducer for rust-lang/rust#149522.
+//!
+//! Generated by `subtle-repro reproducer` ({build}).
+//!
+//! `ct::lt` mirrors crypto-bigint 0.6's `Uint::lt`, which takes the borrow out
+//! of `Uint::sbb` and turns it into a `Choice` the way subtle 2.6 does: through
+//! a `#[inline(never)]` volatile read. Everything in `mod ct` uses only `core`
+//! and can be moved into a `#![no_std]` crate unchanged.
+//!
+//!     rustc -C opt-level=3 repro.rs && ./repro
+//!
+//! Exits 0 if both checks agree with a plain comparison, 1 otherwise.
+
+mod ct {
+    pub type Word = u64;
+    type WideWord = u128;
+    const WORD_BITS: u32 = Word::BITS;
+
+    pub const LIMBS: usize = {limbs};
+
+    /// subtle's `Choice`.
+    #[derive(Copy, Clone, Debug)]
+    pub struct Choice(u8);
+
+    /// subtle's default optimization barrier.
+    #[inline(never)]
+    fn black_box<T: Copy>(input: T) -> T {
+        unsafe { core::ptr::read_volatile(&input) }
+    }
+
+    impl From<u8> for Choice {
+        #[inline]
+        fn from(input: u8) -> Choice {
+            debug_assert!((input == 0u8) | (input == 1u8));
+            Choice(black_box(input))
+        }
+    }
+
+    impl From<Choice> for bool {
+        #[inline]
+        fn from(source: Choice) -> bool {
+            debug_assert!((source.0 == 0u8) | (source.0 == 1u8));
+            source.0 != 0
+        }
+    }
+
+    /// crypto-bigint's `Limb::sbb`.
+    #[inline(always)]
+    const fn limb_sbb(lhs: Word, rhs: Word, borrow: Word) -> (Word, Word) {
+        let a = lhs as WideWord;
+        let b = rhs as WideWord;
+        let borrow = (borrow >> (WORD_BITS - 1)) as WideWord;
+        let ret = a.wrapping_sub(b + borrow);
+        (ret as Word, (ret >> WORD_BITS) as Word)
+    }
+
+    /// crypto-bigint's `Uint::sbb`.
+    #[inline(always)]
+    const fn sbb(lhs: &[Word; LIMBS], rhs: &[Word; LIMBS], mut borrow: Word) -> ([Word; LIMBS], Word) {
+        let mut limbs = [0; LIMBS];
+        let mut i = 0;
+        while i < LIMBS {
+            let (w, b) = limb_sbb(lhs[i], rhs[i], borrow);
+            limbs[i] = w;
+            borrow = b;
+            i += 1;
+        }
+        (limbs, borrow)
+    }
+
+    /// crypto-bigint's `ConstantTimeLess for Uint`: `Uint::lt` gives a
+    /// `ConstChoice` word mask, whose low bit becomes a `Choice`.
+    #[inline]
+    pub fn lt(lhs: &[Word; LIMBS], rhs: &[Word; LIMBS]) -> Choice {
+        let (_res, borrow) = sbb(lhs, rhs, 0);
+        Choice::from((borrow as u8) & 1)
+    }
+}
+
+use ct::{LIMBS, Word};
+
+const X: [Word; LIMBS] = {x};
+const N: [Word; LIMBS] = {n};
+
+/// Plain most-significant-limb-first comparison.
+fn reference_lt(x: &[Word; LIMBS], n: &[Word; LIMBS]) -> bool {
+    x.iter().rev().cmp(n.iter().rev()).is_lt()
+}
+
+/// The rejection loop of `bad_random_mod`, over a fixed list of candidates.
+#[inline(never)]
+fn first_accepted(candidates: &[[Word; LIMBS]], n: &[Word; LIMBS]) -> Option<usize> {
+    let mut i = 0;
+    loop {
+        if i == candidates.len() {
+            return None;
+        }
+        if ct::lt(&candidates[i], n).into() {
+            return Some(i);
+        }
+        i += 1;
+    }
+}
+
+fn main() {
+    let x = core::hint::black_box(X);
+    let n = core::hint::black_box(N);
+    let expected = reference_lt(&x, &n);
+
+    let lt: bool = ct::lt(&x, &n).into();
+    let accepted = first_accepted(&[x], &n);
+
+    println!("x = {x:#x?}");
+    println!("n = {n:#x?}");
+    println!("reference x < n: {expected}");
+    println!("ct::lt:          {lt}");
+    println!("rejection loop:  {accepted:?}");
+    let ok = lt == expected && accepted.is_some() == expected;
+    std::process::exit(if ok { 0 } else { 1 });
+}
+"#;
+
+fn words_literal(words: &[Word]) -> String {
+    let words: Vec<String> = words
+        .iter()
+        .map(|w| format!("{w:#0width$x}", width = Limb::BYTES * 2 + 2))
+        .collect();
+    format!("[{}]", words.join(", "))
+}
+
+/// Renders the reproducer for `x < n` at `limbs` limbs, or `None` if a value
+/// does not fit.
+pub fn render(x: &[Word], n: &[Word], limbs: usize) -> Option<String> {
+    let pad = |words: &[Word]| {
+        if words.iter().skip(limbs).any(|&w| w != 0) {
+            return None;
+        }
+        let mut padded = words.to_vec();
+        padded.resize(limbs, 0);
+        Some(padded)
+    };
+    let (x, n) = (pad(x)?, pad(n)?);
+    let build = build_info();
+    let mut build_line = String::new();
+    let _ = write!(
+        build_line,
+        "a {} build for {}, opt-level {}",
+        build.rustc_version, build.target, build.opt_level
+    );
+    Some(
+        TEMPLATE
+            .replace("{build}", &build_line)
+            .replace("{limbs}", &limbs.to_string())
+            .replace("{x}", &words_literal(&x))
+            .replace("{n}", &words_literal(&n)),
+    )
+}
+
+/// Writes the reproducer, returning the process exit code.
+pub fn reproducer(opts: &ReproducerOptions) -> i32 {
+    if Limb::BITS != 64 {
+        eprintln!("the reproducer template assumes 64-bit limbs");
+        return EXIT_USAGE;
+    }
+    let used = |words: &[Word]| words.iter().rposition(|&w| w != 0).map_or(1, |i| i + 1);
+    let limbs = opts
+        .limbs
+        .unwrap_or_else(|| used(&opts.candidate).max(used(&opts.modulus)));
+    let Some(source) = render(&opts.candidate, &opts.modulus, limbs) else {
+        eprintln!("values do not fit in {limbs} limbs");
+        return EXIT_USAGE;
+    };
+    match &opts.out {
+        Some(path) => {
+            if let Err(err) = std::fs::write(path, source) {
+                eprintln!("cannot write {}: {err}", path.display());
+                return EXIT_USAGE;
+            }
+        }
+        None => print!("{source}"),
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_pads_to_width() {
+        let source = render(&[1], &[2, 3], 4).unwrap();
+        assert!(source.contains("pub const LIMBS: usize = 4;"));
+        assert!(source.contains(
+            "const X: [Word; LIMBS] = [0x0000000000000001, 0x0000000000000000, \
+             0x0000000000000000, 0x0000000000000000];"
+        ));
+        assert!(source.contains(
+            "const N: [Word; LIMBS] = [0x0000000000000002, 0x0000000000000003, \
+             0x0000000000000000, 0x0000000000000000];"
+        ));
+        assert!(source.contains("Generated by `subtle-repro reproducer` (a rustc "));
+        for placeholder in ["{build}", "{limbs}", "{x}", "{n}"] {
+            assert!(!source.contains(placeholder), "{placeholder}");
+        }
+    }
+
+    #[test]
+    fn render_rejects_values_too_wide() {
+        assert_eq!(render(&[1, 1], &[2], 1), None);
+        assert_eq!(render(&[1], &[0, 0, 2], 2), None);
+        // High zero limbs fit.
+        assert!(render(&[1, 0, 0], &[2, 0], 1).is_some());
+    }
+}
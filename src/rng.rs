@@ -0,0 +1,383 @@
+//! Random number generators the reproduction can be driven by.
+
+use core::fmt;
+use core::str::FromStr;
+use std::sync::Arc;
+
+use rand_core::{RngCore, impls};
+
+use crate::record::Recording;
+
+/// A generator selectable from the command line.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RngKind {
+    /// `ChaCha8Rng`, as in the original reproduction.
+    #[default]
+    ChaCha8,
+    ChaCha12,
+    ChaCha20,
+    /// rand 0.8's `StdRng`, which is `ChaCha12Rng` seeded the same way.
+    Std,
+    /// [`CounterRng`] starting at the seed.
+    Counter,
+    /// [`ScriptedRng`] over these bytes; the seed is ignored.
+    Scripted(Arc<[u8]>),
+    /// [`ReplayRng`](crate::record::ReplayRng) over a recording; the seed is
+    /// ignored.
+    Replay(Arc<Recording>),
+}
+
+impl RngKind {
+    /// The kinds that need nothing but a seed.
+    pub const SEEDABLE: [Self; 5] = [
+        Self::ChaCha8,
+        Self::ChaCha12,
+        Self::ChaCha20,
+        Self::Std,
+        Self::Counter,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::ChaCha8 => "chacha8",
+            Self::ChaCha12 => "chacha12",
+            Self::ChaCha20 => "chacha20",
+            Self::Std => "std",
+            Self::Counter => "counter",
+            Self::Scripted(_) => "scripted",
+            Self::Replay(_) => "replay",
+        }
+    }
+}
+
+impl fmt::Display for RngKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for RngKind {
+    type Err = String;
+
+    /// Parses the name of a seedable kind; scripted and replayed generators
+    /// need their input supplied separately.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::SEEDABLE
+            .into_iter()
+            .find(|kind| kind.name() == s)
+            .ok_or_else(|| format!("unknown rng {s:?}"))
+    }
+}
+
+/// Deterministic generator whose `next_u64` returns consecutive integers.
+///
+/// Useful for telling whether a failure depends on the generator or only on
+/// the values sampled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CounterRng(pub u64);
+
+impl RngCore for CounterRng {
+    fn next_u32(&mut self) -> u32 {
+        self.next_u64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.0;
+        self.0 = self.0.wrapping_add(1);
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        impls::fill_bytes_via_next(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Why a scripted or replayed generator stopped serving its input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputStopped {
+    /// The script ran out after `consumed` of its `len` bytes.
+    Script { consumed: usize, len: usize },
+    /// The recording ran out, or stopped matching what was asked for, after
+    /// `served` calls.
+    Replay { served: usize },
+}
+
+impl fmt::Display for InputStopped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Script { consumed, len } => write!(
+                f,
+                "script ran out after {consumed} of its {len} bytes, before the sampler \
+                 accepted a scripted candidate"
+            ),
+            Self::Replay { served } => write!(
+                f,
+                "replay stopped after {served} calls: the recording ran out or no longer \
+                 matches what this build draws"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for InputStopped {}
+
+/// A generator that may run out of input. Once it has, it serves zeros,
+/// which any modulus accepts, so what was sampled after that means nothing.
+pub trait Stoppable {
+    /// Whether, and how, the input ran out.
+    fn stopped(&self) -> Option<InputStopped>;
+}
+
+impl Stoppable for rand_chacha::ChaCha8Rng {
+    fn stopped(&self) -> Option<InputStopped> {
+        None
+    }
+}
+
+impl Stoppable for rand_chacha::ChaCha12Rng {
+    fn stopped(&self) -> Option<InputStopped> {
+        None
+    }
+}
+
+impl Stoppable for rand_chacha::ChaCha20Rng {
+    fn stopped(&self) -> Option<InputStopped> {
+        None
+    }
+}
+
+impl Stoppable for CounterRng {
+    fn stopped(&self) -> Option<InputStopped> {
+        None
+    }
+}
+
+/// Generator that replays a fixed byte sequence, little-endian for
+/// `next_u32` and `next_u64`.
+///
+/// # Panics
+///
+/// Unless built with [`lenient`](Self::lenient), panics when asked for more
+/// bytes than the script holds.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptedRng {
+    script: Arc<[u8]>,
+    pos: usize,
+    lenient: bool,
+    stopped: bool,
+}
+
+impl ScriptedRng {
+    pub fn new(script: Arc<[u8]>) -> Self {
+        Self {
+            script,
+            pos: 0,
+            lenient: false,
+            stopped: false,
+        }
+    }
+
+    /// Like [`new`](Self::new), but once the script runs out serves zeros
+    /// and reports it through [`Stoppable`] instead of panicking.
+    pub fn lenient(script: Arc<[u8]>) -> Self {
+        Self {
+            lenient: true,
+            ..Self::new(script)
+        }
+    }
+
+    /// Bytes consumed so far.
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    fn take<const N: usize>(&mut self) -> [u8; N] {
+        let mut out = [0; N];
+        self.fill_bytes(&mut out);
+        out
+    }
+}
+
+impl RngCore for ScriptedRng {
+    fn next_u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let end = self.pos + dest.len();
+        let bytes = self.script.get(self.pos..end).filter(|_| !self.stopped);
+        let Some(bytes) = bytes else {
+            if self.lenient {
+                self.stopped = true;
+                dest.fill(0);
+                return;
+            }
+            panic!(
+                "scripted rng exhausted: {} bytes requested at offset {} of {}",
+                dest.len(),
+                self.pos,
+                self.script.len()
+            );
+        };
+        dest.copy_from_slice(bytes);
+        self.pos = end;
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl Stoppable for ScriptedRng {
+    fn stopped(&self) -> Option<InputStopped> {
+        self.stopped.then(|| InputStopped::Script {
+            consumed: self.pos,
+            len: self.script.len(),
+        })
+    }
+}
+
+/// Calls `$body` with `$rng` bound to a fresh generator of the given kind,
+/// monomorphizing the body per generator. Every generator is [`Stoppable`]:
+/// scripts and recordings are served leniently, so a short one stops rather
+/// than panics.
+macro_rules! with_rng {
+    ($kind:expr, $seed:expr, |$rng:ident| $body:expr) => {{
+        use rand_core::SeedableRng as _;
+        match &$kind {
+            $crate::rng::RngKind::ChaCha8 => {
+                let $rng = rand_chacha::ChaCha8Rng::seed_from_u64($seed);
+                $body
+            }
+            $crate::rng::RngKind::ChaCha12 | $crate::rng::RngKind::Std => {
+                let $rng = rand_chacha::ChaCha12Rng::seed_from_u64($seed);
+                $body
+            }
+            $crate::rng::RngKind::ChaCha20 => {
+                let $rng = rand_chacha::ChaCha20Rng::seed_from_u64($seed);
+                $body
+            }
+            $crate::rng::RngKind::Counter => {
+                let $rng = $crate::rng::CounterRng($seed);
+                $body
+            }
+            $crate::rng::RngKind::Scripted(script) => {
+                let $rng = $crate::rng::ScriptedRng::lenient(script.clone());
+                $body
+            }
+            $crate::rng::RngKind::Replay(recording) => {
+                let $rng = $crate::record::ReplayRng::lenient(&recording.calls);
+                $body
+            }
+        }
+    }};
+}
+pub(crate) use with_rng;
+
+#[cfg(test)]
+mod tests {
+    use rand_chacha::{ChaCha8Rng, ChaCha12Rng, ChaCha20Rng};
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    fn first_words(kind: &RngKind, seed: u64) -> [u64; 4] {
+        with_rng!(kind, seed, |rng| {
+            let mut rng = rng;
+            [(); 4].map(|_| rng.next_u64())
+        })
+    }
+
+    fn words(mut rng: impl RngCore) -> [u64; 4] {
+        [(); 4].map(|_| rng.next_u64())
+    }
+
+    #[test]
+    fn std_is_chacha12() {
+        let kind: RngKind = "std".parse().unwrap();
+        assert_eq!(kind, RngKind::Std);
+        assert_eq!(first_words(&kind, 7), words(ChaCha12Rng::seed_from_u64(7)));
+    }
+
+    #[test]
+    fn with_rng_dispatches_by_kind() {
+        let seed = 3;
+        assert_eq!(
+            first_words(&RngKind::ChaCha8, seed),
+            words(ChaCha8Rng::seed_from_u64(seed))
+        );
+        assert_eq!(
+            first_words(&RngKind::ChaCha12, seed),
+            words(ChaCha12Rng::seed_from_u64(seed))
+        );
+        assert_eq!(
+            first_words(&RngKind::ChaCha20, seed),
+            words(ChaCha20Rng::seed_from_u64(seed))
+        );
+        assert_eq!(first_words(&RngKind::Counter, seed), [3, 4, 5, 6]);
+        for kind in RngKind::SEEDABLE {
+            assert_eq!(kind.name().parse::<RngKind>(), Ok(kind));
+        }
+    }
+
+    #[test]
+    fn counter_counts_and_wraps() {
+        let mut rng = CounterRng(u64::MAX);
+        assert_eq!(rng.next_u64(), u64::MAX);
+        assert_eq!(rng.next_u64(), 0);
+        assert_eq!(rng.next_u32(), 1);
+        let mut bytes = [0; 12];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0]);
+    }
+
+    #[test]
+    fn scripted_serves_bytes_in_order() {
+        let script: Arc<[u8]> = (1..=16).collect();
+        let mut rng = ScriptedRng::new(script);
+        assert_eq!(rng.next_u32(), u32::from_le_bytes([1, 2, 3, 4]));
+        let mut bytes = [0; 4];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(bytes, [5, 6, 7, 8]);
+        assert_eq!(
+            rng.next_u64(),
+            u64::from_le_bytes([9, 10, 11, 12, 13, 14, 15, 16])
+        );
+        assert_eq!(rng.position(), 16);
+    }
+
+    #[test]
+    #[should_panic(expected = "scripted rng exhausted: 8 bytes requested at offset 4 of 6")]
+    fn scripted_panics_when_exhausted() {
+        let mut rng = ScriptedRng::new(Arc::from([0; 6]));
+        rng.next_u32();
+        rng.next_u64();
+    }
+
+    #[test]
+    fn lenient_script_stops_and_serves_zeros() {
+        let mut rng = ScriptedRng::lenient(Arc::from([1; 6]));
+        assert_eq!(rng.next_u32(), 0x0101_0101);
+        assert_eq!(rng.stopped(), None);
+        assert_eq!(rng.next_u64(), 0);
+        // Stays stopped, though two bytes are left.
+        assert_eq!(rng.next_u32() as u16, 0);
+        assert_eq!(
+            rng.stopped(),
+            Some(InputStopped::Script {
+                consumed: 4,
+                len: 6
+            })
+        );
+    }
+}
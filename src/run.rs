@@ -0,0 +1,356 @@
+//! The `run` command: one sampling session, as in the original reproduction.
+
+use core::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crypto_bigint::{NonZero, Uint, Word};
+use rand_core::RngCore;
+
+use crate::choice_audit::ChoiceAudit;
+use crate::inline_ct::Variant;
+use crate::oracle::{self, Oracle};
+use crate::record::RecordingRng;
+use crate::report::{Format, RunReport, RunResult};
+use crate::rng::{RngKind, Stoppable, with_rng};
+use crate::shapes::Shape;
+use crate::watchdog::{Context, OnStall, Progress, Watchdog};
+use crate::{SamplingError, hex, sample_observed, try_random_mod, with_limbs};
+
+/// Process exit code for a malformed command line or unusable input.
+pub const EXIT_USAGE: i32 = 2;
+/// Process exit code used when `ct_lt` disagrees with the oracle.
+pub const EXIT_DIVERGED: i32 = 4;
+/// Process exit code used when the attempt budget runs out.
+pub const EXIT_EXHAUSTED: i32 = 5;
+/// Process exit code used when a script or a replayed recording runs out
+/// before the sampler accepts a candidate drawn from it.
+pub const EXIT_INPUT_STOPPED: i32 = 8;
+
+/// Comfortably inside CI's 30 second `alarm`.
+pub const DEFAULT_WATCHDOG: Duration = Duration::from_secs(20);
+
+/// Where the modulus comes from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModulusSpec {
+    /// `0 - special`, where `special` is the generator's first `next_u64`.
+    #[default]
+    Special,
+    /// An explicit value, as little-endian words.
+    Hex(Vec<Word>),
+}
+
+/// Why a [`ModulusSpec`] does not give a usable modulus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModulusError {
+    TooWide { limbs: usize },
+    Zero,
+}
+
+impl fmt::Display for ModulusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooWide { limbs } => write!(f, "modulus does not fit in {limbs} limbs"),
+            Self::Zero => f.write_str("modulus must be nonzero"),
+        }
+    }
+}
+
+impl std::error::Error for ModulusError {}
+
+impl ModulusSpec {
+    /// Produces the modulus, drawing `special` from `rng` if needed.
+    pub fn resolve<const L: usize, R>(&self, rng: &mut R) -> Result<NonZero<Uint<L>>, ModulusError>
+    where
+        R: RngCore,
+    {
+        let n = match self {
+            Self::Special => special_modulus(rng.next_u64()),
+            Self::Hex(words) => hex::from_words(words).ok_or(ModulusError::TooWide { limbs: L })?,
+        };
+        Option::from(NonZero::new(n)).ok_or(ModulusError::Zero)
+    }
+}
+
+/// `0 - special`, the modulus of the original reproduction.
+pub fn special_modulus<const L: usize>(special: u64) -> Uint<L> {
+    Uint::<L>::ZERO.wrapping_sub(&Uint::from(special))
+}
+
+/// Options for the `run` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RunOptions {
+    pub seed: u64,
+    pub limbs: usize,
+    pub modulus: ModulusSpec,
+    pub max_attempts: Option<u64>,
+    pub rng: RngKind,
+    pub oracle: bool,
+    pub watchdog: Option<Duration>,
+    pub format: Format,
+    /// Record the generator's stream here.
+    pub record: Option<PathBuf>,
+    /// Which comparison the sampler uses.
+    pub barrier: Variant,
+    /// Samples with this loop shape instead, unbounded.
+    pub shape: Option<Shape>,
+    /// Report every candidate to the watchdog, at the cost of running an
+    /// instrumented loop instead of the original.
+    pub observe: bool,
+    /// Check that every `Choice` from `ct_lt` is a canonical 0 or 1.
+    pub audit_choices: bool,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            seed: 1,
+            limbs: 5,
+            modulus: ModulusSpec::Special,
+            max_attempts: None,
+            rng: RngKind::ChaCha8,
+            oracle: false,
+            watchdog: Some(DEFAULT_WATCHDOG),
+            format: Format::Text,
+            record: None,
+            barrier: Variant::Crates,
+            shape: None,
+            observe: false,
+            audit_choices: false,
+        }
+    }
+}
+
+/// Runs one sampling session, returning the process exit code.
+pub fn run(opts: &RunOptions) -> i32 {
+    with_rng!(opts.rng, opts.seed, |rng| record_and_run(rng, opts))
+}
+
+/// Runs with `rng`, recording it if asked.
+fn record_and_run<R>(mut rng: R, opts: &RunOptions) -> i32
+where
+    R: RngCore + Stoppable,
+{
+    let Some(path) = &opts.record else {
+        return run_limbs(&mut rng, opts);
+    };
+    match RecordingRng::create(rng, path) {
+        Ok(mut rng) => {
+            let code = run_limbs(&mut rng, opts);
+            // A write error stops the recording but not the run, so it only
+            // surfaces here.
+            match rng.finish() {
+                Ok(_) => code,
+                Err(err) => {
+                    eprintln!("recording to {} failed: {err}", path.display());
+                    if code == 0 { EXIT_USAGE } else { code }
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("cannot record to {}: {err}", path.display());
+            EXIT_USAGE
+        }
+    }
+}
+
+fn run_limbs<R>(rng: &mut R, opts: &RunOptions) -> i32
+where
+    R: RngCore + Stoppable,
+{
+    with_limbs!(opts.limbs, |L| run_width::<L, _>(rng, opts), _ => {
+        eprintln!("unsupported limb count {}", opts.limbs);
+        EXIT_USAGE
+    })
+}
+
+fn run_width<const L: usize, R>(rng: &mut R, opts: &RunOptions) -> i32
+where
+    R: RngCore + Stoppable,
+{
+    let n = match opts.modulus.resolve::<L, _>(rng) {
+        Ok(n) => n,
+        Err(_) if let Some(stopped) = rng.stopped() => {
+            eprintln!("{stopped}");
+            return EXIT_INPUT_STOPPED;
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut report = RunReport {
+        seed: opts.seed,
+        limbs: opts.limbs,
+        rng: opts.rng.clone(),
+        modulus: hex::encode(&*n),
+        attempts: 0,
+        result: RunResult::Ok,
+        value: None,
+        disagreements: Vec::new(),
+        choice_audit: None,
+    };
+
+    let progress = Arc::new(Progress::new(&n));
+    let on_stall = (opts.format != Format::Text).then(|| {
+        let mut report = report.clone();
+        Box::new(move |progress: &Progress<L>| {
+            report.result = RunResult::Stalled;
+            report.attempts = progress.attempts();
+            println!("{}", report.to_json());
+        }) as OnStall<L>
+    });
+    let context = Context {
+        seed: opts.seed,
+        modulus: *n,
+        on_stall,
+    };
+    let watchdog = opts
+        .watchdog
+        .map(|deadline| Watchdog::spawn(deadline, context, progress.clone()));
+    let max_attempts = opts.max_attempts.unwrap_or(u64::MAX);
+    let mut audit = ChoiceAudit::<L>::default();
+    let result = if let Some(shape) = opts.shape {
+        // Shapes run as they are, reporting no progress; the oracle can only
+        // check the candidate they accept, whose attempt is not counted.
+        let x = shape.random_mod(rng, &n);
+        match opts.oracle.then(|| oracle::check_bool(0, &x, &n, true)) {
+            Some(Some(divergence)) => Err(SamplingError::Diverged(divergence)),
+            _ => Ok(x),
+        }
+    } else if opts.barrier != Variant::Crates && (opts.oracle || opts.max_attempts.is_some()) {
+        // The inlined variants check every decision with the oracle when
+        // bounded; they report no per-candidate progress.
+        opts.barrier.try_random_mod(rng, &n, max_attempts)
+    } else if opts.audit_choices {
+        let oracle = opts.oracle.then_some(Oracle);
+        sample_observed(rng, &n, max_attempts, (&*progress, (&mut audit, oracle)))
+    } else if opts.oracle {
+        sample_observed(rng, &n, max_attempts, (&*progress, Oracle))
+    } else if opts.observe {
+        sample_observed(rng, &n, max_attempts, &*progress)
+    } else {
+        // The loop under test as the original wrote it, with the chosen
+        // comparison; the watchdog only sees the generator calls it makes.
+        let mut rng = progress.count_draws(&mut *rng);
+        match opts.max_attempts {
+            Some(max_attempts) => try_random_mod(&mut rng, &n, max_attempts),
+            None => Ok(opts.barrier.random_mod(&mut rng, &n)),
+        }
+    };
+    drop(watchdog);
+
+    report.attempts = progress.attempts();
+    if opts.audit_choices {
+        report.choice_audit = Some((&audit).into());
+    }
+    // Once the input stops the generator serves zeros, which any modulus
+    // accepts, so whatever was sampled after that means nothing.
+    let stopped = rng.stopped();
+    let code = match &result {
+        _ if stopped.is_some() => {
+            report.result = RunResult::InputStopped;
+            EXIT_INPUT_STOPPED
+        }
+        Ok(a) => {
+            report.value = Some(hex::encode(a));
+            if audit.anomalies == 0 {
+                0
+            } else {
+                report.result = RunResult::BadChoice;
+                EXIT_DIVERGED
+            }
+        }
+        Err(SamplingError::Diverged(d)) => {
+            report.result = RunResult::Diverged;
+            report.disagreements.push(d.into());
+            EXIT_DIVERGED
+        }
+        Err(SamplingError::Exhausted { .. }) => {
+            report.result = RunResult::Exhausted;
+            EXIT_EXHAUSTED
+        }
+    };
+    if opts.audit_choices && opts.format == Format::Text {
+        print_audit(&audit);
+    }
+    match (opts.format, result) {
+        (Format::Text, _) if let Some(stopped) = stopped => eprintln!("{stopped}"),
+        (Format::Text, Ok(a)) => println!("Hello, {a:?}"),
+        (Format::Text, Err(err)) => eprintln!("{err}"),
+        (Format::Json | Format::Ndjson, _) => println!("{}", report.to_json()),
+    }
+    code
+}
+
+fn print_audit<const L: usize>(audit: &ChoiceAudit<L>) {
+    eprintln!(
+        "audited {} ct_lt results: {} zero, {} one, {} anomalous",
+        audit.comparisons, audit.zeros, audit.ones, audit.anomalies
+    );
+    for a in &audit.recorded {
+        eprintln!(
+            "  attempt {}: unwrap_u8 {}, bool::from {}, x = {}, n = {}",
+            a.attempt,
+            a.byte,
+            a.as_bool,
+            hex::encode(&a.candidate),
+            hex::encode(&a.modulus)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::record::Recording;
+
+    /// A one-limb run on `rng`: `special` comes first, then the candidates.
+    fn opts(rng: RngKind) -> RunOptions {
+        RunOptions {
+            limbs: 1,
+            rng,
+            watchdog: None,
+            format: Format::Json,
+            ..RunOptions::default()
+        }
+    }
+
+    fn script(words: &[u64]) -> RngKind {
+        RngKind::Scripted(words.iter().flat_map(|w| w.to_le_bytes()).collect())
+    }
+
+    #[test]
+    fn short_script_stops_cleanly() {
+        // A modulus of 2^64 - 1, then 8 of the 16 bytes of a candidate.
+        let mut bytes = 1u64.to_le_bytes().to_vec();
+        bytes.extend([5; 8]);
+        let rng = RngKind::Scripted(Arc::from(&bytes[..12]));
+        assert_eq!(run(&opts(rng)), EXIT_INPUT_STOPPED);
+        // Not even a modulus.
+        assert_eq!(run(&opts(script(&[]))), EXIT_INPUT_STOPPED);
+        // Two rejected candidates, then nothing.
+        let mut rng = opts(script(&[1000, u64::MAX, u64::MAX]));
+        assert_eq!(run(&rng), EXIT_INPUT_STOPPED);
+        rng.oracle = true;
+        assert_eq!(run(&rng), EXIT_INPUT_STOPPED);
+    }
+
+    #[test]
+    fn whole_script_runs() {
+        assert_eq!(run(&opts(script(&[1000, u64::MAX, u64::MAX, 5]))), 0);
+    }
+
+    #[test]
+    fn short_recording_stops_cleanly() {
+        let recording = Recording::parse("u64 00000000000003e8\n").unwrap();
+        assert_eq!(
+            run(&opts(RngKind::Replay(recording.into()))),
+            EXIT_INPUT_STOPPED
+        );
+    }
+}
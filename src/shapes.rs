@@ -0,0 +1,387 @@
+//! Rejection samplers with the meaning of [`random_mod`](crate::random_mod)
+//! but a different shape, and the `shapes` command, to find which lowering
+//! of the `loop { ... if ... return }` triggers the hang.
+//!
+//! Every shape compares with crypto-bigint's `ct_lt` and subtle's `Choice`,
+//! as the original does, and none has an attempt budget, so a miscompiled
+//! comparison hangs them the same way. The command builds each profile as
+//! by `matrix` and runs every shape in it with `run --shape` under the
+//! watchdog.
+
+use core::fmt;
+use core::str::FromStr;
+
+use crypto_bigint::{NonZero, RandomBits, Uint};
+use rand_core::RngCore;
+use subtle::ConstantTimeLess;
+
+use crate::build_info::build_info;
+use crate::json;
+use crate::matrix::{self, MatrixOptions, Outcome, Profile};
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+
+/// How the sampling loop is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shape {
+    /// The original: `loop` and `if ct_lt.into() { return }`.
+    Loop,
+    /// `while !bool::from(ct_lt) { ... }`.
+    While,
+    /// `repeat_with(..).find(..)`.
+    Find,
+    /// A tail call per rejected candidate.
+    Recursion,
+    /// Two candidates per iteration.
+    Unrolled,
+    /// The comparison in an `#[inline(never)]` function.
+    InlineNever,
+    /// The comparison in an `#[inline(always)]` function.
+    InlineAlways,
+    /// `ct_lt.unwrap_u8() == 1` instead of `bool::from`.
+    UnwrapU8,
+}
+
+impl Shape {
+    pub const ALL: [Self; 8] = [
+        Self::Loop,
+        Self::While,
+        Self::Find,
+        Self::Recursion,
+        Self::Unrolled,
+        Self::InlineNever,
+        Self::InlineAlways,
+        Self::UnwrapU8,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Loop => "loop",
+            Self::While => "while",
+            Self::Find => "find",
+            Self::Recursion => "recursion",
+            Self::Unrolled => "unrolled",
+            Self::InlineNever => "inline-never",
+            Self::InlineAlways => "inline-always",
+            Self::UnwrapU8 => "unwrap-u8",
+        }
+    }
+
+    /// Samples below `n` with this shape.
+    pub fn random_mod<const L: usize, R: RngCore>(
+        self,
+        rng: &mut R,
+        n: &NonZero<Uint<L>>,
+    ) -> Uint<L> {
+        match self {
+            Self::Loop => crate::random_mod(rng, n),
+            Self::While => while_random_mod(rng, n),
+            Self::Find => find_random_mod(rng, n),
+            Self::Recursion => {
+                let n: &Uint<L> = n;
+                recursive_random_mod(rng, n, n.bits_vartime())
+            }
+            Self::Unrolled => unrolled_random_mod(rng, n),
+            Self::InlineNever => inline_never_random_mod(rng, n),
+            Self::InlineAlways => inline_always_random_mod(rng, n),
+            Self::UnwrapU8 => unwrap_u8_random_mod(rng, n),
+        }
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|shape| shape.name() == s)
+            .ok_or_else(|| format!("unknown shape {s:?}"))
+    }
+}
+
+/// The [`Shape::While`] sampler.
+pub fn while_random_mod<const L: usize, R: RngCore>(rng: &mut R, n: &NonZero<Uint<L>>) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    let mut x = Uint::random_bits(rng, n_bits);
+    while !bool::from(x.ct_lt(n)) {
+        x = Uint::random_bits(rng, n_bits);
+    }
+    x
+}
+
+/// The [`Shape::Find`] sampler.
+pub fn find_random_mod<const L: usize, R: RngCore>(rng: &mut R, n: &NonZero<Uint<L>>) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    core::iter::repeat_with(|| Uint::random_bits(rng, n_bits))
+        .find(|x| x.ct_lt(n).into())
+        .unwrap_or_else(|| unreachable!("repeat_with never ends"))
+}
+
+/// The [`Shape::Recursion`] sampler.
+///
+/// Optimized, the tail call becomes a loop; unoptimized, every rejected
+/// candidate takes a stack frame, which is fine while `ct_lt` is right.
+pub fn recursive_random_mod<const L: usize, R: RngCore>(
+    rng: &mut R,
+    n: &Uint<L>,
+    n_bits: u32,
+) -> Uint<L> {
+    let x = Uint::random_bits(rng, n_bits);
+    if x.ct_lt(n).into() {
+        x
+    } else {
+        recursive_random_mod(rng, n, n_bits)
+    }
+}
+
+/// The [`Shape::Unrolled`] sampler.
+pub fn unrolled_random_mod<const L: usize, R: RngCore>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if x.ct_lt(n).into() {
+            return x;
+        }
+        let y = Uint::random_bits(rng, n_bits);
+        if y.ct_lt(n).into() {
+            return y;
+        }
+    }
+}
+
+#[inline(never)]
+fn accepts_never<const L: usize>(x: &Uint<L>, n: &Uint<L>) -> bool {
+    x.ct_lt(n).into()
+}
+
+/// The [`Shape::InlineNever`] sampler.
+pub fn inline_never_random_mod<const L: usize, R: RngCore>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if accepts_never(&x, n) {
+            return x;
+        }
+    }
+}
+
+#[inline(always)]
+fn accepts_always<const L: usize>(x: &Uint<L>, n: &Uint<L>) -> bool {
+    x.ct_lt(n).into()
+}
+
+/// The [`Shape::InlineAlways`] sampler.
+pub fn inline_always_random_mod<const L: usize, R: RngCore>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if accepts_always(&x, n) {
+            return x;
+        }
+    }
+}
+
+/// The [`Shape::UnwrapU8`] sampler.
+pub fn unwrap_u8_random_mod<const L: usize, R: RngCore>(
+    rng: &mut R,
+    n: &NonZero<Uint<L>>,
+) -> Uint<L> {
+    let n: &Uint<L> = n;
+    let n_bits = n.bits_vartime();
+    loop {
+        let x = Uint::random_bits(rng, n_bits);
+        if x.ct_lt(n).unwrap_u8() == 1 {
+            return x;
+        }
+    }
+}
+
+/// Options for the `shapes` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShapesOptions {
+    /// Toolchains, profiles and run arguments, as for `matrix`.
+    pub build: MatrixOptions,
+    pub shapes: Vec<Shape>,
+}
+
+impl Default for ShapesOptions {
+    fn default() -> Self {
+        Self {
+            build: MatrixOptions::default(),
+            shapes: Shape::ALL.into(),
+        }
+    }
+}
+
+/// Every shape's outcome under one configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShapeRow {
+    pub toolchain: String,
+    pub profile: Profile,
+    pub outcomes: Vec<(Shape, Outcome)>,
+}
+
+impl ShapeRow {
+    /// The shapes that hung, diverged or failed.
+    pub fn failed(&self) -> Vec<Shape> {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| *outcome != Outcome::Pass)
+            .map(|(shape, _)| *shape)
+            .collect()
+    }
+}
+
+/// Builds one configuration and runs every shape in it.
+fn shape_row(opts: &ShapesOptions, toolchain: Option<&str>, profile: &Profile) -> ShapeRow {
+    let build = &opts.build;
+    let name = toolchain.unwrap_or("default");
+    eprintln!("building {name} {}", profile.label());
+    let binary = matrix::build(
+        toolchain,
+        None,
+        profile,
+        &build.source,
+        &build.target_dir(name, profile),
+        build.offline,
+        build.build_timeout,
+    );
+    if let Err(log) = &binary {
+        eprintln!("{log}");
+    }
+    let outcomes = opts
+        .shapes
+        .iter()
+        .map(|&shape| {
+            let outcome = match &binary {
+                Ok(binary) => {
+                    let mut run_args = vec!["--shape".to_owned(), shape.name().to_owned()];
+                    run_args.extend(build.run_args.iter().cloned());
+                    matrix::run_binary(binary, &run_args, build.timeout)
+                }
+                Err(_) => Outcome::BuildFailed,
+            };
+            eprintln!("  {shape}: {}", outcome.name());
+            (shape, outcome)
+        })
+        .collect();
+    ShapeRow {
+        toolchain: name.to_owned(),
+        profile: profile.clone(),
+        outcomes,
+    }
+}
+
+/// Configurations down the side, shapes across the top.
+fn print_rows(shapes: &[Shape], rows: &[ShapeRow]) {
+    let label = |row: &ShapeRow| format!("{} {}", row.toolchain, row.profile.label());
+    let width = rows.iter().map(|r| label(r).len()).max().unwrap_or(0);
+    let mut header = format!("{:<width$}", "configuration");
+    for shape in shapes {
+        header.push_str(&format!("  {shape:<12}"));
+    }
+    println!("{}", header.trim_end());
+    for row in rows {
+        let mut line = format!("{:<width$}", label(row));
+        for (shape, outcome) in &row.outcomes {
+            let w = shape.name().len().max(12);
+            line.push_str(&format!("  {:<w$}", outcome.name()));
+        }
+        println!("{}", line.trim_end());
+    }
+}
+
+fn row_object(row: &ShapeRow) -> String {
+    let outcomes = row
+        .outcomes
+        .iter()
+        .fold(json::Object::new(), |obj, (shape, outcome)| {
+            obj.str(shape.name(), outcome.name())
+        });
+    json::Object::new()
+        .str("toolchain", &row.toolchain)
+        .str("profile", &row.profile.label())
+        .raw("shapes", &outcomes.finish())
+        .finish()
+}
+
+/// Builds every configuration, runs every shape in each and prints which
+/// miscompile, returning the process exit code.
+pub fn shapes(opts: &ShapesOptions) -> i32 {
+    let profiles = opts.build.profiles();
+    if profiles.is_empty() || opts.shapes.is_empty() {
+        eprintln!("shapes has no profiles or no shapes");
+        return EXIT_USAGE;
+    }
+    let mut rows = Vec::new();
+    for toolchain in matrix::toolchains(&opts.build.toolchains) {
+        for profile in &profiles {
+            rows.push(shape_row(opts, toolchain.as_deref(), profile));
+        }
+    }
+
+    match opts.build.format {
+        Format::Text => print_rows(&opts.shapes, &rows),
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .raw("rows", &json::array(rows.iter().map(row_object)))
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => rows.iter().for_each(|row| println!("{}", row_object(row))),
+    }
+
+    if rows.iter().all(|row| row.failed().is_empty()) {
+        0
+    } else {
+        EXIT_DIVERGED
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::boundary::random_bits_script;
+    use crate::rng::ScriptedRng;
+
+    #[test]
+    fn every_shape_samples_the_same() {
+        let n = NonZero::new(Uint::<2>::from_words([5, 9])).unwrap();
+        let n_bits = n.bits_vartime();
+        // Two rejected candidates, the accepted one, and one more for the
+        // unrolled shape, which may draw in pairs.
+        let candidates = [*n, n.wrapping_add(&Uint::ONE), Uint::from_u64(3), Uint::ONE];
+        let script: Vec<u8> = candidates
+            .iter()
+            .flat_map(|x| random_bits_script(x, n_bits))
+            .collect();
+        let expected = crate::random_mod(&mut ScriptedRng::new(script.clone().into()), &n);
+        assert_eq!(expected, Uint::from_u64(3));
+        for shape in Shape::ALL {
+            let mut rng = ScriptedRng::new(script.clone().into());
+            assert_eq!(shape.random_mod(&mut rng, &n), expected, "{shape}");
+        }
+    }
+}
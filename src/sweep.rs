@@ -0,0 +1,240 @@
+//! The `sweep` command: the original scenario over a range of seeds.
+
+use core::fmt;
+use core::ops::Range;
+
+use crypto_bigint::{NonZero, Uint};
+use rand_core::RngCore;
+use subtle::Choice;
+
+use crate::build_info::build_info;
+use crate::oracle::{DivergenceHex, Oracle};
+use crate::report::Format;
+use crate::rng::{InputStopped, RngKind, Stoppable, with_rng};
+use crate::run::{EXIT_DIVERGED, EXIT_EXHAUSTED, EXIT_INPUT_STOPPED, EXIT_USAGE, special_modulus};
+use crate::{Observer, SamplingError, json, sample_observed, with_limbs};
+
+/// Options for the `sweep` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SweepOptions {
+    pub seeds: Range<u64>,
+    pub limbs: usize,
+    pub rng: RngKind,
+    pub max_attempts: u64,
+    pub format: Format,
+}
+
+impl Default for SweepOptions {
+    fn default() -> Self {
+        Self {
+            seeds: 0..64,
+            limbs: 5,
+            rng: RngKind::ChaCha8,
+            max_attempts: 10_000,
+            format: Format::Text,
+        }
+    }
+}
+
+/// What happened to one seed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Pass {
+        attempts: u64,
+    },
+    Diverged {
+        divergence: DivergenceHex,
+    },
+    Exhausted {
+        attempts: u64,
+    },
+    /// `special` was zero, so the modulus was too; nothing was sampled.
+    Skipped,
+    /// The script or recording ran out before a candidate from it was
+    /// accepted.
+    Stopped {
+        attempts: u64,
+        stopped: InputStopped,
+    },
+}
+
+impl Outcome {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Pass { .. } => "pass",
+            Self::Diverged { .. } => "diverged",
+            Self::Exhausted { .. } => "exhausted",
+            Self::Skipped => "skipped",
+            Self::Stopped { .. } => "stopped",
+        }
+    }
+
+    fn attempts(&self) -> u64 {
+        match self {
+            Self::Pass { attempts }
+            | Self::Exhausted { attempts }
+            | Self::Stopped { attempts, .. } => *attempts,
+            Self::Diverged { divergence } => divergence.attempt + 1,
+            Self::Skipped => 0,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Counts candidates drawn.
+#[derive(Debug, Default)]
+struct Count(u64);
+
+impl<const L: usize> Observer<L> for Count {
+    fn observe(
+        &mut self,
+        _: u64,
+        _: &Uint<L>,
+        _: &Uint<L>,
+        _: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+/// Runs the original scenario for one seed: `special` is the generator's
+/// first draw and the modulus is `0 - special`.
+pub fn sweep_seed(seed: u64, limbs: usize, rng: &RngKind, max_attempts: u64) -> Option<Outcome> {
+    with_rng!(*rng, seed, |rng| {
+        with_limbs!(limbs, |L| Some(seed_outcome::<L, _>(rng, max_attempts)), _ => None)
+    })
+}
+
+fn seed_outcome<const L: usize, R>(mut rng: R, max_attempts: u64) -> Outcome
+where
+    R: RngCore + Stoppable,
+{
+    let n: Uint<L> = special_modulus(rng.next_u64());
+    let mut count = Count::default();
+    let stopped = |rng: &R, attempts| {
+        let stopped = rng.stopped()?;
+        Some(Outcome::Stopped { attempts, stopped })
+    };
+    let Some(n) = Option::<NonZero<_>>::from(NonZero::new(n)) else {
+        return stopped(&rng, 0).unwrap_or(Outcome::Skipped);
+    };
+    let result = sample_observed(&mut rng, &n, max_attempts, (&mut count, Oracle));
+    if let Some(outcome) = stopped(&rng, count.0) {
+        return outcome;
+    }
+    match result {
+        Ok(_) => Outcome::Pass { attempts: count.0 },
+        Err(SamplingError::Diverged(d)) => Outcome::Diverged {
+            divergence: (&d).into(),
+        },
+        Err(SamplingError::Exhausted { attempts, .. }) => Outcome::Exhausted { attempts },
+    }
+}
+
+/// Runs the sweep and prints its report, returning the process exit code.
+pub fn sweep(opts: &SweepOptions) -> i32 {
+    let mut results = Vec::new();
+    for seed in opts.seeds.clone() {
+        let Some(outcome) = sweep_seed(seed, opts.limbs, &opts.rng, opts.max_attempts) else {
+            eprintln!("unsupported limb count {}", opts.limbs);
+            return EXIT_USAGE;
+        };
+        results.push((seed, outcome));
+    }
+
+    match opts.format {
+        Format::Text => print_table(&results),
+        Format::Json => println!("{}", to_json(opts, &results)),
+        Format::Ndjson => {
+            for (seed, outcome) in &results {
+                let line = outcome_object(*seed, outcome)
+                    .raw("build", &build_info().to_json())
+                    .u64("limbs", opts.limbs as u64)
+                    .str("rng", opts.rng.name())
+                    .u64("max_attempts", opts.max_attempts)
+                    .finish();
+                println!("{line}");
+            }
+        }
+    }
+
+    if results
+        .iter()
+        .any(|(_, o)| matches!(o, Outcome::Diverged { .. }))
+    {
+        EXIT_DIVERGED
+    } else if results
+        .iter()
+        .any(|(_, o)| matches!(o, Outcome::Exhausted { .. }))
+    {
+        EXIT_EXHAUSTED
+    } else if results
+        .iter()
+        .any(|(_, o)| matches!(o, Outcome::Stopped { .. }))
+    {
+        EXIT_INPUT_STOPPED
+    } else {
+        0
+    }
+}
+
+fn print_table(results: &[(u64, Outcome)]) {
+    println!("{:>20}  {:<9}  {:>10}", "seed", "outcome", "attempts");
+    for (seed, outcome) in results {
+        println!("{seed:>20}  {outcome:<9}  {:>10}", outcome.attempts());
+        match outcome {
+            Outcome::Diverged { divergence } => {
+                println!("{:>20}  x = {}", "", divergence.candidate);
+                println!("{:>20}  n = {}", "", divergence.modulus);
+            }
+            Outcome::Stopped { stopped, .. } => println!("{:>20}  {stopped}", ""),
+            _ => {}
+        }
+    }
+    let count = |name| results.iter().filter(|(_, o)| o.name() == name).count();
+    println!(
+        "{} seeds: {} pass, {} diverged, {} exhausted, {} skipped, {} stopped",
+        results.len(),
+        count("pass"),
+        count("diverged"),
+        count("exhausted"),
+        count("skipped"),
+        count("stopped"),
+    );
+}
+
+/// Starts a JSON object describing one seed's outcome.
+pub fn outcome_object(seed: u64, outcome: &Outcome) -> json::Object {
+    let obj = json::Object::new()
+        .u64("seed", seed)
+        .str("outcome", outcome.name())
+        .u64("attempts", outcome.attempts());
+    match outcome {
+        Outcome::Diverged { divergence } => obj.raw("divergence", &divergence.to_json()),
+        Outcome::Stopped { stopped, .. } => obj.str("stopped", &stopped.to_string()),
+        _ => obj,
+    }
+}
+
+fn to_json(opts: &SweepOptions, results: &[(u64, Outcome)]) -> String {
+    json::Object::new()
+        .raw("build", &build_info().to_json())
+        .u64("limbs", opts.limbs as u64)
+        .str("rng", opts.rng.name())
+        .u64("max_attempts", opts.max_attempts)
+        .raw(
+            "seeds",
+            &json::array(
+                results
+                    .iter()
+                    .map(|(seed, o)| outcome_object(*seed, o).finish()),
+            ),
+        )
+        .finish()
+}
@@ -0,0 +1,205 @@
+//! Locally installed Rust toolchains, and cargo invocations against them.
+
+use core::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Names of the toolchains `rustup toolchain list` reports, default first
+/// as rustup prints them.
+pub fn installed() -> io::Result<Vec<String>> {
+    let out = Command::new("rustup")
+        .args(["toolchain", "list"])
+        .output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&out.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout)
+        .lines()
+        // "stable-x86_64-unknown-linux-gnu (active, default)"
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|name| *name != "no")
+        .map(str::to_owned)
+        .collect())
+}
+
+/// `cargo` from `toolchain`, or from the environment when it is `None`.
+///
+/// Goes through `rustup run` rather than `cargo +toolchain`, so it works
+/// even where `cargo` on the path is not the rustup proxy.
+pub fn cargo(toolchain: Option<&str>) -> Command {
+    match toolchain {
+        Some(toolchain) => {
+            let mut command = Command::new("rustup");
+            command.args(["run", toolchain, "cargo"]);
+            command
+        }
+        None => Command::new("cargo"),
+    }
+}
+
+/// Release channel, in the order a version number passes through them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Channel {
+    Nightly,
+    Beta,
+    Stable,
+}
+
+/// A parsed `rustc --version`, ordered by release.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    pub channel: Channel,
+    /// Commit date, when rustc reports one.
+    pub date: Option<String>,
+}
+
+impl Version {
+    /// Parses e.g. `rustc 1.92.0-nightly (0be8e16 2025-10-01)`.
+    pub fn parse(version: &str) -> Option<Self> {
+        let mut words = version.split_whitespace();
+        if words.next() != Some("rustc") {
+            return None;
+        }
+        let number = words.next()?;
+        let (number, pre) = number.split_once('-').unwrap_or((number, ""));
+        let channel = match pre {
+            "" => Channel::Stable,
+            _ if pre.starts_with("beta") => Channel::Beta,
+            _ => Channel::Nightly,
+        };
+        let mut parts = number.split('.').map(str::parse::<u32>);
+        let (major, minor, patch) = (
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+            parts.next()?.ok()?,
+        );
+        let date = words.nth(1).map(|d| d.trim_end_matches(')').to_owned());
+        Some(Self {
+            major,
+            minor,
+            patch,
+            channel,
+            date,
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        match self.channel {
+            Channel::Nightly => f.write_str("-nightly")?,
+            Channel::Beta => f.write_str("-beta")?,
+            Channel::Stable => {}
+        }
+        match &self.date {
+            Some(date) => write!(f, " ({date})"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The version of `toolchain`'s rustc.
+pub fn version(toolchain: &str) -> io::Result<Version> {
+    let out = Command::new("rustup")
+        .args(["run", toolchain, "rustc", "--version"])
+        .output()?;
+    let text = String::from_utf8_lossy(&out.stdout);
+    Version::parse(&text).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unrecognized rustc version {:?}", text.trim()),
+        )
+    })
+}
+
+/// `rustc` from `toolchain`, or from the environment when it is `None`.
+fn rustc(toolchain: Option<&str>) -> Command {
+    match toolchain {
+        Some(toolchain) => {
+            let mut command = Command::new("rustup");
+            command.args(["run", toolchain, "rustc"]);
+            command
+        }
+        None => Command::new("rustc"),
+    }
+}
+
+/// The trimmed stdout of `rustc args`.
+fn rustc_output(mut command: Command, args: &[&str]) -> io::Result<String> {
+    let out = command.args(args).output()?;
+    if !out.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&out.stderr).trim().to_owned(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&out.stdout).trim().to_owned())
+}
+
+/// The sysroot of `toolchain`, or of rustc from the environment when it is
+/// `None`.
+pub fn sysroot(toolchain: Option<&str>) -> io::Result<PathBuf> {
+    rustc_output(rustc(toolchain), &["--print", "sysroot"]).map(PathBuf::from)
+}
+
+/// Whether `toolchain`, or rustc from the environment when it is `None`,
+/// has the standard library for `target`.
+pub fn has_target(toolchain: Option<&str>, target: &str) -> io::Result<bool> {
+    Ok(sysroot(toolchain)?
+        .join("lib")
+        .join("rustlib")
+        .join(target)
+        .join("lib")
+        .is_dir())
+}
+
+/// A field of `rustc -vV`, such as `host` or `LLVM version`.
+pub fn verbose_field(toolchain: Option<&str>, key: &str) -> io::Result<String> {
+    let text = rustc_output(rustc(toolchain), &["-vV"])?;
+    text.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(": "))
+        .map(str::to_owned)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("rustc -vV has no {key:?}"),
+            )
+        })
+}
+
+/// The LLVM data layout of `target`, which rustc knows even when the
+/// target's standard library is not installed.
+///
+/// Printing a target spec is unstable, so this sets `RUSTC_BOOTSTRAP`.
+pub fn data_layout(toolchain: Option<&str>, target: &str) -> io::Result<String> {
+    let mut command = rustc(toolchain);
+    command.env("RUSTC_BOOTSTRAP", "1");
+    let spec = rustc_output(
+        command,
+        &[
+            "-Z",
+            "unstable-options",
+            "--print",
+            "target-spec-json",
+            "--target",
+            target,
+        ],
+    )?;
+    spec.lines()
+        .find_map(|line| {
+            let value = line.trim().strip_prefix("\"data-layout\": \"")?;
+            Some(value[..value.find('"')?].to_owned())
+        })
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("no data layout in the spec of {target}"),
+            )
+        })
+}
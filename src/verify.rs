@@ -0,0 +1,307 @@
+//! The `verify-ct` command: model-checks subtle's comparison traits on small
+//! `Uint`s against native integer comparisons.
+
+use core::fmt::LowerHex;
+
+use crypto_bigint::{Limb, Uint, Word};
+use rand_chacha::ChaCha8Rng;
+use rand_core::{RngCore, SeedableRng};
+use subtle::{ConstantTimeEq, ConstantTimeGreater, ConstantTimeLess};
+
+use crate::build_info::build_info;
+use crate::json;
+use crate::report::Format;
+use crate::run::{EXIT_DIVERGED, EXIT_USAGE};
+
+/// Mismatches kept per suite for the report.
+pub const MAX_REPORTED: usize = 16;
+
+/// Options for the `verify-ct` command.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyOptions {
+    /// Check every pair of values in the lowest and highest `2^window_bits`.
+    pub window_bits: u32,
+    /// Random pairs per strategy.
+    pub random: u64,
+    pub seed: u64,
+    /// Number of ChaCha8 seeds whose first draw is used as `special`.
+    pub specials: u64,
+    pub format: Format,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            window_bits: 10,
+            random: 1 << 18,
+            seed: 1,
+            specials: 16,
+            format: Format::Text,
+        }
+    }
+}
+
+/// A comparison that disagreed with its native counterpart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mismatch {
+    pub op: &'static str,
+    pub a: String,
+    pub b: String,
+    pub got: bool,
+    pub expected: bool,
+}
+
+impl Mismatch {
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .str("op", self.op)
+            .str("a", &self.a)
+            .str("b", &self.b)
+            .bool("got", self.got)
+            .bool("expected", self.expected)
+            .finish()
+    }
+}
+
+/// Results of checking one width against one native type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Suite {
+    pub name: &'static str,
+    pub pairs: u64,
+    pub mismatches: u64,
+    /// The first [`MAX_REPORTED`] mismatches.
+    pub reported: Vec<Mismatch>,
+}
+
+impl Suite {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ..Self::default()
+        }
+    }
+
+    /// Checks `ct_lt`, `ct_gt` and `ct_eq` on `(a, b)` against native `<`,
+    /// `>` and `==`.
+    fn check<const L: usize, T>(&mut self, a: T, b: T, to_uint: fn(T) -> Uint<L>)
+    where
+        T: Copy + Ord + LowerHex,
+    {
+        let (x, y) = (to_uint(a), to_uint(b));
+        self.pairs += 1;
+        let checks = [
+            ("ct_lt", x.ct_lt(&y).into(), a < b),
+            ("ct_gt", x.ct_gt(&y).into(), a > b),
+            ("ct_eq", x.ct_eq(&y).into(), a == b),
+        ];
+        for (op, got, expected) in checks {
+            if got == expected {
+                continue;
+            }
+            self.mismatches += 1;
+            if self.reported.len() < MAX_REPORTED {
+                self.reported.push(Mismatch {
+                    op,
+                    a: format!("{a:#x}"),
+                    b: format!("{b:#x}"),
+                    got,
+                    expected,
+                });
+            }
+        }
+    }
+
+    fn check_all<const L: usize, T>(&mut self, values: &[T], to_uint: fn(T) -> Uint<L>)
+    where
+        T: Copy + Ord + LowerHex,
+    {
+        for &a in values {
+            for &b in values {
+                self.check(a, b, to_uint);
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        json::Object::new()
+            .str("name", self.name)
+            .u64("pairs", self.pairs)
+            .u64("mismatches", self.mismatches)
+            .raw(
+                "reported",
+                &json::array(self.reported.iter().map(Mismatch::to_json)),
+            )
+            .finish()
+    }
+}
+
+fn uint1(a: u64) -> Uint<1> {
+    Uint::from_words([a as Word])
+}
+
+fn uint2(a: u128) -> Uint<2> {
+    Uint::from_words([a as Word, (a >> 64) as Word])
+}
+
+/// The `special` values of the original scenario for the first `count` seeds.
+fn specials(count: u64) -> Vec<u64> {
+    (0..count)
+        .map(|seed| ChaCha8Rng::seed_from_u64(seed).next_u64())
+        .collect()
+}
+
+/// Values interesting for a single 64-bit limb.
+fn limb_edges() -> Vec<u64> {
+    vec![
+        0,
+        1,
+        2,
+        (1 << 32) - 1,
+        1 << 32,
+        (1 << 32) + 1,
+        (1 << 63) - 1,
+        1 << 63,
+        (1 << 63) + 1,
+        u64::MAX - 1,
+        u64::MAX,
+    ]
+}
+
+/// `Uint<1>` against `u64`.
+pub fn verify_u64(opts: &VerifyOptions) -> Suite {
+    let mut suite = Suite::new("Uint<1> vs u64");
+
+    let window = 1u64 << opts.window_bits;
+    let low: Vec<u64> = (0..window).collect();
+    let high: Vec<u64> = (0..window).map(|i| u64::MAX - i).collect();
+    suite.check_all(&low, uint1);
+    suite.check_all(&high, uint1);
+
+    let mut edges = limb_edges();
+    for special in specials(opts.specials) {
+        let n = 0u64.wrapping_sub(special);
+        edges.extend([n.wrapping_sub(1), n, n.wrapping_add(1)]);
+    }
+    suite.check_all(&edges, uint1);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(opts.seed);
+    for _ in 0..opts.random {
+        let (a, b) = (rng.next_u64(), rng.next_u64());
+        suite.check(a, b, uint1);
+        suite.check(a, a ^ (1 << (b % 64)), uint1);
+        suite.check(a, a, uint1);
+    }
+    suite
+}
+
+/// `Uint<2>` against `u128`, concentrating on the limb boundary.
+pub fn verify_u128(opts: &VerifyOptions) -> Suite {
+    let mut suite = Suite::new("Uint<2> vs u128");
+
+    let window = 1u128 << opts.window_bits;
+    let low: Vec<u128> = (0..window).collect();
+    let high: Vec<u128> = (0..window).map(|i| u128::MAX - i).collect();
+    let boundary: Vec<u128> = (0..window).map(|i| (1 << 64) - window / 2 + i).collect();
+    suite.check_all(&low, uint2);
+    suite.check_all(&high, uint2);
+    suite.check_all(&boundary, uint2);
+
+    let limbs = limb_edges();
+    let mut edges: Vec<u128> = limbs
+        .iter()
+        .flat_map(|&hi| limbs.iter().map(move |&lo| (hi as u128) << 64 | lo as u128))
+        .collect();
+    for special in specials(opts.specials) {
+        let n = 0u128.wrapping_sub(special as u128);
+        edges.extend([n.wrapping_sub(1), n, n.wrapping_add(1)]);
+        // Equal high limb, low limb on either side of n's.
+        edges.extend([n ^ 1, n & !(u64::MAX as u128), n | u64::MAX as u128]);
+    }
+    suite.check_all(&edges, uint2);
+
+    let mut rng = ChaCha8Rng::seed_from_u64(opts.seed);
+    let mut next = || (rng.next_u64() as u128) << 64 | rng.next_u64() as u128;
+    for _ in 0..opts.random {
+        let (a, b) = (next(), next());
+        suite.check(a, b, uint2);
+        // Same high limb, so the decision rests on the low limb's borrow.
+        suite.check(a, (a >> 64) << 64 | b as u64 as u128, uint2);
+        // Same low limb.
+        suite.check(a, (b >> 64) << 64 | a as u64 as u128, uint2);
+        suite.check(a, a ^ (1 << (b % 128)), uint2);
+    }
+    suite
+}
+
+/// Runs every suite and prints a report, returning the process exit code.
+pub fn verify(opts: &VerifyOptions) -> i32 {
+    if Limb::BITS != 64 {
+        eprintln!(
+            "verify-ct assumes 64-bit limbs; this target has {}-bit limbs",
+            Limb::BITS
+        );
+        return EXIT_USAGE;
+    }
+    let suites = [verify_u64(opts), verify_u128(opts)];
+
+    match opts.format {
+        Format::Text => {
+            for suite in &suites {
+                println!(
+                    "{}: {} pairs, {} mismatches",
+                    suite.name, suite.pairs, suite.mismatches
+                );
+                for m in &suite.reported {
+                    println!(
+                        "  {}({}, {}) = {}, expected {}",
+                        m.op, m.a, m.b, m.got, m.expected
+                    );
+                }
+            }
+        }
+        Format::Json => {
+            let report = json::Object::new()
+                .raw("build", &build_info().to_json())
+                .raw("suites", &json::array(suites.iter().map(Suite::to_json)))
+                .finish();
+            println!("{report}");
+        }
+        Format::Ndjson => {
+            for suite in &suites {
+                println!("{}", suite.to_json());
+            }
+        }
+    }
+
+    if suites.iter().any(|suite| suite.mismatches > 0) {
+        EXIT_DIVERGED
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_window_has_no_mismatches() {
+        let opts = VerifyOptions {
+            window_bits: 2,
+            random: 16,
+            specials: 2,
+            ..VerifyOptions::default()
+        };
+        let (u64s, u128s) = (verify_u64(&opts), verify_u128(&opts));
+        // Two windows of 4 values, 11 limb edges and 3 per special, squared,
+        // and 3 checks per random pair.
+        assert_eq!(u64s.pairs, 2 * 4 * 4 + 17 * 17 + 3 * 16);
+        // Three windows, 11 * 11 limb pairs and 6 per special, squared, and
+        // 4 checks per random pair.
+        assert_eq!(u128s.pairs, 3 * 4 * 4 + 133 * 133 + 4 * 16);
+        for suite in [u64s, u128s] {
+            assert_eq!(suite.mismatches, 0, "{suite:?}");
+            assert!(suite.reported.is_empty());
+        }
+    }
+}
@@ -0,0 +1,327 @@
+//! Wall-clock watchdog that explains a stalled sampler before killing it.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, mpsc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crypto_bigint::{Limb, RandomBits, Uint};
+use rand_core::{Error, RngCore};
+use subtle::Choice;
+
+use crate::{Observer, SamplingError, hex};
+
+/// Process exit code used when the watchdog fires.
+pub const EXIT_STALLED: i32 = 3;
+
+/// Number of most recent candidates kept for the stall report.
+pub const RECENT: usize = 8;
+
+/// Sampler progress shared between the sampling thread and the watchdog.
+///
+/// An observed sampler reports every candidate; the plain loop is left alone
+/// and only its generator calls are seen, through [`count_draws`], which keeps
+/// enough of their output to rebuild the last candidates.
+///
+/// [`count_draws`]: Self::count_draws
+#[derive(Debug)]
+pub struct Progress<const L: usize> {
+    attempts: AtomicU64,
+    draws: AtomicU64,
+    /// The output of the last generator calls, indexed by call number modulo
+    /// the length.
+    words: Box<[AtomicU64]>,
+    n_bits: u32,
+    /// Generator calls per candidate: `random_bits` makes one per limb.
+    draws_per_candidate: u64,
+    recent: Mutex<VecDeque<(Uint<L>, bool)>>,
+}
+
+impl<const L: usize> Progress<L> {
+    /// Progress of sampling below `n`.
+    pub fn new(n: &Uint<L>) -> Self {
+        let n_bits = n.bits_vartime();
+        let draws_per_candidate = u64::from(n_bits.div_ceil(Limb::BITS)).max(1);
+        // Room for the candidates reported and the one still being compared,
+        // with slack for the sampler running on while the watchdog reads.
+        let words = 4 * (RECENT + 1) * draws_per_candidate as usize;
+        Self {
+            attempts: AtomicU64::new(0),
+            draws: AtomicU64::new(0),
+            words: (0..words).map(|_| AtomicU64::new(0)).collect(),
+            n_bits,
+            draws_per_candidate,
+            recent: Mutex::default(),
+        }
+    }
+
+    /// Candidates drawn, as observed or as counted from generator calls.
+    pub fn attempts(&self) -> u64 {
+        let counted = self.draws.load(Ordering::Relaxed) / self.draws_per_candidate;
+        self.attempts.load(Ordering::Relaxed).max(counted)
+    }
+
+    /// Wraps `rng` so the watchdog can count its calls without the sampler
+    /// reporting anything.
+    pub fn count_draws<R: RngCore>(&self, rng: R) -> CountingRng<'_, R> {
+        CountingRng {
+            rng,
+            draws: &self.draws,
+            words: &self.words,
+        }
+    }
+
+    /// The last [`RECENT`] candidates and their `ct_lt` results, oldest first.
+    pub fn recent(&self) -> Vec<(Uint<L>, bool)> {
+        let recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        if !recent.is_empty() {
+            return recent.iter().copied().collect();
+        }
+        drop(recent);
+        self.recent_drawn()
+    }
+
+    /// Rebuilds the last candidates of an unobserved loop from its generator
+    /// output. Each was followed by another draw, so `ct_lt` rejected it; the
+    /// newest, whose comparison may still be running, is left out.
+    fn recent_drawn(&self) -> Vec<(Uint<L>, bool)> {
+        let per = self.draws_per_candidate;
+        let ring = self.words.len() as u64;
+        // The sampler keeps drawing while this reads, so only the slots it
+        // cannot have overwritten in the meantime are kept.
+        for _ in 0..4 {
+            let end = self.draws.load(Ordering::Acquire);
+            let words: Vec<u64> = self
+                .words
+                .iter()
+                .map(|w| w.load(Ordering::Relaxed))
+                .collect();
+            atomic::fence(Ordering::Acquire);
+            let start = (self.draws.load(Ordering::Relaxed) + 1).saturating_sub(ring);
+            // Candidates drawn whole in `start..end` and followed by a draw.
+            let first = start.div_ceil(per);
+            let last = end.saturating_sub(1) / per;
+            if first + (RECENT as u64).min(last) > last {
+                continue;
+            }
+            return (last.saturating_sub(RECENT as u64).max(first)..last)
+                .map(|k| {
+                    let mut replay = Replay {
+                        words: &words,
+                        next: k * per,
+                    };
+                    (Uint::random_bits(&mut replay, self.n_bits), false)
+                })
+                .collect();
+        }
+        Vec::new()
+    }
+}
+
+impl<const L: usize> Observer<L> for &Progress<L> {
+    fn observe(
+        &mut self,
+        attempt: u64,
+        x: &Uint<L>,
+        _: &Uint<L>,
+        lt: Choice,
+    ) -> Result<(), SamplingError<L>> {
+        self.attempts.store(attempt + 1, Ordering::Relaxed);
+        let mut recent = self.recent.lock().unwrap_or_else(PoisonError::into_inner);
+        if recent.len() == RECENT {
+            recent.pop_front();
+        }
+        recent.push_back((*x, lt.into()));
+        Ok(())
+    }
+}
+
+/// A generator that bumps a shared counter on every call and keeps the first
+/// eight bytes of its output in a ring.
+///
+/// The counter has a single writer, so a plain load and store is enough and
+/// the sampling loop gains no atomic read-modify-write.
+#[derive(Debug)]
+pub struct CountingRng<'a, R> {
+    rng: R,
+    draws: &'a AtomicU64,
+    words: &'a [AtomicU64],
+}
+
+impl<R> CountingRng<'_, R> {
+    fn bump(&self, output: &[u8]) {
+        let mut word = [0; 8];
+        let len = output.len().min(8);
+        word[..len].copy_from_slice(&output[..len]);
+        let draws = self.draws.load(Ordering::Relaxed);
+        let slot = (draws % self.words.len() as u64) as usize;
+        // Release, so a reader that sees this word also sees the count that
+        // came before it and can tell the slot was overwritten.
+        self.words[slot].store(u64::from_ne_bytes(word), Ordering::Release);
+        self.draws.store(draws + 1, Ordering::Release);
+    }
+}
+
+impl<R: RngCore> RngCore for CountingRng<'_, R> {
+    fn next_u32(&mut self) -> u32 {
+        let value = self.rng.next_u32();
+        self.bump(&u64::from(value).to_ne_bytes());
+        value
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let value = self.rng.next_u64();
+        self.bump(&value.to_ne_bytes());
+        value
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest);
+        self.bump(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        let result = self.rng.try_fill_bytes(dest);
+        self.bump(dest);
+        result
+    }
+}
+
+/// Serves the words a [`CountingRng`] kept, from call number `next` on.
+struct Replay<'a> {
+    words: &'a [u64],
+    next: u64,
+}
+
+impl Replay<'_> {
+    fn word(&mut self) -> u64 {
+        let word = self.words[(self.next % self.words.len() as u64) as usize];
+        self.next += 1;
+        word
+    }
+}
+
+impl RngCore for Replay<'_> {
+    fn next_u32(&mut self) -> u32 {
+        self.word() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.word()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let word = self.word().to_ne_bytes();
+        let len = dest.len().min(8);
+        dest[..len].copy_from_slice(&word[..len]);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Extra reporting to do when the watchdog fires.
+pub type OnStall<const L: usize> = Box<dyn FnOnce(&Progress<L>) + Send>;
+
+/// The inputs a stall report is about.
+pub struct Context<const L: usize> {
+    pub seed: u64,
+    pub modulus: Uint<L>,
+    /// Called after the stderr report, before exiting.
+    pub on_stall: Option<OnStall<L>>,
+}
+
+/// A running watchdog; dropping it stops it.
+#[derive(Debug)]
+pub struct Watchdog {
+    done: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Starts a thread that, unless disarmed within `deadline`, prints a stall
+    /// report to stderr and exits the process with [`EXIT_STALLED`].
+    pub fn spawn<const L: usize>(
+        deadline: Duration,
+        context: Context<L>,
+        progress: Arc<Progress<L>>,
+    ) -> Self {
+        let (done, rx) = mpsc::channel::<()>();
+        let handle = thread::spawn(move || {
+            if let Err(mpsc::RecvTimeoutError::Timeout) = rx.recv_timeout(deadline) {
+                report(deadline, &context, &progress);
+                if let Some(on_stall) = context.on_stall {
+                    on_stall(&progress);
+                }
+                std::process::exit(EXIT_STALLED);
+            }
+        });
+        Self {
+            done: Some(done),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        drop(self.done.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn report<const L: usize>(deadline: Duration, context: &Context<L>, progress: &Progress<L>) {
+    let n = &context.modulus;
+    eprintln!("watchdog: sampler stalled for {deadline:?}");
+    eprintln!("  seed:       {}", context.seed);
+    eprintln!("  modulus:    {}", hex::encode(n));
+    eprintln!("  n bits:     {}", n.bits_vartime());
+    eprintln!("  candidates: {}", progress.attempts());
+    let recent = progress.recent();
+    if recent.is_empty() {
+        eprintln!("  recent candidates: none drawn yet");
+        return;
+    }
+    eprintln!("  recent candidates (oldest first):");
+    for (x, lt) in recent {
+        eprintln!("    {} ct_lt={lt}", hex::encode(&x));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_chacha::ChaCha8Rng;
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn rebuilds_rejected_candidates_from_draws() {
+        let n = Uint::<2>::from_words([0, 1 << 40]);
+        let progress = Progress::new(&n);
+        let mut rng = progress.count_draws(ChaCha8Rng::seed_from_u64(1));
+        let drawn: Vec<Uint<2>> = (0..20)
+            .map(|_| Uint::random_bits(&mut rng, n.bits_vartime()))
+            .collect();
+        let rejected = drawn[19 - RECENT..19].iter().map(|&x| (x, false));
+        assert_eq!(progress.recent(), rejected.collect::<Vec<_>>());
+        assert_eq!(progress.attempts(), 20);
+    }
+
+    #[test]
+    fn leaves_out_a_candidate_not_yet_followed() {
+        let n = Uint::<1>::from_u64(1000);
+        let progress = Progress::new(&n);
+        let mut rng = progress.count_draws(ChaCha8Rng::seed_from_u64(1));
+        assert!(progress.recent().is_empty());
+        let x = Uint::<1>::random_bits(&mut rng, n.bits_vartime());
+        assert!(progress.recent().is_empty());
+        let _ = Uint::<1>::random_bits(&mut rng, n.bits_vartime());
+        assert_eq!(progress.recent(), [(x, false)]);
+    }
+}
@@ -0,0 +1,65 @@
+_ZN12subtle_repro10random_mod17hE:
+	.cfi_startproc
+	sub	sp, sp, #96
+	stp	x29, x30, [sp, #32]
+	stp	x24, x23, [sp, #48]
+	stp	x22, x21, [sp, #64]
+	stp	x20, x19, [sp, #80]
+	add	x29, sp, #32
+	.cfi_def_cfa w29, 64
+	mov	x19, x8
+	mov	x20, x0
+	ldp	x21, x22, [x1]
+	cmp	x22, #0
+	csel	x8, x21, x22, eq
+	clz	x8, x8
+	mov	w9, #128
+	sub	w9, w9, w8
+	mov	w10, #64
+	sub	w10, w10, w8
+	csel	w23, w10, w9, eq
+	stp	xzr, xzr, [sp]
+	add	x8, sp, #16
+	mov	x1, sp
+	mov	w2, #2
+	mov	w3, w23
+	bl	_ZN13crypto_bigint4uint4rand16random_bits_core17hE
+	ldr	w8, [sp, #16]
+	cmp	w8, #3
+	b.ne	.LBB1_5
+	mov	x24, sp
+.LBB1_2:
+	ldp	x8, x9, [sp]
+	cmp	x21, x8
+	cset	w10, hi
+	subs	x11, x9, x22
+	ngc	x12, xzr
+	cmp	x11, x10
+	sbc	x12, x12, xzr
+	and	w0, w12, #0x1
+	bl	_ZN6subtle9black_box17hE
+	tbnz	w0, #0, .LBB1_6
+	stp	xzr, xzr, [sp]
+	add	x8, sp, #16
+	mov	x0, x20
+	mov	x1, x24
+	mov	w2, #2
+	mov	w3, w23
+	bl	_ZN13crypto_bigint4uint4rand16random_bits_core17hE
+	ldr	w8, [sp, #16]
+	cmp	w8, #3
+	b.eq	.LBB1_2
+.LBB1_5:
+	adrp	x0, .Lanon.unwrap_failed
+	add	x0, x0, :lo12:.Lanon.unwrap_failed
+	bl	_RNvNtCsgEmfK2I1SDS_4core6result13unwrap_failed
+.LBB1_6:
+	ldp	x8, x9, [sp]
+	stp	x8, x9, [x19]
+	ldp	x20, x19, [sp, #80]
+	ldp	x22, x21, [sp, #64]
+	ldp	x24, x23, [sp, #48]
+	ldp	x29, x30, [sp, #32]
+	add	sp, sp, #96
+	ret
+.Lfunc_end1:
@@ -0,0 +1,26 @@
+_ZN12subtle_repro6shapes5Shape10random_mod17hE:
+	movq	_ZN12subtle_repro6shapes13accepts_never17hE@GOTPCREL(%rip), %r15
+.LBB21_48:
+	xorps	%xmm0, %xmm0
+	movaps	%xmm0, (%rsp)
+	leaq	52(%rsp), %rdi
+	movq	72(%rsp), %rsi
+	movq	%r13, %rdx
+	movl	%ebp, %r8d
+	callq	_ZN13crypto_bigint4uint4rand16random_bits_core17hE
+	movl	52(%rsp), %eax
+	cmpl	$3, %eax
+	jne	.LBB21_58
+	movq	(%rsp), %rax
+	movq	96(%rsp), %rdi
+	movq	%rax, (%rdi)
+	movq	%r12, %rsi
+	callq	*%r15
+	testb	%al, %al
+	je	.LBB21_48
+	jmp	.LBB21_69
+.LBB21_58:
+	ud2
+.LBB21_69:
+	retq
+.Lfunc_end21:
@@ -0,0 +1,117 @@
+_ZN12subtle_repro10random_mod17hE:
+	.cfi_startproc
+	pushq	%rbp
+	.cfi_def_cfa_offset 16
+	pushq	%r15
+	.cfi_def_cfa_offset 24
+	pushq	%r14
+	.cfi_def_cfa_offset 32
+	pushq	%r13
+	.cfi_def_cfa_offset 40
+	pushq	%r12
+	.cfi_def_cfa_offset 48
+	pushq	%rbx
+	.cfi_def_cfa_offset 56
+	subq	$72, %rsp
+	.cfi_def_cfa_offset 128
+	.cfi_offset %rbx, -56
+	.cfi_offset %r12, -48
+	.cfi_offset %r13, -40
+	.cfi_offset %r14, -32
+	.cfi_offset %r15, -24
+	.cfi_offset %rbp, -16
+	movq	%rsi, %r14
+	movq	(%rdx), %r13
+	movq	8(%rdx), %rbx
+	xorl	%ebp, %ebp
+	testq	%rbx, %rbx
+	setne	%bpl
+	movq	%rbx, %rax
+	cmoveq	%r13, %rax
+	shll	$6, %ebp
+	movl	$127, %ecx
+	bsrq	%rax, %rcx
+	xorl	$63, %ecx
+	subl	%ecx, %ebp
+	addl	$64, %ebp
+	movq	%rdi, 24(%rsp)
+	movq	8(%rdi), %r15
+	pxor	%xmm0, %xmm0
+	movdqa	%xmm0, (%rsp)
+	leaq	36(%rsp), %rdi
+	movq	%rsp, %rdx
+	movl	$2, %ecx
+	movl	%ebp, %r8d
+	callq	_ZN13crypto_bigint4uint4rand16random_bits_core17hE
+	movl	36(%rsp), %eax
+	cmpl	$3, %eax
+	jne	.LBB1_5
+	movq	%rsp, %r12
+	.p2align	4
+.LBB1_2:
+	movq	(%rsp), %rax
+	movq	8(%rsp), %r15
+	movq	%rax, %xmm0
+	movdqa	%xmm0, 48(%rsp)
+	xorl	%ecx, %ecx
+	cmpq	%rax, %r13
+	seta	%cl
+	movq	%r15, %rax
+	subq	%rbx, %rax
+	movl	$0, %edx
+	sbbq	%rdx, %rdx
+	cmpq	%rcx, %rax
+	sbbq	$0, %rdx
+	andb	$1, %dl
+	movzbl	%dl, %edi
+	callq	*_ZN6subtle9black_box17hE@GOTPCREL(%rip)
+	testb	%al, %al
+	jne	.LBB1_6
+	pxor	%xmm0, %xmm0
+	movdqa	%xmm0, (%rsp)
+	movl	$2, %ecx
+	leaq	36(%rsp), %rdi
+	movq	%r14, %rsi
+	movq	%r12, %rdx
+	movl	%ebp, %r8d
+	callq	_ZN13crypto_bigint4uint4rand16random_bits_core17hE
+	movl	36(%rsp), %eax
+	cmpl	$3, %eax
+	je	.LBB1_2
+	movq	24(%rsp), %rcx
+	movdqa	48(%rsp), %xmm0
+	movq	%xmm0, (%rcx)
+.LBB1_5:
+	movq	24(%rsp), %rcx
+	movq	%r15, 8(%rcx)
+	movq	40(%rsp), %rcx
+	movl	%eax, (%rsp)
+	movq	%rcx, 4(%rsp)
+	leaq	alloc_c446d585e5958b35032cd6b060454c2b.llvm.6285740776452898810(%rip), %rdi
+	leaq	vtable.0.llvm.6285740776452898810(%rip), %rcx
+	leaq	alloc_fae3eb549dae92653c65723cc41acfe4.llvm.6285740776452898810(%rip), %r8
+	movq	%rsp, %rdx
+	movl	$24, %esi
+	callq	*_RNvNtCsgEmfK2I1SDS_4core6result13unwrap_failed@GOTPCREL(%rip)
+.LBB1_6:
+	movq	24(%rsp), %rax
+	movaps	48(%rsp), %xmm0
+	movlps	%xmm0, (%rax)
+	movq	%r15, 8(%rax)
+	addq	$72, %rsp
+	.cfi_def_cfa_offset 56
+	popq	%rbx
+	.cfi_def_cfa_offset 48
+	popq	%r12
+	.cfi_def_cfa_offset 40
+	popq	%r13
+	.cfi_def_cfa_offset 32
+	popq	%r14
+	.cfi_def_cfa_offset 24
+	popq	%r15
+	.cfi_def_cfa_offset 16
+	popq	%rbp
+	.cfi_def_cfa_offset 8
+	retq
+.Lfunc_end1:
+
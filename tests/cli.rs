@@ -0,0 +1,19 @@
+//! The binary's exit status on a malformed command line.
+
+use std::process::Command;
+
+use subtle_repro::run::EXIT_USAGE;
+
+#[test]
+fn unknown_flag_exits_with_usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_subtle-repro"))
+        .args(["run", "--bogus"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(EXIT_USAGE));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("error: unknown option \"--bogus\""),
+        "{stderr}"
+    );
+}